@@ -3,7 +3,9 @@
 use toml::Value as TomlValue;
 
 use crate::{
-    load::Load,
+    config::ExtTomlValueLoadPrimitives,
+    error::Result,
+    load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
     types::float::float,
 };
@@ -16,6 +18,11 @@ pub struct Antispikes {
 }
 
 impl Antispikes {
+    #[allow(dead_code)] // for tests
+    pub const fn new(antispikes_type: AntispikesType, antispikes_k: float) -> Self {
+        Self { antispikes_type, antispikes_k }
+    }
+
     pub fn calc(&self, points_1: &Vec<float>, points_2: &Vec<float>) -> float {
         self.antispikes_k * self.antispikes_type.calc(points_1, points_2)
     }
@@ -62,8 +69,25 @@ impl AntispikesType {
 
 impl Load for Antispikes {
     const TOML_NAME: &'static str = "antispikes";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        todo!()
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            antispikes_type: AntispikesType::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            antispikes_k: toml_value.load_float("antispikes_k", stacktrace)?,
+        })
+    }
+}
+
+impl Load for AntispikesType {
+    const TOML_NAME: &'static str = "antispikes_type";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let antispikes_type_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match antispikes_type_str {
+            "DySqr" | "dy_sqr" => Self::DySqr,
+            "DyAbs" | "dy_abs" => Self::DyAbs,
+            _ => return Err(stacktrace.err_unknown_type(antispikes_type_str, ["DySqr", "dy_sqr", "DyAbs", "dy_abs"]))
+        })
     }
 }
 
@@ -0,0 +1,261 @@
+//! Command line interface.
+
+use std::path::PathBuf;
+
+use crate::config::ConfigOverride;
+
+
+pub const USAGE: &str = "\
+Usage:
+    deconvolution-rs [fit] [OPTIONS] <INSTRUMENT> <MEASURED>...
+    deconvolution-rs convolve [OPTIONS] <INSTRUMENT> <MEASURED>...
+    deconvolution-rs check-config [OPTIONS]
+
+Subcommands:
+    fit             deconvolve each measured spectrum (default),
+                    or all of them together, if `[global_fit]` is in config
+    convolve        only convolve instrument with function from config at it's initial values,
+                    measured spectra are used just for x grid and fit residue
+    check-config    load config, print it and exit
+
+Options:
+    -c, --config <PATH>         config file [default: config.toml]
+    -o, --output-dir <PATH>     directory for output files [default: near measured files]
+    -s, --set <KEY=VALUE>       override config value, e.g. `fit_algorithm.pattern_search.alpha=1.2`
+    -q, --quiet                 print only errors
+    -v, --verbose               print more details
+    -h, --help                  print this help";
+
+const CONFIG_FILEPATH_DEFAULT: &str = "config.toml";
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliArgs {
+    pub subcommand: Subcommand,
+    pub config_filepath: String,
+    pub config_overrides: Vec<ConfigOverride>,
+    pub output_dir: Option<PathBuf>,
+    pub verbosity: Verbosity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subcommand {
+    Fit { filepathstr_instrument: String, filepathstrs_measured: Vec<String> },
+    Convolve { filepathstr_instrument: String, filepathstrs_measured: Vec<String> },
+    CheckConfig,
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn is_normal_or_more(&self) -> bool {
+        *self >= Self::Normal
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        *self >= Self::Verbose
+    }
+}
+
+
+impl CliArgs {
+    /// Parse CLI args (without program name).
+    ///
+    /// Returns `Err` with message, which should be printed along with [`USAGE`].
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut config_filepath: Option<String> = None;
+        let mut config_overrides: Vec<ConfigOverride> = vec![];
+        let mut output_dir: Option<PathBuf> = None;
+        let mut verbosity: Option<Verbosity> = None;
+        let mut positionals: Vec<String> = vec![];
+        let mut is_help: bool = false;
+
+        let mut args_iter = args.iter();
+        let mut is_options_ended: bool = false;
+        while let Some(arg) = args_iter.next() {
+            if is_options_ended || !arg.starts_with('-') || arg == "-" {
+                positionals.push(arg.clone());
+                continue;
+            }
+            // `--name=value` or `--name value`:
+            let (name, value_inline): (&str, Option<&str>) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name, Some(value)),
+                _ => (arg, None),
+            };
+            let mut get_value = || -> Result<String, String> {
+                match value_inline {
+                    Some(value) => Ok(value.to_string()),
+                    None => args_iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("option `{name}` requires a value")),
+                }
+            };
+            let set_verbosity = |verbosity: &mut Option<Verbosity>, verbosity_new: Verbosity| -> Result<(), String> {
+                match verbosity {
+                    Some(verbosity_old) if *verbosity_old != verbosity_new => Err("`--quiet` and `--verbose` can't be used together".to_string()),
+                    _ => { *verbosity = Some(verbosity_new); Ok(()) }
+                }
+            };
+            match name {
+                "--" => { is_options_ended = true }
+                "-h" | "--help" => { is_help = true }
+                "-c" | "--config" => {
+                    if config_filepath.is_some() { return Err(format!("option `{name}` provided more than once")) }
+                    config_filepath = Some(get_value()?);
+                }
+                "-o" | "--output-dir" => {
+                    if output_dir.is_some() { return Err(format!("option `{name}` provided more than once")) }
+                    output_dir = Some(PathBuf::from(get_value()?));
+                }
+                "-s" | "--set" => {
+                    config_overrides.push(ConfigOverride::parse(&get_value()?)?);
+                }
+                "-q" | "--quiet" => set_verbosity(&mut verbosity, Verbosity::Quiet)?,
+                "-v" | "--verbose" => set_verbosity(&mut verbosity, Verbosity::Verbose)?,
+                _ => return Err(format!("unknown option `{name}`")),
+            }
+            if value_inline.is_some() && !matches!(name, "-c" | "--config" | "-o" | "--output-dir" | "-s" | "--set") {
+                return Err(format!("option `{name}` doesn't take a value"));
+            }
+        }
+
+        let subcommand: Subcommand = if is_help {
+            Subcommand::Help
+        } else {
+            let (subcommand_name, files): (&str, &[String]) = match positionals.first().map(|s| s.as_str()) {
+                Some(name @ ("fit" | "convolve" | "check-config")) => (name, &positionals[1..]),
+                _ => ("fit", &positionals),
+            };
+            match (subcommand_name, files) {
+                ("check-config", []) => Subcommand::CheckConfig,
+                ("check-config", [..]) => return Err("`check-config` doesn't take files".to_string()),
+                (_, []) => return Err("expected at least two filepaths (instrumental & measured), provided zero".to_string()),
+                (_, [_]) => return Err("expected at least two filepaths (instrumental & measured), provided only one".to_string()),
+                (name, [filepathstr_instrument, filepathstrs_measured @ ..]) => {
+                    let filepathstr_instrument = filepathstr_instrument.clone();
+                    let filepathstrs_measured = filepathstrs_measured.to_vec();
+                    match name {
+                        "fit" => Subcommand::Fit { filepathstr_instrument, filepathstrs_measured },
+                        "convolve" => Subcommand::Convolve { filepathstr_instrument, filepathstrs_measured },
+                        _ => unreachable!()
+                    }
+                }
+            }
+        };
+
+        Ok(Self {
+            subcommand,
+            config_filepath: config_filepath.unwrap_or_else(|| CONFIG_FILEPATH_DEFAULT.to_string()),
+            config_overrides,
+            output_dir,
+            verbosity: verbosity.unwrap_or(Verbosity::Normal),
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod parse {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, String> {
+        CliArgs::parse(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    fn fit_a_bc() -> Subcommand {
+        Subcommand::Fit {
+            filepathstr_instrument: "a".to_string(),
+            filepathstrs_measured: vec!["b".to_string(), "c".to_string()],
+        }
+    }
+
+    #[test]
+    fn positional_only() {
+        assert_eq!(
+            Ok(CliArgs {
+                subcommand: fit_a_bc(),
+                config_filepath: "config.toml".to_string(),
+                config_overrides: vec![],
+                output_dir: None,
+                verbosity: Verbosity::Normal,
+            }),
+            parse(&["a", "b", "c"])
+        );
+    }
+
+    #[test]
+    fn fit_with_options() {
+        assert_eq!(
+            Ok(CliArgs {
+                subcommand: fit_a_bc(),
+                config_filepath: "my.toml".to_string(),
+                config_overrides: vec![
+                    ConfigOverride::parse("fit_algorithm.pattern_search.alpha=1.2").unwrap(),
+                    ConfigOverride::parse("output_params.significant_digits=3").unwrap(),
+                ],
+                output_dir: Some(PathBuf::from("out")),
+                verbosity: Verbosity::Verbose,
+            }),
+            parse(&[
+                "fit", "a",
+                "--config", "my.toml",
+                "-s", "fit_algorithm.pattern_search.alpha=1.2",
+                "--output-dir=out",
+                "b",
+                "--set=output_params.significant_digits=3",
+                "-v",
+                "c",
+            ])
+        );
+    }
+
+    #[test]
+    fn convolve() {
+        assert_eq!(
+            Subcommand::Convolve { filepathstr_instrument: "a".to_string(), filepathstrs_measured: vec!["b".to_string()] },
+            parse(&["convolve", "-q", "a", "b"]).unwrap().subcommand
+        );
+    }
+
+    #[test]
+    fn check_config() {
+        assert_eq!(Subcommand::CheckConfig, parse(&["check-config", "-c", "x.toml"]).unwrap().subcommand);
+        assert!(parse(&["check-config", "a"]).is_err());
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(Subcommand::Help, parse(&["--help"]).unwrap().subcommand);
+        assert_eq!(Subcommand::Help, parse(&["fit", "-h"]).unwrap().subcommand);
+    }
+
+    #[test]
+    fn files_after_double_dash() {
+        assert_eq!(
+            Subcommand::Fit { filepathstr_instrument: "-a".to_string(), filepathstrs_measured: vec!["--b".to_string()] },
+            parse(&["--", "-a", "--b"]).unwrap().subcommand
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a"]).is_err());
+        assert!(parse(&["convolve", "a"]).is_err());
+        assert!(parse(&["a", "b", "--unknown"]).is_err());
+        assert!(parse(&["a", "b", "--config"]).is_err());
+        assert!(parse(&["a", "b", "-c", "x", "-c", "y"]).is_err());
+        assert!(parse(&["a", "b", "-q", "-v"]).is_err());
+        assert!(parse(&["a", "b", "--quiet=1"]).is_err());
+        assert!(parse(&["a", "b", "--set", "no_equal_sign"]).is_err());
+        assert!(parse(&["a", "b", "--set", "=1"]).is_err());
+    }
+}
@@ -8,13 +8,27 @@ use toml::{
 };
 
 use crate::{
-    fit_algorithms::FitAlgorithmVariant,
+    error::{Error, Result},
+    fit_algorithms::{FitAlgorithmVariant, levenberg_marquardt::LevenbergMarquardt},
     load::{LoadAutoImplFns, Load},
+    result_file::ResultFormat,
     stacktrace::Stacktrace,
+    summary::SummaryFormat,
     types::float::float,
 };
 
-use super::deconvolution::{DeconvolutionVariant, deconvolution_data::AlignStepsTo};
+use super::deconvolution::{
+    DeconvolutionVariant,
+    add_ons::AddOns,
+    convolution::ConvolutionMethod,
+    deconvolution_data::{AlignStepsTo, FitMask, Weights},
+    irf_params::IrfParams,
+    types::{
+        fourier::Fourier,
+        lifetime_distribution::{LifetimeDistribution, LifetimeDistributionMethod},
+        richardson_lucy::RichardsonLucy,
+    },
+};
 
 
 
@@ -25,33 +39,138 @@ pub struct Config {
     pub input_params: ConfigInputParams,
     pub output_params: ConfigOutputParams,
     pub fit_algorithm: ConfigFitAlgorithmParams,
+    pub global_fit: Option<ConfigGlobalFit>,
 }
 impl Config {
-    pub fn load_from_default_file() -> Self {
-        Self::load_from_file("config.toml")
+    pub fn load_from_file_with_overrides(filepath: &str, config_overrides: &[ConfigOverride]) -> Result<Self> {
+        let text = read_file_to_string(filepath)
+            .map_err(|io_error| Error::ConfigRead { filepath: filepath.to_string(), io_error })?;
+        let toml_table = text.parse::<TomlTable>()
+            .map_err(|toml_error| Error::ConfigParse { filepath: filepath.to_string(), toml_error })?;
+        Self::load_from_toml_table_with_overrides(toml_table, config_overrides)
     }
-    pub fn load_from_file(filename: &str) -> Self {
-        let text = read_file_to_string(filename)
-            .expect("can't read config file");
-        Self::load_from_text(&text)
+    #[cfg(test)]
+    fn load_from_text(text: &str) -> Result<Self> {
+        Self::load_from_text_with_overrides(text, &[])
     }
-    fn load_from_text(text: &str) -> Self {
+    #[cfg(test)]
+    fn load_from_text_with_overrides(text: &str, config_overrides: &[ConfigOverride]) -> Result<Self> {
         let toml_table = text.parse::<TomlTable>()
-            .expect("can't parse text as toml table");
-        Self::load_from_toml_table(toml_table)
+            .map_err(|toml_error| Error::ConfigParse { filepath: "<text>".to_string(), toml_error })?;
+        Self::load_from_toml_table_with_overrides(toml_table, config_overrides)
     }
-    fn load_from_toml_table(toml_table: TomlTable) -> Self {
+    fn load_from_toml_table_with_overrides(mut toml_table: TomlTable, config_overrides: &[ConfigOverride]) -> Result<Self> {
+        for config_override in config_overrides {
+            config_override.apply_to(&mut toml_table)?;
+        }
         let toml_value: TomlValue = toml_table.into();
         Self::load_from_toml_value(&toml_value)
     }
-    fn load_from_toml_value(toml_value: &TomlValue) -> Self {
-        Self {
-            deconvolution_function: ConfigDeconvolutionFunc::load_from_parent_as_root(toml_value),
-            deconvolution_params: ConfigDeconvolutionParams::load_from_parent_as_root(toml_value),
-            input_params: ConfigInputParams::load_from_parent_as_root(toml_value),
-            output_params: ConfigOutputParams::load_from_parent_as_root(toml_value),
-            fit_algorithm: ConfigFitAlgorithmParams::load_from_parent_as_root(toml_value),
+    fn load_from_toml_value(toml_value: &TomlValue) -> Result<Self> {
+        let config = Self {
+            deconvolution_function: ConfigDeconvolutionFunc::load_from_parent_as_root(toml_value)?,
+            deconvolution_params: ConfigDeconvolutionParams::load_from_parent_as_root(toml_value)?,
+            input_params: ConfigInputParams::load_from_parent_as_root(toml_value)?,
+            output_params: ConfigOutputParams::load_from_parent_as_root(toml_value)?,
+            fit_algorithm: ConfigFitAlgorithmParams::load_from_parent_as_root(toml_value)?,
+            // optional, default is fit each measured spectrum separately
+            global_fit: toml_value
+                .get(ConfigGlobalFit::TOML_NAME)
+                .map(|global_fit| ConfigGlobalFit::load_from_self_handle_stacktrace(global_fit, &Stacktrace::empty()))
+                .transpose()?,
+        };
+        config.check_sections_consistency()?;
+        Ok(config)
+    }
+
+    /// Check values from different sections, which can't be used together.
+    fn check_sections_consistency(&self) -> Result<()> {
+        let stacktrace_input_params = Stacktrace::new(ConfigInputParams::TOML_NAME);
+        // they don't minimize residue, so there is nothing to apply weights and fit mask to:
+        let deconvolution_function_not_weighted: Option<&'static str> = match self.deconvolution_function {
+            DeconvolutionVariant::Fourier(..) => Some(Fourier::TOML_NAME),
+            DeconvolutionVariant::RichardsonLucy(..) => Some(RichardsonLucy::TOML_NAME),
+            _ => None,
+        };
+        if let Some(deconvolution_function_name) = deconvolution_function_not_weighted {
+            if self.input_params.weights != Weights::None {
+                return Err(stacktrace_input_params.pushed(Weights::TOML_NAME).err(&format!(
+                    "must be `none` for `{deconvolution_function_name}`, bc it uses all points with same weight"
+                )));
+            }
+            if self.input_params.fit_mask != FitMask::default() {
+                return Err(stacktrace_input_params.err(&format!(
+                    "`fit_window` and `fit_exclude` can't be used with `{deconvolution_function_name}`, bc it uses all points"
+                )));
+            }
+        }
+        // default target is `chi^2 / N = 1`, which is meaningless without sigmas:
+        if let DeconvolutionVariant::LifetimeDistribution(LifetimeDistribution {
+            method: LifetimeDistributionMethod::MaxEntropy { target_reduced_chi_square: None },
+            ..
+        }) = self.deconvolution_function {
+            if self.input_params.weights == Weights::None {
+                return Err(Stacktrace::new("deconvolution_function").pushed(LifetimeDistribution::TOML_NAME).err(
+                    "`target_reduced_chi_square` must be set for `method = \"max_entropy\"`, if `weights = \"none\"`"
+                ));
+            }
+        }
+        // it always minimizes sum of squared residuals, so other diff function would be silently ignored:
+        if let FitAlgorithmVariant::LevenbergMarquardt(..) = self.fit_algorithm {
+            if self.deconvolution_function.is_fitted() && !self.deconvolution_function.get_diff_function_type().is_least_squares() {
+                return Err(Stacktrace::new(ConfigFitAlgorithmParams::TOML_NAME).pushed(LevenbergMarquardt::TOML_NAME).err(
+                    "can be used only with `diff_function_type` = `DySqr` or `DySqrPerEl`, bc it minimizes sum of squared residuals"
+                ));
+            }
         }
+        Ok(())
+    }
+}
+
+
+/// Single config value override, like `fit_algorithm.pattern_search.alpha=1.2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+    keys: Vec<String>,
+    value: TomlValue,
+}
+impl ConfigOverride {
+    /// Parse from `key.subkey=value` string.
+    ///
+    /// `value` is parsed as toml value, and if it can't be parsed, it's treated as string,
+    /// so `initial_values=a=1, s=2` works same as `initial_values="a=1, s=2"`.
+    pub fn parse(key_value: &str) -> Result<Self, String> {
+        let (keys, value) = key_value
+            .split_once('=')
+            .ok_or_else(|| format!("can't parse config override `{key_value}`: expected `key=value`"))?;
+        let keys: Vec<String> = keys.trim().split('.').map(|key| key.trim().to_string()).collect();
+        if keys.iter().any(|key| key.is_empty()) {
+            return Err(format!("can't parse config override `{key_value}`: empty key"));
+        }
+        let value: TomlValue = format!("value = {value}")
+            .parse::<TomlTable>()
+            .ok()
+            .and_then(|mut toml_table| toml_table.remove("value"))
+            .unwrap_or_else(|| TomlValue::String(value.trim().to_string()));
+        Ok(Self { keys, value })
+    }
+
+    /// Set value, creating tables on the way, if they don't exist.
+    fn apply_to(&self, toml_table: &mut TomlTable) -> Result<()> {
+        let (key_last, keys_tables) = self.keys.split_last().unwrap();
+        let mut toml_table: &mut TomlTable = toml_table;
+        for (i, key) in keys_tables.iter().enumerate() {
+            toml_table = toml_table
+                .entry(key)
+                .or_insert_with(|| TomlValue::Table(TomlTable::new()))
+                .as_table_mut()
+                .ok_or_else(|| Error::ConfigValue {
+                    path: self.keys[..=i].iter().map(|key| format!("`{key}`")).collect::<Vec<_>>().join(" -> "),
+                    msg: "can't override config: isn't a table".to_string(),
+                })?;
+        }
+        toml_table.insert(key_last.clone(), self.value.clone());
+        Ok(())
     }
 }
 
@@ -63,15 +182,36 @@ pub struct ConfigDeconvolutionParams {
     pub try_randomized_initial_values: u64,
     pub initial_values_random_scale: float,
     pub print_only_better_deconvolution: bool,
+    pub convolution_method: ConvolutionMethod,
+    pub irf_params: IrfParams,
+    pub add_ons: AddOns,
 }
 impl Load for ConfigDeconvolutionParams {
     const TOML_NAME: &'static str = "deconvolution_params";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            try_randomized_initial_values: toml_value.load_u64("try_randomized_initial_values", stacktrace),
-            initial_values_random_scale: toml_value.load_float("initial_values_random_scale", stacktrace),
-            print_only_better_deconvolution: toml_value.load_bool("print_only_better_deconvolution", stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            try_randomized_initial_values: toml_value.load_u64("try_randomized_initial_values", stacktrace)?,
+            initial_values_random_scale: toml_value.load_float("initial_values_random_scale", stacktrace)?,
+            print_only_better_deconvolution: toml_value.load_bool("print_only_better_deconvolution", stacktrace)?,
+            // optional, default is `auto`
+            convolution_method: toml_value
+                .get(ConvolutionMethod::TOML_NAME)
+                .map(|convolution_method| ConvolutionMethod::load_from_self_handle_stacktrace(convolution_method, stacktrace))
+                .transpose()?
+                .unwrap_or(ConvolutionMethod::Auto),
+            // optional, default is no instrument params
+            irf_params: toml_value
+                .get(IrfParams::TOML_NAME)
+                .map(|irf_params| IrfParams::load_from_self_handle_stacktrace(irf_params, stacktrace))
+                .transpose()?
+                .unwrap_or_default(),
+            // optional, default is no add-ons
+            add_ons: toml_value
+                .get(AddOns::TOML_NAME)
+                .map(|add_ons| AddOns::load_from_self_handle_stacktrace(add_ons, stacktrace))
+                .transpose()?
+                .unwrap_or_default(),
+        })
     }
 }
 
@@ -79,81 +219,149 @@ impl Load for ConfigDeconvolutionParams {
 pub struct ConfigInputParams {
     pub align_step_to: AlignStepsTo,
     pub max_step_relative_diff: float,
+    pub weights: Weights,
+    pub fit_mask: FitMask,
 }
 impl Load for ConfigInputParams {
     const TOML_NAME: &'static str = "input_params";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            align_step_to: AlignStepsTo::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            max_step_relative_diff: toml_value.load_float("max_step_relative_diff", stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            align_step_to: AlignStepsTo::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            max_step_relative_diff: toml_value.load_float("max_step_relative_diff", stacktrace)?,
+            // optional, default is `none`
+            weights: toml_value
+                .get(Weights::TOML_NAME)
+                .map(|weights| Weights::load_from_self_handle_stacktrace(weights, stacktrace))
+                .transpose()?
+                .unwrap_or(Weights::None),
+            fit_mask: FitMask::load_from_input_params(toml_value, stacktrace)?,
+        })
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ConfigOutputParams {
     pub significant_digits: u8,
+    pub result_format: ResultFormat,
+    pub summary_format: SummaryFormat,
 }
 impl Load for ConfigOutputParams {
     const TOML_NAME: &'static str = "output_params";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        let significant_digits = toml_value.load_u8("significant_digits", stacktrace);
-        assert!(significant_digits < 20);
-        Self {
-            significant_digits,
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let significant_digits = toml_value.load_u8("significant_digits", stacktrace)?;
+        if significant_digits >= 20 {
+            return Err(stacktrace.pushed("significant_digits").err("must be less than 20"));
         }
+        Ok(Self {
+            significant_digits,
+            // optional, default is `txt`
+            result_format: toml_value
+                .get(ResultFormat::TOML_NAME)
+                .map(|result_format| ResultFormat::load_from_self_handle_stacktrace(result_format, stacktrace))
+                .transpose()?
+                .unwrap_or(ResultFormat::Txt),
+            // optional, default is `csv`
+            summary_format: toml_value
+                .get(SummaryFormat::TOML_NAME)
+                .map(|summary_format| SummaryFormat::load_from_self_handle_stacktrace(summary_format, stacktrace))
+                .transpose()?
+                .unwrap_or(SummaryFormat::Csv),
+        })
     }
 }
 
 type ConfigFitAlgorithmParams = FitAlgorithmVariant;
 
+/// If present, all measured spectra are fitted together, with some params shared between them.
+#[derive(Debug, PartialEq)]
+pub struct ConfigGlobalFit {
+    /// Params, which names starts with any of this, are shared, others are local for each measured spectrum.
+    pub shared_params: Vec<String>,
+}
+impl Load for ConfigGlobalFit {
+    const TOML_NAME: &'static str = "global_fit";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        const SHARED_PARAMS_NAME: &str = "shared_params";
+        // optional, default is `["tau"]`
+        let shared_params: Vec<String> = match toml_value.get(SHARED_PARAMS_NAME) {
+            None => vec!["tau".to_string()],
+            Some(shared_params) => {
+                let stacktrace = stacktrace.pushed(SHARED_PARAMS_NAME);
+                shared_params
+                    .as_array()
+                    .ok_or_else(|| stacktrace.err_cant_parse_as("array of strings"))?
+                    .iter()
+                    .map(|shared_param| {
+                        shared_param
+                            .as_str()
+                            .map(|shared_param| shared_param.to_string())
+                            .ok_or_else(|| stacktrace.err_cant_parse_as("array of strings"))
+                    })
+                    .collect::<Result<_>>()?
+            }
+        };
+        Ok(Self { shared_params })
+    }
+}
+
 
 
-trait ExtTomlValueLoadPrimitives {
-    fn load_float(&self, name: &'static str, stacktrace: &Stacktrace) -> float;
-    fn load_bool(&self, name: &'static str, stacktrace: &Stacktrace) -> bool;
-    fn load_u64(&self, name: &'static str, stacktrace: &Stacktrace) -> u64;
-    fn load_u8(&self, name: &'static str, stacktrace: &Stacktrace) -> u8;
+pub trait ExtTomlValueLoadPrimitives {
+    fn load_float(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<float>;
+    fn load_bool(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<bool>;
+    fn load_u64(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<u64>;
+    fn load_u8(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<u8>;
+    /// `None` if there is no `name`, else it's value, which must be non-negative.
+    fn load_optional_non_negative_float(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<Option<float>>;
 }
 impl ExtTomlValueLoadPrimitives for TomlValue {
-    fn load_float(&self, name: &'static str, stacktrace: &Stacktrace) -> float {
+    fn load_float(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<float> {
         let stacktrace = stacktrace.pushed(name);
         self
             .get(name)
-            .unwrap_or_else(|| stacktrace.panic_not_found())
+            .ok_or_else(|| stacktrace.err_not_found())?
             .as_float()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("float"))
+            .ok_or_else(|| stacktrace.err_cant_parse_as("float"))
     }
 
-    fn load_bool(&self, name: &'static str, stacktrace: &Stacktrace) -> bool {
+    fn load_bool(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<bool> {
         let stacktrace = stacktrace.pushed(name);
         self
             .get(name)
-            .unwrap_or_else(|| stacktrace.panic_not_found())
+            .ok_or_else(|| stacktrace.err_not_found())?
             .as_bool()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("bool"))
+            .ok_or_else(|| stacktrace.err_cant_parse_as("bool"))
     }
 
-    fn load_u64(&self, name: &'static str, stacktrace: &Stacktrace) -> u64 {
+    fn load_u64(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<u64> {
         let stacktrace = stacktrace.pushed(name);
         self
             .get(name)
-            .unwrap_or_else(|| stacktrace.panic_not_found())
+            .ok_or_else(|| stacktrace.err_not_found())?
             .as_integer()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("integer"))
+            .ok_or_else(|| stacktrace.err_cant_parse_as("integer"))?
             .try_into/* ::<u64> */()
-            .unwrap_or_else(|_| stacktrace.panic_cant_parse_as("u64"))
+            .map_err(|_| stacktrace.err_cant_parse_as("u64"))
     }
 
-    fn load_u8(&self, name: &'static str, stacktrace: &Stacktrace) -> u8 {
+    fn load_u8(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<u8> {
         let stacktrace = stacktrace.pushed(name);
         self
             .get(name)
-            .unwrap_or_else(|| stacktrace.panic_not_found())
+            .ok_or_else(|| stacktrace.err_not_found())?
             .as_integer()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("integer"))
-            .try_into/* ::<u64> */()
-            .unwrap_or_else(|_| stacktrace.panic_cant_parse_as("u64"))
+            .ok_or_else(|| stacktrace.err_cant_parse_as("integer"))?
+            .try_into/* ::<u8> */()
+            .map_err(|_| stacktrace.err_cant_parse_as("u8"))
+    }
+
+    fn load_optional_non_negative_float(&self, name: &'static str, stacktrace: &Stacktrace) -> Result<Option<float>> {
+        if self.get(name).is_none() { return Ok(None) }
+        let value = self.load_float(name, stacktrace)?;
+        if value < 0. {
+            return Err(stacktrace.pushed(name).err("must be non-negative"));
+        }
+        Ok(Some(value))
     }
 }
 
@@ -189,13 +397,20 @@ fn load_from_text_ok() {
             try_randomized_initial_values: 42,
             initial_values_random_scale: 10.,
             print_only_better_deconvolution: true,
+            convolution_method: ConvolutionMethod::Auto,
+            irf_params: IrfParams::default(),
+            add_ons: AddOns::default(),
         },
         input_params: ConfigInputParams {
             align_step_to: AlignStepsTo::Smaller,
             max_step_relative_diff: 0.02,
+            weights: Weights::None,
+            fit_mask: FitMask::default(),
         },
         output_params: ConfigOutputParams {
             significant_digits: 4,
+            result_format: ResultFormat::Txt,
+            summary_format: SummaryFormat::Csv,
         },
         fit_algorithm: ConfigFitAlgorithmParams::PatternSearch(PatternSearch {
             fit_algorithm_min_step: 1e-4,
@@ -204,6 +419,7 @@ fn load_from_text_ok() {
             alpha: 1.1,
             beta: None,
         }),
+        global_fit: None,
     };
     let config_actual = Config::load_from_text(r#"
         [deconvolution_function.SatExp_TwoDecExp_SeparateConsts]
@@ -229,15 +445,129 @@ fn load_from_text_ok() {
         initial_step = 1.0
         alpha = 1.1     # step increase coefficient
         #beta = 0.9     # step decrease coefficient, default = 1/alpha
-    "#);
+    "#).unwrap();
     dbg!(&config_expected, &config_actual);
     assert_eq!(config_expected, config_actual);
 }
 
-#[should_panic(expected = "`deconvolution_function` -> `Two_SatExp_DecExp` -> `diff_function_type`: not found")]
 #[test]
-fn load_from_text_panic() {
-    let _ = Config::load_from_text(r#"
+fn load_from_text_per_point_ok() {
+    use crate::{
+        antispikes::{Antispikes, AntispikesType},
+        deconvolution::types::{
+            per_points::{InitialValues_PerPoint, PerPoint},
+            value_and_domain::ValueAndDomain,
+        },
+        diff_function::DiffFunction,
+    };
+    let deconvolution_function_expected = ConfigDeconvolutionFunc::PerPoint(PerPoint {
+        diff_function_type: DiffFunction::DySqr,
+        antispikes: Some(Antispikes::new(AntispikesType::DyAbs, 0.5)),
+        initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::range_with_min(0.1, 0.)),
+    });
+    let config_actual = Config::load_from_text(r#"
+        [deconvolution_function.PerPoint]
+        diff_function_type = "DySqr"
+        antispikes = { antispikes_type = "DyAbs", antispikes_k = 0.5 }
+        initial_value = "v=0.1>0"
+
+        [deconvolution_params]
+        try_randomized_initial_values = 0
+        initial_values_random_scale = 10.0
+        print_only_better_deconvolution = false
+
+        [input_params]
+        align_steps_to = "smaller"
+        max_step_relative_diff = 0.02
+
+        [output_params]
+        significant_digits = 4
+
+        [fit_algorithm.pattern_search]
+        fit_algorithm_min_step = 1e-4
+        fit_residue_evals_max = 1_000_000
+        initial_step = 1.0
+        alpha = 1.1
+    "#).unwrap();
+    assert_eq!(deconvolution_function_expected, config_actual.deconvolution_function);
+}
+
+#[test]
+fn load_from_text_with_overrides_ok() {
+    use crate::fit_algorithms::pattern_search::PatternSearch;
+    let config_actual = Config::load_from_text_with_overrides(
+        r#"
+            [deconvolution_function.Exponents]
+            diff_function_type = "DySqr"
+            initial_values = "a0=1, s0=0, t0=1"
+
+            [deconvolution_params]
+            try_randomized_initial_values = 0
+            initial_values_random_scale = 10.0
+            print_only_better_deconvolution = false
+
+            [input_params]
+            align_steps_to = "smaller"
+            max_step_relative_diff = 0.02
+
+            [output_params]
+            significant_digits = 4
+
+            [fit_algorithm.pattern_search]
+            fit_algorithm_min_step = 1e-4
+            fit_residue_evals_max = 1_000_000
+            initial_step = 1.0
+            alpha = 1.1
+        "#,
+        &[
+            ConfigOverride::parse("fit_algorithm.pattern_search.alpha=1.2").unwrap(),
+            ConfigOverride::parse("fit_algorithm.pattern_search.beta = 0.5").unwrap(),
+            ConfigOverride::parse("output_params.significant_digits=3").unwrap(),
+            ConfigOverride::parse("deconvolution_params.convolution_method=fft").unwrap(),
+            ConfigOverride::parse("output_params.result_format=json").unwrap(),
+            ConfigOverride::parse(r#"global_fit.shared_params=["tau", "shift"]"#).unwrap(),
+        ],
+    ).unwrap();
+    assert_eq!(
+        ConfigFitAlgorithmParams::PatternSearch(PatternSearch {
+            fit_algorithm_min_step: 1e-4,
+            fit_residue_evals_max: 1_000_000,
+            initial_step: 1.,
+            alpha: 1.2,
+            beta: Some(0.5),
+        }),
+        config_actual.fit_algorithm,
+    );
+    assert_eq!(3, config_actual.output_params.significant_digits);
+    assert_eq!(ConvolutionMethod::Fft, config_actual.deconvolution_params.convolution_method);
+    assert_eq!(ResultFormat::Json, config_actual.output_params.result_format);
+    assert_eq!(
+        Some(ConfigGlobalFit { shared_params: vec!["tau".to_string(), "shift".to_string()] }),
+        config_actual.global_fit,
+    );
+}
+
+#[test]
+fn config_override_parse() {
+    assert_eq!(
+        Ok(ConfigOverride { keys: vec!["a".to_string(), "b".to_string()], value: TomlValue::Float(1.5) }),
+        ConfigOverride::parse("a.b=1.5")
+    );
+    assert_eq!(
+        Ok(ConfigOverride { keys: vec!["a".to_string()], value: TomlValue::String("x=1, y=2".to_string()) }),
+        ConfigOverride::parse("a=x=1, y=2")
+    );
+    assert_eq!(
+        Ok(ConfigOverride { keys: vec!["a".to_string()], value: TomlValue::String("x".to_string()) }),
+        ConfigOverride::parse(r#"a="x""#)
+    );
+    assert!(ConfigOverride::parse("a").is_err());
+    assert!(ConfigOverride::parse("a..b=1").is_err());
+}
+
+#[test]
+fn load_from_text_err() {
+    let err = Config::load_from_text(r#"
         [deconvolution_function.Two_SatExp_DecExp]
         #diff_function_type = "DySqr"
         initial_values = [ 0.12, 296.0, 3.96, 6.7, 1.16, 310.0, 23.2, 1.79 ]
@@ -260,5 +590,119 @@ fn load_from_text_panic() {
         initial_step = 1.0
         alpha = 1.1     # step increase coefficient
         #beta = 0.9     # step decrease coefficient, default = 1/alpha
-    "#);
+    "#).unwrap_err();
+    assert_eq!(
+        "`deconvolution_function` -> `Two_SatExp_DecExp` -> `diff_function_type`: not found",
+        err.to_string(),
+    );
+}
+
+#[test]
+fn load_from_text_err_levenberg_marquardt_with_not_least_squares() {
+    let load = |diff_function_type: &str| -> Result<Config> {
+        Config::load_from_text(&format!(r#"
+            [deconvolution_function.SatExp_DecExp]
+            diff_function_type = "{diff_function_type}"
+            initial_values = "a=1, s=0, ta=1, tb=10"
+
+            [deconvolution_params]
+            try_randomized_initial_values = 0
+            initial_values_random_scale = 10.0
+            print_only_better_deconvolution = false
+
+            [input_params]
+            align_steps_to = "smaller"
+            max_step_relative_diff = 0.02
+
+            [output_params]
+            significant_digits = 4
+
+            [fit_algorithm.levenberg_marquardt]
+            fit_residue_evals_max = 100_000
+            initial_lambda = 1e-3
+            lambda_increase = 10.0
+            min_relative_improvement = 1e-10
+            jacobian_relative_step = 1e-7
+        "#))
+    };
+    assert!(load("DySqr").is_ok());
+    for diff_function_type in ["DyAbs", "LeastDist", "PoissonMLE", "Huber", "Cauchy", "Tukey"] {
+        assert_eq!(
+            "`fit_algorithm` -> `levenberg_marquardt`: can be used only with `diff_function_type` = `DySqr` or `DySqrPerEl`, bc it minimizes sum of squared residuals",
+            load(diff_function_type).unwrap_err().to_string(),
+        );
+    }
+}
+
+#[test]
+fn load_from_text_err_weights_with_not_weighted_deconvolution() {
+    const CONFIG_WITHOUT_INPUT_PARAMS: &str = r#"
+        [deconvolution_function.Fourier]
+        regularization_type = "Wiener"
+        regularization_k = 1e-3
+
+        [deconvolution_params]
+        try_randomized_initial_values = 0
+        initial_values_random_scale = 10.0
+        print_only_better_deconvolution = false
+
+        [output_params]
+        significant_digits = 4
+
+        [fit_algorithm.nelder_mead]
+        fit_algorithm_min_step = 1e-4
+        fit_residue_evals_max = 1_000
+        initial_step = 1.0
+    "#;
+    let load = |input_params: &str| -> Result<Config> {
+        Config::load_from_text(&format!("{CONFIG_WITHOUT_INPUT_PARAMS}\n[input_params]\nalign_steps_to = \"smaller\"\nmax_step_relative_diff = 0.02\n{input_params}"))
+    };
+    assert!(load("").is_ok());
+    assert_eq!(
+        "`input_params` -> `weights`: must be `none` for `Fourier`, bc it uses all points with same weight",
+        load(r#"weights = "poisson""#).unwrap_err().to_string(),
+    );
+    assert_eq!(
+        "`input_params`: `fit_window` and `fit_exclude` can't be used with `Fourier`, bc it uses all points",
+        load("fit_window = [1.0, 2.0]").unwrap_err().to_string(),
+    );
+}
+
+#[test]
+fn load_from_text_err_max_entropy_without_weights() {
+    let load = |lifetime_distribution: &str, input_params: &str| -> Result<Config> {
+        Config::load_from_text(&format!(r#"
+            [deconvolution_function.LifetimeDistribution]
+            tau_min = 0.1
+            tau_max = 1000.0
+            taus_len = 50
+            shift = 0.0
+            method = "max_entropy"
+            {lifetime_distribution}
+
+            [deconvolution_params]
+            try_randomized_initial_values = 0
+            initial_values_random_scale = 10.0
+            print_only_better_deconvolution = false
+
+            [input_params]
+            align_steps_to = "smaller"
+            max_step_relative_diff = 0.02
+            {input_params}
+
+            [output_params]
+            significant_digits = 4
+
+            [fit_algorithm.nelder_mead]
+            fit_algorithm_min_step = 1e-4
+            fit_residue_evals_max = 1_000
+            initial_step = 1.0
+        "#))
+    };
+    assert!(load("", r#"weights = "poisson""#).is_ok());
+    assert!(load("target_reduced_chi_square = 1e-4", "").is_ok());
+    assert_eq!(
+        "`deconvolution_function` -> `LifetimeDistribution`: `target_reduced_chi_square` must be set for `method = \"max_entropy\"`, if `weights = \"none\"`",
+        load("", "").unwrap_err().to_string(),
+    );
 }
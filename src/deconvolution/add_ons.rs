@@ -0,0 +1,176 @@
+//! Add-ons: constant background and scattered light, added to convolved points of any model.
+
+use toml::Value as TomlValue;
+
+use crate::{
+    error::Result,
+    load::Load,
+    spectrum::Spectrum,
+    stacktrace::Stacktrace,
+    types::{float::float, linalg::DVect},
+};
+
+use super::{
+    extra_params::{ExtraParams, ExtraParamsAutoImplFns, load_vads_from_str},
+    types::value_and_domain::ValueAndDomain,
+};
+
+
+/// Optional components, which are added after convolution:
+/// `convolved + background + scatter * instrument`.
+///
+/// Their params are appended after model and instrument params in params vector (in this order).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AddOns {
+    /// Constant background.
+    pub background: Option<ValueAndDomain>,
+    /// Amplitude of scattered excitation light, which have shape of instrument.
+    pub scatter: Option<ValueAndDomain>,
+}
+
+/// Values of [`AddOns`], `0` for disabled ones.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AddOnsValues {
+    pub background: float,
+    pub scatter: float,
+}
+
+impl AddOns {
+    pub const BACKGROUND_NAME: &'static str = "background";
+    pub const SCATTER_NAME: &'static str = "scatter";
+
+    /// `params` are only params of add-ons, in the same order as [`get_names`](ExtraParamsAutoImplFns::get_names).
+    pub fn params_to_values(&self, params: &[float]) -> AddOnsValues {
+        assert_eq!(self.len(), params.len());
+        let mut params = params.iter().copied();
+        AddOnsValues {
+            background: self.background.and_then(|_| params.next()).unwrap_or(0.),
+            scatter: self.scatter.and_then(|_| params.next()).unwrap_or(0.),
+        }
+    }
+}
+
+impl ExtraParams for AddOns {
+    fn get_names_and_vads(&self) -> Vec<(&'static str, ValueAndDomain)> {
+        [(Self::BACKGROUND_NAME, self.background), (Self::SCATTER_NAME, self.scatter)]
+            .into_iter()
+            .filter_map(|(name, vad)| vad.map(|vad| (name, vad)))
+            .collect()
+    }
+}
+
+impl AddOnsValues {
+    /// Add background and scatter to `points_convolved`, which are on the grid of `measured`.
+    ///
+    /// Scatter is `instrument_points` (on the grid of `instrument`, which step must be same as in `measured`)
+    /// taken at the same `x` as measured points, by linear interpolation, and zero outside of instrument.
+    pub fn apply_to_convolved(
+        &self,
+        points_convolved: &mut DVect,
+        instrument_points: &[float],
+        instrument: &Spectrum,
+        measured: &Spectrum,
+    ) {
+        assert_eq!(instrument.step, measured.step);
+        assert_eq!(measured.points.len(), points_convolved.len());
+        if self.background != 0. {
+            points_convolved.add_scalar_mut(self.background);
+        }
+        if self.scatter != 0. {
+            let instrument_len = instrument_points.len();
+            let get_point = |i: isize| -> float {
+                if 0 <= i && (i as usize) < instrument_len { instrument_points[i as usize] } else { 0. }
+            };
+            let offset_in_points = (measured.x_start - instrument.x_start) / instrument.step;
+            for (i, point_convolved) in points_convolved.iter_mut().enumerate() {
+                let x = i as float + offset_in_points;
+                let i_lhs = x.floor();
+                let t = x - i_lhs;
+                let i_lhs = i_lhs as isize;
+                *point_convolved += self.scatter * ((1. - t) * get_point(i_lhs) + t * get_point(i_lhs + 1));
+            }
+        }
+    }
+}
+
+
+impl Load for AddOns {
+    const TOML_NAME: &'static str = "add_ons";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let mut vads = load_vads_from_str(toml_value, [Self::BACKGROUND_NAME, Self::SCATTER_NAME], stacktrace)?;
+        Ok(Self {
+            background: vads.remove(Self::BACKGROUND_NAME),
+            scatter: vads.remove(Self::SCATTER_NAME),
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod apply_to_convolved {
+    use super::*;
+
+    fn instrument() -> Spectrum {
+        Spectrum { points: vec![0., 1., 2., 1., 0.], step: 0.5, x_start: 1. }
+    }
+
+    fn measured() -> Spectrum {
+        Spectrum { points: vec![0.; 4], step: 0.5, x_start: 2. }
+    }
+
+    #[test]
+    fn background() {
+        let mut points_convolved = DVect::from_vec(vec![1., 2., 3., 4.]);
+        AddOnsValues { background: 0.5, scatter: 0. }
+            .apply_to_convolved(&mut points_convolved, &instrument().points, &instrument(), &measured());
+        assert_eq!(DVect::from_vec(vec![1.5, 2.5, 3.5, 4.5]), points_convolved);
+    }
+
+    #[test]
+    fn scatter() {
+        let mut points_convolved = DVect::from_vec(vec![1., 1., 1., 1.]);
+        AddOnsValues { background: 0., scatter: 2. }
+            .apply_to_convolved(&mut points_convolved, &instrument().points, &instrument(), &measured());
+        assert_eq!(DVect::from_vec(vec![5., 3., 1., 1.]), points_convolved);
+    }
+
+    #[test]
+    fn scatter_with_fraction_offset() {
+        let mut points_convolved = DVect::from_vec(vec![0.; 4]);
+        let measured = Spectrum { x_start: 1.25, ..measured() };
+        AddOnsValues { background: 0., scatter: 1. }
+            .apply_to_convolved(&mut points_convolved, &instrument().points, &instrument(), &measured);
+        assert_eq!(DVect::from_vec(vec![0.5, 1.5, 1.5, 0.5]), points_convolved);
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn both() {
+        assert_eq!(
+            AddOns { background: Some(ValueAndDomain::fixed(0.1)), scatter: Some(ValueAndDomain::range_with_min(1e-3, 0.)) },
+            AddOns::load_from_self(&TomlValue::String("background==0.1, scatter=1e-3>0".to_string()), &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_background() {
+        assert_eq!(
+            AddOns { background: Some(ValueAndDomain::free(0.)), scatter: None },
+            AddOns::load_from_self(&TomlValue::String("background=0".to_string()), &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            "`add_ons`: unkown type: `offset`, known types: [`background`, `scatter`]",
+            AddOns::load_from_self(&TomlValue::String("offset=1".to_string()), &Stacktrace::new("add_ons")).unwrap_err().to_string()
+        );
+    }
+}
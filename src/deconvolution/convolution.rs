@@ -1,11 +1,72 @@
 //! Convolution.
 
-use crate::types::{
-    float::float,
-    linalg::DVect,
-    named_wrappers::{ConvolvedV, DeconvolvedV, InstrumentRevV},
+use toml::Value as TomlValue;
+
+use crate::{
+    error::Result,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::DVect,
+        named_wrappers::{ConvolvedV, DeconvolvedV, InstrumentRevV},
+    },
 };
 
+use super::fft::{complex, fft, ifft};
+
+
+/// Which algorithm to use for convolution, result is the same (up to float rounding).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConvolutionMethod {
+    /// Sliding dot product, `O(N·M)`.
+    Direct,
+    /// Using FFT, `O((N+M)·log(N+M))`.
+    Fft,
+    /// [`Fft`](ConvolutionMethod::Fft) if it is estimated to be faster, else [`Direct`](ConvolutionMethod::Direct).
+    Auto,
+}
+
+impl ConvolutionMethod {
+    /// How much one FFT butterfly is slower than one multiply-add in direct convolution (rough estimate).
+    const AUTO_FFT_COST_RATIO: usize = 30;
+
+    pub fn convolve(&self, instrument_rev: &InstrumentRevV, deconvolved: DeconvolvedV) -> ConvolvedV {
+        match self {
+            Self::Direct => convolve_by_points_v(instrument_rev, deconvolved),
+            Self::Fft => convolve_by_points_fft_v(instrument_rev, deconvolved),
+            Self::Auto => if Self::is_fft_faster(instrument_rev.0.len(), deconvolved.0.len()) {
+                convolve_by_points_fft_v(instrument_rev, deconvolved)
+            } else {
+                convolve_by_points_v(instrument_rev, deconvolved)
+            }
+        }
+    }
+
+    /// Compare `N·M` of direct convolution vs `L·log2(L)` of FFT, where `L` is FFT len.
+    fn is_fft_faster(instrument_len: usize, deconvolved_len: usize) -> bool {
+        let fft_len: usize = (deconvolved_len + instrument_len).saturating_sub(1).next_power_of_two();
+        let direct_cost: usize = instrument_len * deconvolved_len;
+        let fft_cost: usize = Self::AUTO_FFT_COST_RATIO * fft_len * (fft_len.ilog2() as usize);
+        direct_cost > fft_cost
+    }
+}
+
+impl Load for ConvolutionMethod {
+    const TOML_NAME: &'static str = "convolution_method";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let convolution_method_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match convolution_method_str {
+            "direct" => Self::Direct,
+            "fft" => Self::Fft,
+            "auto" => Self::Auto,
+            _ => return Err(stacktrace.err_unknown_type(convolution_method_str, ["direct", "fft", "auto"]))
+        })
+    }
+}
+
 
 // /// Must be used only in `tests` & `DeconvolutionData::convolve()`.
 // #[deprecated = "use `convolve_by_points_v` instead"]
@@ -88,6 +149,45 @@ pub(super) fn convolve_by_points_v( // TODO: `pub(super)`.
 }
 
 
+/// Same as [`convolve_by_points_v`], but using FFT.
+///
+/// Both `instrument` and `deconvolved` are real, so they are packed into one complex vector
+/// as real and imaginary parts, to do only one forward FFT.
+pub(super) fn convolve_by_points_fft_v(
+    instrument_rev: &InstrumentRevV,
+    deconvolved: DeconvolvedV,
+) -> ConvolvedV {
+    let instrument_len: usize = instrument_rev.0.len();
+    assert!(instrument_len % 2 == 1, "instrument_len = {}", instrument_len);
+    let ilh = instrument_len / 2;
+    let deconvolved_len: usize = deconvolved.0.len();
+    let convolved_len = deconvolved_len;
+    if convolved_len == 0 { return ConvolvedV(DVect::zeros(0)) }
+    // full linear convolution have `deconvolved_len + instrument_len - 1` points,
+    // so no wrap around happens with this len.
+    let fft_len: usize = (deconvolved_len + instrument_len - 1).next_power_of_two();
+    let mut packed: Vec<complex> = vec![complex::new(0., 0.); fft_len];
+    for (i, &instrument_point) in instrument_rev.0.iter().rev().enumerate() {
+        packed[i].re = instrument_point;
+    }
+    for (i, &deconvolved_point) in deconvolved.0.iter().enumerate() {
+        packed[i].im = deconvolved_point;
+    }
+    fft(&mut packed);
+    // unpack: I[k] = (Z[k] + conj(Z[-k])) / 2, D[k] = (Z[k] - conj(Z[-k])) / 2i,
+    // => I[k]·D[k] = (Z[k]² - conj(Z[-k])²) / 4i.
+    let mut product: Vec<complex> = (0..fft_len)
+        .map(|k| {
+            let z_k = packed[k];
+            let z_neg_k_conj = packed[(fft_len - k) % fft_len].conj();
+            (z_k * z_k - z_neg_k_conj * z_neg_k_conj) * complex::new(0., -0.25)
+        })
+        .collect();
+    ifft(&mut product);
+    ConvolvedV(DVect::from_fn(convolved_len, |i, _| product[i + ilh].re))
+}
+
+
 
 #[cfg(test)]
 mod convolve {
@@ -95,13 +195,50 @@ mod convolve {
 
     use crate::types::float::float;
 
+    /// Also checks, that FFT convolution gives same result, as direct one.
     fn convolve(instrument: &Vec<float>, deconvolved: &Vec<float>) -> Vec<float> {
         use crate::types::named_wrappers::{Deconvolved, Instrument};
-        use super::convolve_by_points_v;
-        convolve_by_points_v(
+        use super::{convolve_by_points_fft_v, convolve_by_points_v};
+        let convolved_direct = convolve_by_points_v(
             &Instrument(instrument.to_vec()).into(),
             Deconvolved(deconvolved.to_vec()).into(),
-        ).0.data.as_vec().to_vec()
+        ).0.data.as_vec().to_vec();
+        let convolved_fft = convolve_by_points_fft_v(
+            &Instrument(instrument.to_vec()).into(),
+            Deconvolved(deconvolved.to_vec()).into(),
+        ).0.data.as_vec().to_vec();
+        assert_eq!(convolved_direct.len(), convolved_fft.len());
+        for (direct, fft) in convolved_direct.iter().zip(&convolved_fft) {
+            assert!((direct - fft).abs() < 1e-9, "convolved_direct = {convolved_direct:?}\nconvolved_fft = {convolved_fft:?}");
+        }
+        convolved_direct
+    }
+
+    mod fft_same_as_direct {
+        use super::*;
+        fn gauss(len: usize, sigma: float) -> Vec<float> {
+            let center = (len / 2) as float;
+            (0..len).map(|i| (-((i as float - center) / sigma).powi(2) / 2.).exp()).collect()
+        }
+        fn decay(len: usize) -> Vec<float> {
+            (0..len).map(|i| if i < len / 10 { 0. } else { (-((i - len / 10) as float) / 50.).exp() }).collect()
+        }
+        #[test]
+        fn instrument_wide() {
+            let _ = convolve(&gauss(301, 30.), &decay(1000));
+        }
+        #[test]
+        fn instrument_longer_than_spectrum() {
+            let _ = convolve(&gauss(101, 10.), &decay(40));
+        }
+        #[test]
+        fn spectrum_len_is_power_of_two() {
+            let _ = convolve(&gauss(17, 3.), &decay(1024));
+        }
+        #[test]
+        fn spectrum_is_single_point() {
+            let _ = convolve(&gauss(5, 1.), &vec![2.]);
+        }
     }
 
     mod per_point {
@@ -741,4 +878,3 @@ mod convolve {
         // }
     }
 }
-
@@ -1,30 +1,44 @@
 //! Deconvolution Data.
 
-use std::{cmp::Ordering, fs::File, io::Write};
+use std::{cmp::Ordering, fs::File, io::{self, Write}, iter, path::Path};
 
+use rand::{rngs::ThreadRng, thread_rng};
 use toml::Value as TomlValue;
 
 use crate::{
-    fit_algorithms::{Fit, FitAlgorithmVariant, FitResult},
+    diff_function::{DiffFunction, calc_least_dists_sq, calc_poisson_deviance},
+    error::{Error, Result},
+    extensions::ToStringWithSignificantDigits,
+    fit_algorithms::{Fit, FitAlgorithmVariant, FitData, FitDataAutoImplFns, FitResult, calc_degrees_of_freedom},
     load::Load,
     spectrum::Spectrum,
     stacktrace::Stacktrace,
-    types::{float::float, named_wrappers::{ConvolvedV, DeconvolvedV, InstrumentRevV, MeasuredV, Params, ParamsG, ParamsV}},
+    types::{
+        float::float,
+        linalg::DVect,
+        named_wrappers::{ConvolvedV, DeconvolvedV, Instrument, InstrumentRevV, Measured, MeasuredV, Params, ParamsG, ParamsV},
+    },
 };
 
 use super::{
     DeconvolutionVariant,
-    convolution::convolve_by_points_v,
+    add_ons::{AddOns, AddOnsValues},
+    convolution::ConvolutionMethod,
+    extra_params::ExtraParamsAutoImplFns,
     initial_values::InitialValuesGeneric,
+    irf_params::{IrfParams, IrfValues},
     types::{
+        log_normal_dec_exp::InitialValues_LogNormalDecExp,
         sat_exp__dec_exp::InitialValues_SatExp_DecExp,
         sat_exp__dec_exp_plus_const::InitialValues_SatExp_DecExpPlusConst,
+        sat_exp__stretched_exp::InitialValues_SatExp_StretchedExp,
         sat_exp__two_dec_exp::InitialValues_SatExp_TwoDecExp,
         sat_exp__two_dec_exp__constrained_consts::InitialValues_SatExp_TwoDecExp_ConstrainedConsts,
         sat_exp__two_dec_exp__separate_consts::InitialValues_SatExp_TwoDecExp_SeparateConsts,
         sat_exp__two_dec_exp_plus_const::InitialValues_SatExp_TwoDecExpPlusConst,
         sigmoid__two_dec_exp__constrained_consts::InitialValues_Sigmoid_TwoDecExp_ConstrainedConsts,
         two__sat_exp__dec_exp::InitialValues_Two_SatExp_DecExp,
+        value_and_domain::ValueAndDomain,
     },
 };
 
@@ -36,7 +50,18 @@ pub type DeconvolutionResultOrError = FitResult;
 pub struct DeconvolutionData {
     pub instrument: Spectrum,
     pub measured: Spectrum,
+    /// Sigmas (uncertainties) of [`measured`](DeconvolutionData::measured) points, used as weights `1/sigma`.
+    ///
+    /// If `None`, all points have same weight, as if all sigmas are `1`.
+    pub sigmas: Option<Vec<float>>,
+    pub fit_mask: FitMask,
+    /// Instrument params, fitted together with [`deconvolution`](DeconvolutionData::deconvolution) params,
+    /// which are appended after them.
+    pub irf_params: IrfParams,
+    /// Background and scatter, added after convolution, which params are appended after [`irf_params`](DeconvolutionData::irf_params).
+    pub add_ons: AddOns,
     pub deconvolution: DeconvolutionVariant,
+    pub convolution_method: ConvolutionMethod,
 }
 
 impl DeconvolutionData {
@@ -70,7 +95,7 @@ impl DeconvolutionData {
             Some(Ordering::Equal) => {}
             Some(Ordering::Less) => match align_steps_to {
                 AlignStepsTo::Smaller => {
-                    self.measured.recalculate_with_step(self.instrument.step);
+                    self.recalculate_measured_with_step(self.instrument.step);
                 }
                 AlignStepsTo::Bigger => {
                     self.instrument.recalculate_with_step(self.measured.step);
@@ -81,7 +106,7 @@ impl DeconvolutionData {
                     self.instrument.recalculate_with_step(self.measured.step);
                 }
                 AlignStepsTo::Bigger => {
-                    self.measured.recalculate_with_step(self.instrument.step);
+                    self.recalculate_measured_with_step(self.instrument.step);
                 }
             }
             None => panic!("One of the steps is `NaN`")
@@ -90,18 +115,150 @@ impl DeconvolutionData {
         self
     }
 
+    /// Recalculate [`measured`](DeconvolutionData::measured) and its [`sigmas`](DeconvolutionData::sigmas) with new step.
+    fn recalculate_measured_with_step(&mut self, step_new: float) {
+        if let Some(sigmas) = &mut self.sigmas {
+            let mut sigmas_spectrum = Spectrum {
+                points: sigmas.clone(),
+                step: self.measured.step,
+                x_start: self.measured.x_start,
+            };
+            sigmas_spectrum.recalculate_with_step(step_new);
+            *sigmas = sigmas_spectrum.points;
+        }
+        self.measured.recalculate_with_step(step_new);
+    }
+
+    /// Set [`sigmas`](DeconvolutionData::sigmas) from [`measured`](DeconvolutionData::measured) as counts,
+    /// by Poisson statistics: `sigma = sqrt(y)`, and `1` for `y < 1`, so zero counts don't have infinite weight.
+    ///
+    /// Must be called after [`aligned_steps_to`](DeconvolutionData::aligned_steps_to),
+    /// bc it can change [`measured`](DeconvolutionData::measured) points.
+    pub fn with_poisson_sigmas(mut self) -> Self {
+        self.sigmas = Some(self.measured.points.iter().map(|&y| y.max(1.).sqrt()).collect());
+        self
+    }
+
+    /// Weights `1/sigma` of measured points, `None` if there is no sigmas.
+    fn get_weights_v(&self) -> Option<DVect> {
+        self.sigmas.as_ref().map(|sigmas| {
+            assert_eq!(self.measured.points.len(), sigmas.len());
+            DVect::from_iterator(sigmas.len(), sigmas.iter().map(|sigma| 1. / sigma))
+        })
+    }
+
+    /// Indices of measured points used in fit, `None` if all points are used, see [`FitMask`].
+    fn get_fit_indices(&self) -> Option<Vec<usize>> {
+        self.fit_mask.get_indices(&self.measured)
+    }
+
+    fn get_fit_indices_or_all(&self) -> Vec<usize> {
+        self.get_fit_indices().unwrap_or_else(|| (0..self.measured.points.len()).collect())
+    }
+
+    /// Number of measured points used in fit, see [`FitMask`].
+    pub fn get_fit_points_len(&self) -> usize {
+        self.get_fit_indices().map_or(self.measured.points.len(), |fit_indices| fit_indices.len())
+    }
+
+    /// Make number of deconvolved points (if it depends on them) same as in [`measured`].
+    ///
+    /// Must be called after [`aligned_steps_to`](DeconvolutionData::aligned_steps_to),
+    /// bc it can change number of [`measured`] points.
+    ///
+    /// [`measured`]: DeconvolutionData::measured
+    pub fn sized_to_measured(mut self) -> Self {
+        self.deconvolution.set_points_len(self.measured.points.len());
+        self
+    }
+
     pub fn deconvolve(
         &self,
         fit_algorithm: &FitAlgorithmVariant,
         initial_values_random_scale: Option<float>,
     ) -> DeconvolutionResultOrError {
         self.assert_steps_is_aligned();
+        // non-iterative ones don't need fit algorithm:
+        match &self.deconvolution {
+            DeconvolutionVariant::Fourier(fourier) => {
+                return Ok(self.deconvolve_directly(|points_instrument, points_measured| {
+                    (fourier.deconvolve(points_instrument, points_measured), 0, None)
+                }));
+            }
+            DeconvolutionVariant::RichardsonLucy(richardson_lucy) => {
+                return Ok(self.deconvolve_directly(|points_instrument, points_measured| {
+                    let (points_deconvolved, iterations) = richardson_lucy.deconvolve(points_instrument, points_measured);
+                    (points_deconvolved, iterations, None)
+                }));
+            }
+            DeconvolutionVariant::Regularized(regularized) => {
+                let (weights, fit_indices) = (self.get_weights_v(), self.get_fit_indices());
+                return Ok(self.deconvolve_directly(|points_instrument, points_measured| {
+                    let (points_deconvolved, lambda) = regularized.deconvolve(points_instrument, points_measured, weights.as_ref(), fit_indices.as_deref());
+                    (points_deconvolved, 0, Some(lambda))
+                }));
+            }
+            DeconvolutionVariant::LifetimeDistribution(lifetime_distribution) => {
+                let x_start_end = (self.measured.x_start, self.measured.get_x_end());
+                let (weights, fit_indices) = (self.get_weights_v(), self.get_fit_indices());
+                return Ok(self.deconvolve_directly(|points_instrument, points_measured| {
+                    lifetime_distribution.deconvolve(points_instrument, points_measured, x_start_end, weights.as_ref(), fit_indices.as_deref())
+                }));
+            }
+            _ => {}
+        }
         let initial_params = if let Some(initial_values_random_scale) = initial_values_random_scale {
-            ParamsG::<float>(self.deconvolution.get_initial_values_randomized_v(initial_values_random_scale).0.data.as_vec().to_vec())
+            ParamsG::<float>(self.get_initial_params_randomized_v(initial_values_random_scale).0.data.as_vec().to_vec())
+        } else {
+            self.get_initial_params()
+        };
+        // estimated from not randomized initial params, so fit residues of all tries are comparable:
+        let deconvolution_data = self.with_robust_scale_estimated();
+        let fit = fit_algorithm.fit(&deconvolution_data, initial_params)?;
+        let covariance = fit.covariance.or_else(|| deconvolution_data.calc_covariance(&fit.params));
+        Ok(Fit { covariance, ..fit })
+    }
+
+    /// Same, but robust scale of diff function (if any) is estimated from residuals at initial params,
+    /// so it's fixed while fitting, see [`DiffFunction::with_robust_scale_estimated`].
+    pub fn with_robust_scale_estimated(&self) -> Self {
+        let (instrument_rev, measured) = self.prepare();
+        let residuals: DVect = self.calc_residuals_v(&self.get_initial_params().into(), &instrument_rev, &measured);
+        let mut deconvolution_data = self.clone();
+        deconvolution_data.deconvolution.set_diff_function_type(
+            self.deconvolution.get_diff_function_type().with_robust_scale_estimated(residuals.as_slice())
+        );
+        deconvolution_data
+    }
+
+    /// Deconvolve by `deconvolve_params(points_instrument, points_measured)`, which returns model params (e.g. deconvolved points),
+    /// number of iterations (zero for non-iterative ones) and regularization lambda (if any), for variants, which params are calculated, not fitted.
+    ///
+    /// [`irf_params`](DeconvolutionData::irf_params) and [`add_ons`](DeconvolutionData::add_ons) aren't fitted,
+    /// their initial values are applied to instrument and subtracted from measured.
+    fn deconvolve_directly(&self, deconvolve_params: impl FnOnce(&[float], &[float]) -> (Vec<float>, u64, Option<float>)) -> Fit {
+        let initial_params: Params = self.get_initial_params();
+        let instrument_points: Vec<float> = if self.irf_params.is_empty() {
+            self.instrument.points.clone()
         } else {
-            self.deconvolution.get_initial_values()
+            self.get_irf_values(&initial_params).apply_to_instrument(&self.instrument)
         };
-        fit_algorithm.fit(&self, initial_params)
+        let mut add_ons_points: DVect = DVect::zeros(self.measured.points.len());
+        self.get_add_ons_values(&initial_params).apply_to_convolved(&mut add_ons_points, &instrument_points, &self.instrument, &self.measured);
+        let measured_points: Vec<float> = self.measured.points.iter().zip(add_ons_points.iter())
+            .map(|(measured_point, add_ons_point)| measured_point - add_ons_point)
+            .collect();
+        let (model_params, iterations, regularization_lambda) = deconvolve_params(&instrument_points, &measured_points);
+        let mut params: Params = ParamsG::<float>(model_params);
+        params.0.extend_from_slice(&initial_params.0[self.get_model_params_amount()..]);
+        let fit_residue: float = FitData::calc_residue_function_v(self, &params.clone().into(), &self.prepare());
+        Fit {
+            params,
+            fit_residue,
+            fit_residue_evals: iterations,
+            covariance: None,
+            regularization_lambda,
+        }
     }
 
     // pub fn calc_residue_function(&self, params: &Params) -> float {
@@ -118,21 +275,166 @@ impl DeconvolutionData {
     pub fn calc_residue_function_v(&self, params: &ParamsV, instrument_rev: &InstrumentRevV, measured: &MeasuredV) -> float {
         let points_convolved: ConvolvedV = self.convolve_from_params_v(params, instrument_rev);
         assert_eq!(self.get_params_amount(), params.0.len());
-        self.deconvolution.calc_residue_function_v(measured, points_convolved)
+        // Poisson likelihood is already correctly weighted by itself, so measured points mustn't be scaled.
+        let weights = if self.is_poisson_mle() { None } else { self.get_weights_v() };
+        let fit_indices = self.get_fit_indices();
+        if weights.is_none() && fit_indices.is_none() {
+            return self.deconvolution.calc_residue_function_v(measured, points_convolved);
+        }
+        if let DiffFunction::LeastDist { x_scale, y_scale } = self.deconvolution.get_diff_function_type() {
+            // distance depends on neighbour points, so it's calculated on whole unweighted curve,
+            // and only then distances are weighted and masked:
+            let mut dists_sq: DVect = DVect::from_vec(calc_least_dists_sq(measured.0.as_slice(), points_convolved.0.as_slice(), x_scale, y_scale));
+            if let Some(weights) = weights {
+                dists_sq.component_mul_assign(&weights.map(|weight| weight.powi(2)));
+            }
+            if let Some(fit_indices) = fit_indices {
+                dists_sq = dists_sq.select_rows(&fit_indices);
+            }
+            return dists_sq.sum().sqrt();
+        }
+        let (mut points_measured, mut points_convolved): (DVect, DVect) = (measured.0.clone(), points_convolved.0);
+        if let Some(weights) = weights {
+            points_measured.component_mul_assign(&weights);
+            points_convolved.component_mul_assign(&weights);
+        }
+        if let Some(fit_indices) = fit_indices {
+            points_measured = points_measured.select_rows(&fit_indices);
+            points_convolved = points_convolved.select_rows(&fit_indices);
+        }
+        self.deconvolution.calc_residue_function_v(&MeasuredV(points_measured), ConvolvedV(points_convolved))
     }
 
+    /// Weighted residuals (`(measured - convolved) / sigma`) for each point used in fit (see [`FitMask`]),
+    /// used by algorithms working with vector, not scalar.
+    pub fn calc_residuals_v(&self, params: &ParamsV, instrument_rev: &InstrumentRevV, measured: &MeasuredV) -> DVect {
+        let points_convolved: ConvolvedV = self.convolve_from_params_v(params, instrument_rev);
+        assert_eq!(self.get_params_amount(), params.0.len());
+        let residuals: DVect = &measured.0 - points_convolved.0;
+        let residuals: DVect = match self.get_weights_v() {
+            None => residuals,
+            Some(weights) => residuals.component_mul(&weights),
+        };
+        match self.get_fit_indices() {
+            None => residuals,
+            Some(fit_indices) => residuals.select_rows(&fit_indices),
+        }
+    }
+
+    /// Number of all fitted params: [`deconvolution`] ones, then [`irf_params`] ones, then [`add_ons`] ones.
+    ///
+    /// [`deconvolution`]: DeconvolutionData::deconvolution
+    /// [`irf_params`]: DeconvolutionData::irf_params
+    /// [`add_ons`]: DeconvolutionData::add_ons
     pub fn get_params_amount(&self) -> usize {
-        self.deconvolution.get_initial_values_len(/*self.measured.points.len()*/)
+        self.get_model_params_amount() + self.irf_params.len() + self.add_ons.len()
+    }
+
+    /// Number of not fixed params, see [`calc_degrees_of_freedom`].
+    pub fn get_free_params_amount(&self) -> usize {
+        self.get_initial_vads().iter().filter(|vad| !vad.is_fixed()).count()
+    }
+
+    /// Are residuals divided by known absolute sigmas (from file or poisson),
+    /// so covariance isn't rescaled by residual variance, see [`calc_covariance_from_jacobian`](crate::fit_algorithms::calc_covariance_from_jacobian).
+    pub fn is_residuals_in_sigmas(&self) -> bool {
+        self.sigmas.is_some()
     }
 
     pub fn get_initial_params(&self) -> Params {
-        let initial_params: Params = self.deconvolution.get_initial_values();
-        assert_eq!(self.deconvolution.get_initial_values_len(), initial_params.0.len());
+        let mut initial_params: Params = self.deconvolution.get_initial_values();
+        initial_params.0.extend(self.irf_params.get_vads().iter().chain(&self.add_ons.get_vads()).map(|vad| vad.value));
+        assert_eq!(self.get_params_amount(), initial_params.0.len());
         initial_params
     }
 
+    pub fn get_initial_params_randomized_v(&self, initial_values_random_scale: float) -> ParamsV {
+        let mut rng = thread_rng();
+        self.get_initial_params_randomized_with_rng_v(initial_values_random_scale, &mut rng)
+    }
+
+    pub fn get_initial_params_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV {
+        let model_params: ParamsV = self.deconvolution.get_initial_values_randomized_with_rng_v(initial_values_random_scale, rng);
+        if !self.has_extra_params() { return model_params }
+        let irf_params: Vec<float> = self.irf_params.get_randomized_with_rng(initial_values_random_scale, rng);
+        let add_ons: Vec<float> = self.add_ons.get_randomized_with_rng(initial_values_random_scale, rng);
+        ParamsV(DVect::from_iterator(
+            self.get_params_amount(),
+            model_params.0.iter().copied().chain(irf_params).chain(add_ons),
+        ))
+    }
+
+    pub fn get_params_names(&self) -> Vec<String> {
+        let mut names = self.deconvolution.get_params_names();
+        names.extend(self.irf_params.get_names());
+        names.extend(self.add_ons.get_names());
+        names
+    }
+
+    pub fn get_initial_vads(&self) -> Vec<ValueAndDomain> {
+        let mut vads = self.deconvolution.get_initial_vads();
+        vads.extend(self.irf_params.get_vads());
+        vads.extend(self.add_ons.get_vads());
+        vads
+    }
+
+    /// Only [`deconvolution`](DeconvolutionData::deconvolution) params, without [`irf_params`] and [`add_ons`] ones.
+    ///
+    /// [`irf_params`]: DeconvolutionData::irf_params
+    /// [`add_ons`]: DeconvolutionData::add_ons
+    pub fn get_model_params(&self, params: &Params) -> Params {
+        ParamsG::<float>(params.0[..self.get_model_params_amount()].to_vec())
+    }
+
+    /// Values of [`irf_params`](DeconvolutionData::irf_params), `0` for disabled ones.
+    pub fn get_irf_values(&self, params: &Params) -> IrfValues {
+        let irf_params_start = self.get_model_params_amount();
+        self.irf_params.params_to_values(&params.0[irf_params_start..irf_params_start+self.irf_params.len()])
+    }
+
+    /// Values of [`add_ons`](DeconvolutionData::add_ons), `0` for disabled ones.
+    pub fn get_add_ons_values(&self, params: &Params) -> AddOnsValues {
+        self.add_ons.params_to_values(&params.0[self.get_model_params_amount()+self.irf_params.len()..])
+    }
+
+    fn get_model_params_amount(&self) -> usize {
+        self.deconvolution.get_initial_values_len()
+    }
+
+    fn has_extra_params(&self) -> bool {
+        !self.irf_params.is_empty() || !self.add_ons.is_empty()
+    }
+
+    /// Split `params` into [`deconvolution`], [`irf_params`] and [`add_ons`] ones.
+    ///
+    /// [`deconvolution`]: DeconvolutionData::deconvolution
+    /// [`irf_params`]: DeconvolutionData::irf_params
+    /// [`add_ons`]: DeconvolutionData::add_ons
+    fn split_params_v<'a>(&self, params: &'a ParamsV) -> (ParamsV, &'a [float], &'a [float]) {
+        let params: &[float] = params.0.as_slice();
+        let (model_params, extra_params) = params.split_at(self.get_model_params_amount());
+        let (irf_params, add_ons) = extra_params.split_at(self.irf_params.len());
+        (ParamsV(DVect::from_column_slice(model_params)), irf_params, add_ons)
+    }
+
     pub fn is_params_ok_v(&self, params: &ParamsV) -> bool {
-        self.deconvolution.is_params_ok_v(params)
+        if !self.has_extra_params() { return self.deconvolution.is_params_ok_v(params) }
+        let (model_params, irf_params, add_ons) = self.split_params_v(params);
+        self.deconvolution.is_params_ok_v(&model_params)
+            && self.irf_params.is_params_ok(irf_params)
+            && self.add_ons.is_params_ok(add_ons)
+    }
+
+    pub fn clamp_params_v(&self, params: &ParamsV) -> ParamsV {
+        if !self.has_extra_params() { return self.deconvolution.clamp_params_v(params) }
+        let (model_params, irf_params, add_ons) = self.split_params_v(params);
+        let model_params: ParamsV = self.deconvolution.clamp_params_v(&model_params);
+        ParamsV(DVect::from_iterator(
+            self.get_params_amount(),
+            model_params.0.iter().copied()
+                .chain(self.irf_params.clamp_params(irf_params))
+                .chain(self.add_ons.clamp_params(add_ons)),
+        ))
     }
 
     // pub fn convolve_from_params(&self, params: &Params) -> Convolved {
@@ -145,14 +447,38 @@ impl DeconvolutionData {
     //     self.convolve_from_points(points_deconvolved)
     // }
 
+    /// If there are [`irf_params`](DeconvolutionData::irf_params), `instrument_rev` is ignored,
+    /// and instead one is built from [`instrument`](DeconvolutionData::instrument) with this params applied.
+    ///
+    /// [`add_ons`](DeconvolutionData::add_ons) are added after convolution.
     pub fn convolve_from_params_v(&self, params: &ParamsV, instrument_rev: &InstrumentRevV) -> ConvolvedV {
-        // convert `params` into `points` ("deconvolved"):
-        let points_deconvolved: DeconvolvedV = self.deconvolution.params_to_points_v(
-            params,
-            self.measured.points.len(),
-            (self.measured.x_start, self.measured.get_x_end())
-        );
-        self.convolve_from_points_v(points_deconvolved, instrument_rev)
+        let x_start_end = (self.measured.x_start, self.measured.get_x_end());
+        if !self.has_extra_params() {
+            // convert `params` into `points` ("deconvolved"):
+            let points_deconvolved: DeconvolvedV = self.deconvolution.params_to_points_v(params, self.measured.points.len(), x_start_end);
+            return self.convolve_from_points_v(points_deconvolved, instrument_rev);
+        }
+        let (model_params, irf_params, add_ons) = self.split_params_v(params);
+        let points_deconvolved: DeconvolvedV = self.deconvolution.params_to_points_v(&model_params, self.measured.points.len(), x_start_end);
+        let instrument_points_modified: Option<Vec<float>> = (!self.irf_params.is_empty()).then(|| {
+            self.irf_params.params_to_values(irf_params).apply_to_instrument(&self.instrument)
+        });
+        let mut points_convolved: ConvolvedV = match &instrument_points_modified {
+            Some(instrument_points_modified) => {
+                let instrument_rev: InstrumentRevV = Instrument(instrument_points_modified.clone()).into();
+                self.convolve_from_points_v(points_deconvolved, &instrument_rev)
+            }
+            None => self.convolve_from_points_v(points_deconvolved, instrument_rev),
+        };
+        if !self.add_ons.is_empty() {
+            self.add_ons.params_to_values(add_ons).apply_to_convolved(
+                &mut points_convolved.0,
+                instrument_points_modified.as_deref().unwrap_or(&self.instrument.points),
+                &self.instrument,
+                &self.measured,
+            );
+        }
+        points_convolved
     }
 
     // pub fn convolve_from_points(&self, points_deconvolved: Deconvolved) -> Convolved {
@@ -162,155 +488,390 @@ impl DeconvolutionData {
     // }
 
     pub fn convolve_from_points_v(&self, points_deconvolved: DeconvolvedV, instrument_rev: &InstrumentRevV) -> ConvolvedV {
-        let points_convolved: ConvolvedV = convolve_by_points_v(instrument_rev, points_deconvolved);
+        let points_convolved: ConvolvedV = self.convolution_method.convolve(instrument_rev, points_deconvolved);
         assert_eq!(self.measured.points.len(), points_convolved.0.len());
         points_convolved
     }
 
+    /// Weighted residuals at fit params, see [`calc_residuals_v`](DeconvolutionData::calc_residuals_v).
+    fn calc_fit_residuals(&self, deconvolution_results: &Fit) -> DVect {
+        self.calc_residuals_v(
+            &deconvolution_results.params.clone().into(),
+            &Instrument(self.instrument.points.clone()).into(),
+            &Measured(self.measured.points.clone()).into(),
+        )
+    }
+
+    fn is_poisson_mle(&self) -> bool {
+        self.deconvolution.get_diff_function_type() == DiffFunction::PoissonMLE
+    }
+
+    /// Cash C-statistic (Poisson deviance) at fit params, see [`calc_poisson_deviance`].
+    ///
+    /// `None` if fit isn't Poisson maximum likelihood one, bc then it's meaningless.
+    pub fn calc_cash_statistic(&self, deconvolution_results: &Fit) -> Option<float> {
+        if !self.is_poisson_mle() { return None }
+        let points_convolved: ConvolvedV = self.convolve_from_params_v(
+            &deconvolution_results.params.clone().into(),
+            &Instrument(self.instrument.points.clone()).into(),
+        );
+        Some(
+            self.get_fit_indices_or_all().into_iter()
+                .map(|i| calc_poisson_deviance(self.measured.points[i], points_convolved.0[i]))
+                .sum()
+        )
+    }
+
+    /// `chi^2 = sum( ((measured - convolved) / sigma)^2 )`.
+    pub fn calc_chi_square(&self, deconvolution_results: &Fit) -> float {
+        self.calc_fit_residuals(deconvolution_results).norm_squared()
+    }
+
+    /// `chi^2 / (N - p)`, see [`calc_degrees_of_freedom`], so with correct sigmas values near `1` means good fit.
+    ///
+    /// `NaN` if there are no degrees of freedom.
     pub fn calc_reduced_chi_square(&self, deconvolution_results: &Fit) -> float {
-        // src: https://www.originlab.com/doc/Quick-Help/measure-fitresult
-        deconvolution_results.fit_residue / (self.deconvolution.get_initial_values_len() as float)
+        // src: https://en.wikipedia.org/wiki/Reduced_chi-squared_statistic
+        let Some(degrees_of_freedom) = calc_degrees_of_freedom(self.get_fit_points_len(), self.get_free_params_amount()) else {
+            return float::NAN
+        };
+        self.calc_chi_square(deconvolution_results) / (degrees_of_freedom as float)
     }
 
+    /// `1 - RSS / TSS`, where `RSS` is unweighted residual sum of squares and `TSS` is total sum of squares.
+    ///
+    /// `NaN` if all measured points used in fit are same (`TSS = 0`).
     pub fn calc_r_square(&self, deconvolution_results: &Fit) -> float {
         // src: https://en.wikipedia.org/wiki/Coefficient_of_determination#Definitions
-        let residual_sum_of_squares = deconvolution_results.fit_residue;
-        let y_avg = self.measured.points.iter().sum::<float>() / (self.measured.points.len() as float);
-        let total_sum_of_squares = self.measured.points.iter()
+        let fit_indices: Vec<usize> = self.get_fit_indices_or_all();
+        let residual_sum_of_squares = {
+            let residuals: DVect = self.calc_fit_residuals(deconvolution_results);
+            match &self.sigmas {
+                None => residuals.norm_squared(),
+                Some(sigmas) => residuals.iter().zip(&fit_indices).map(|(r, &i)| (r * sigmas[i]).powi(2)).sum(),
+            }
+        };
+        let points_measured: Vec<float> = fit_indices.iter().map(|&i| self.measured.points[i]).collect();
+        let y_avg = points_measured.iter().sum::<float>() / (points_measured.len() as float);
+        let total_sum_of_squares = points_measured.iter()
             .map(|y_i| (y_i - y_avg).powi(2))
             .sum::<float>();
-        let r_square = 1. - residual_sum_of_squares / total_sum_of_squares;
-        assert!(r_square >= 0.);
-        assert!(r_square <= 1.);
-        r_square
+        if total_sum_of_squares == 0. { return float::NAN }
+        // can be negative, if model is worse than average, e.g. when fit is weighted or deconvolution isn't fitted.
+        1. - residual_sum_of_squares / total_sum_of_squares
     }
 
+    /// `1 - (1 - R^2) (N - 1) / (N - p - 1)`, see [`calc_degrees_of_freedom`].
+    ///
+    /// `NaN` if there are not enough degrees of freedom.
     pub fn calc_adjusted_r_square(&self, deconvolution_results: &Fit) -> float {
         // src: https://en.wikipedia.org/wiki/Coefficient_of_determination#Adjusted_R2
+        let n = self.get_fit_points_len();
+        let Some(degrees_of_freedom) = calc_degrees_of_freedom(n, self.get_free_params_amount()) else {
+            return float::NAN
+        };
+        // `N - p - 1`:
+        if degrees_of_freedom <= 1 { return float::NAN }
         let r_square = self.calc_r_square(deconvolution_results);
-        let n = self.measured.points.len() as float;
-        let p = self.deconvolution.get_initial_values_len() as float;
-        1. - (1. - r_square) * ( (n-1.) / (n-p-1.) )
+        1. - (1. - r_square) * ( ((n-1) as float) / ((degrees_of_freedom-1) as float) )
+    }
+
+    /// Standard errors of params and their correlation matrix, if fit have covariance matrix.
+    ///
+    /// If `significant_digits` is `None`, values are written with full precision.
+    pub fn build_params_uncertainties_msg(&self, deconvolution_results: &Fit, significant_digits: Option<u8>) -> Option<String> {
+        let standard_errors = deconvolution_results.get_standard_errors()?;
+        let correlation_matrix = deconvolution_results.get_correlation_matrix()?;
+        let params_names = self.get_params_names();
+        assert_eq!(params_names.len(), standard_errors.len());
+        let to_string = |value: float| -> String {
+            match significant_digits {
+                Some(significant_digits) => value.to_string_with_significant_digits(significant_digits),
+                None => value.to_string(),
+            }
+        };
+        let errors_scale: &str = if self.is_residuals_in_sigmas() { "from sigmas" } else { "scaled by residuals variance" };
+        let mut lines: Vec<String> = vec![format!("params uncertainties (standard errors, {errors_scale}):")];
+        for ((name, &value), &standard_error) in params_names.iter().zip(&deconvolution_results.params.0).zip(&standard_errors) {
+            lines.push(format!("- {name}={value} ± {standard_error}", value=to_string(value), standard_error=to_string(standard_error)));
+        }
+        lines.push("correlation matrix:".to_string());
+        let name_width: usize = params_names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let column_width: usize = name_width.max("-0.000".len());
+        lines.push(
+            iter::once(format!("{:name_width$}", ""))
+                .chain(params_names.iter().map(|name| format!("{name:>column_width$}")))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        for (name, row) in params_names.iter().zip(correlation_matrix.row_iter()) {
+            lines.push(
+                iter::once(format!("{name:name_width$}"))
+                    .chain(row.iter().map(|correlation| format!("{correlation:>column_width$.3}")))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+        Some(lines.join("\n"))
     }
 
     pub fn write_result_to_file(
         &self,
         filepathstr_output: &str,
         fit_goodness_msg: &str,
-        params: &Params,
+        deconvolution_results: &Fit,
         desmos_function_str: Result<String, &str>,
         origin_function_str: Result<String, &str>,
         fit_algorithm: &FitAlgorithmVariant,
-    ) {
-        let mut file_output = File::create(filepathstr_output).unwrap();
-        writeln!(file_output, "name: {name}", name=self.deconvolution.get_name()).unwrap();
-        writeln!(file_output, "\n{fit_goodness_msg}").unwrap();
-        writeln!(file_output, "\nparams:").unwrap();
+    ) -> Result<()> {
+        let to_output_write_err = |io_error: io::Error| Error::OutputWrite { filepath: filepathstr_output.to_string(), io_error };
+        let params: &Params = &self.get_model_params(&deconvolution_results.params);
+        let mut file_output = File::create(filepathstr_output).map_err(to_output_write_err)?;
+        writeln!(file_output, "name: {name}", name=self.deconvolution.get_name()).map_err(to_output_write_err)?;
+        writeln!(file_output, "\n{fit_goodness_msg}").map_err(to_output_write_err)?;
+        writeln!(file_output, "\nparams:").map_err(to_output_write_err)?;
         // TODO(refactor): make this a method in corresponding types
         type DV = DeconvolutionVariant;
         match &self.deconvolution {
-            DV::PerPoint(..) => {
+            DV::PerPoint(..) | DV::Fourier(..) | DV::RichardsonLucy(..) | DV::Regularized(..) => {
+                // points are written into separate file, near the result file, so it can be easily plotted.
+                let filepath_output = Path::new(filepathstr_output);
+                let filepath_output_deconvolved = filepath_output.with_file_name(format!(
+                    "{stem}_deconvolved.dat",
+                    stem=filepath_output.file_stem().unwrap().to_str().unwrap(),
+                ));
+                let filepathstr_output_deconvolved: &str = filepath_output_deconvolved.to_str().unwrap();
                 let sd_deconvolved = Spectrum {
                     points: params.0.clone(),
                     step: self.get_step(),
                     x_start: self.measured.x_start,
                 };
-                sd_deconvolved.write_to_file(filepathstr_output);
+                sd_deconvolved.write_to_file(filepathstr_output_deconvolved)?;
+                writeln!(file_output, "- points_len={}", params.0.len()).map_err(to_output_write_err)?;
+                writeln!(file_output, "- deconvolved points are in `{filepathstr_output_deconvolved}`").map_err(to_output_write_err)?;
+                if let Some(regularization_lambda) = deconvolution_results.regularization_lambda {
+                    writeln!(file_output, "- lambda={regularization_lambda}").map_err(to_output_write_err)?;
+                }
+            }
+            DV::LifetimeDistribution(lifetime_distribution) => {
+                // distribution is written into separate file, near the result file, so it can be easily plotted.
+                let filepath_output = Path::new(filepathstr_output);
+                let filepath_output_distribution = filepath_output.with_file_name(format!(
+                    "{stem}_distribution.dat",
+                    stem=filepath_output.file_stem().unwrap().to_str().unwrap(),
+                ));
+                let filepathstr_output_distribution: &str = filepath_output_distribution.to_str().unwrap();
+                // taus are log-spaced, so `x` is `log10(tau)`:
+                let sd_distribution = Spectrum {
+                    points: params.0.clone(),
+                    step: lifetime_distribution.get_log10_tau_step(),
+                    x_start: lifetime_distribution.tau_min.log10(),
+                };
+                sd_distribution.write_to_file(filepathstr_output_distribution)?;
+                let amplitudes_sum: float = params.0.iter().sum();
+                writeln!(file_output, "- shift={}", lifetime_distribution.shift).map_err(to_output_write_err)?;
+                writeln!(file_output, "- method={:?}", lifetime_distribution.method).map_err(to_output_write_err)?;
+                writeln!(file_output, "- taus: from {} to {}, taus_len={}", lifetime_distribution.tau_min, lifetime_distribution.tau_max, params.0.len()).map_err(to_output_write_err)?;
+                writeln!(file_output, "- amplitudes_sum={amplitudes_sum}").map_err(to_output_write_err)?;
+                if amplitudes_sum > 0. {
+                    let tau_mean: float = params.0.iter().zip(lifetime_distribution.get_taus())
+                        .map(|(amplitude, tau)| amplitude * tau)
+                        .sum::<float>() / amplitudes_sum;
+                    writeln!(file_output, "- tau_mean (amplitude weighted)={tau_mean}").map_err(to_output_write_err)?;
+                } else {
+                    writeln!(file_output, "- tau_mean isn't defined, bc all amplitudes are zero").map_err(to_output_write_err)?;
+                }
+                writeln!(file_output, "- distribution (amplitude vs log10(tau)) is in `{filepathstr_output_distribution}`").map_err(to_output_write_err)?;
+                if let Some(regularization_lambda) = deconvolution_results.regularization_lambda {
+                    writeln!(file_output, "- lambda={regularization_lambda}").map_err(to_output_write_err)?;
+                }
             }
             DV::Exponents(..) => {
                 for (i, [amplitude, shift, tau]) in params.0.array_chunks().enumerate() {
-                    writeln!(file_output, "- i={i}:").unwrap();
-                    writeln!(file_output, "  - amplitude={amplitude}").unwrap();
-                    writeln!(file_output, "  - shift={shift}").unwrap();
-                    writeln!(file_output, "  - tau={tau}").unwrap();
+                    writeln!(file_output, "- i={i}:").map_err(to_output_write_err)?;
+                    writeln!(file_output, "  - amplitude={amplitude}").map_err(to_output_write_err)?;
+                    writeln!(file_output, "  - shift={shift}").map_err(to_output_write_err)?;
+                    writeln!(file_output, "  - tau={tau}").map_err(to_output_write_err)?;
                 }
             }
             DV::SatExp_DecExp(..) => {
                 type SelfF = InitialValues_SatExp_DecExp<float>;
                 let SelfF { amplitude, shift, tau_a, tau_b } = SelfF::from_vec(&params);
-                writeln!(file_output, "- amplitude={amplitude}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
             }
             DV::Two_SatExp_DecExp(..) => {
                 type SelfF = InitialValues_Two_SatExp_DecExp<float>;
                 let SelfF { amplitude_1, shift_1, tau_a1, tau_b1, amplitude_2, shift_2, tau_a2, tau_b2 } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude_1={amplitude_1}").unwrap();
-                writeln!(file_output, "- shift_1={shift_1}").unwrap();
-                writeln!(file_output, "- tau_a1={tau_a1}").unwrap();
-                writeln!(file_output, "- tau_b1={tau_b1}").unwrap();
-                writeln!(file_output, "- amplitude_2={amplitude_2}").unwrap();
-                writeln!(file_output, "- shift_2={shift_2}").unwrap();
-                writeln!(file_output, "- tau_a2={tau_a2}").unwrap();
-                writeln!(file_output, "- tau_b2={tau_b2}").unwrap();
+                writeln!(file_output, "- amplitude_1={amplitude_1}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift_1={shift_1}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a1={tau_a1}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b1={tau_b1}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- amplitude_2={amplitude_2}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift_2={shift_2}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a2={tau_a2}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b2={tau_b2}").map_err(to_output_write_err)?;
             }
             DV::SatExp_DecExpPlusConst(..) => {
                 type SelfF = InitialValues_SatExp_DecExpPlusConst<float>;
                 let SelfF { amplitude, shift, height, tau_a, tau_b } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude={amplitude}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- height={height}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- height={height}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
             }
             DV::SatExp_TwoDecExp(..) => {
                 type SelfF = InitialValues_SatExp_TwoDecExp<float>;
                 let SelfF { amplitude, shift, tau_a, tau_b, tau_c } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude={amplitude}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
-                writeln!(file_output, "- tau_c={tau_c}").unwrap();
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_c={tau_c}").map_err(to_output_write_err)?;
             }
             DV::SatExp_TwoDecExpPlusConst(..) => {
                 type SelfF = InitialValues_SatExp_TwoDecExpPlusConst<float>;
                 let SelfF { amplitude, shift, height, tau_a, tau_b, tau_c } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude={amplitude}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- height={height}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
-                writeln!(file_output, "- tau_c={tau_c}").unwrap();
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- height={height}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_c={tau_c}").map_err(to_output_write_err)?;
             }
             DV::SatExp_TwoDecExp_SeparateConsts(..) => {
                 type SelfF = InitialValues_SatExp_TwoDecExp_SeparateConsts<float>;
                 let SelfF { amplitude_b, amplitude_c, shift, tau_a, tau_b, tau_c } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude_b={amplitude_b}").unwrap();
-                writeln!(file_output, "- amplitude_c={amplitude_c}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
-                writeln!(file_output, "- tau_c={tau_c}").unwrap();
+                writeln!(file_output, "- amplitude_b={amplitude_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- amplitude_c={amplitude_c}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_c={tau_c}").map_err(to_output_write_err)?;
             }
             DV::SatExp_TwoDecExp_ConstrainedConsts(..) => {
                 type SelfF = InitialValues_SatExp_TwoDecExp_ConstrainedConsts<float>;
                 let SelfF { amplitude_a, amplitude_b, shift, tau_a, tau_b, tau_c } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude_a={amplitude_a}").unwrap();
-                writeln!(file_output, "- amplitude_b={amplitude_b}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
-                writeln!(file_output, "- tau_c={tau_c}").unwrap();
+                writeln!(file_output, "- amplitude_a={amplitude_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- amplitude_b={amplitude_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_c={tau_c}").map_err(to_output_write_err)?;
             }
             DV::Sigmoid_TwoDecExp_ConstrainedConsts(..) => {
                 type SelfF = InitialValues_Sigmoid_TwoDecExp_ConstrainedConsts<float>;
                 let SelfF { amplitude_a, amplitude_b, shift, tau_a, tau_b, tau_c } = SelfF::from_vec(params);
-                writeln!(file_output, "- amplitude_a={amplitude_a}").unwrap();
-                writeln!(file_output, "- amplitude_b={amplitude_b}").unwrap();
-                writeln!(file_output, "- shift={shift}").unwrap();
-                writeln!(file_output, "- tau_a={tau_a}").unwrap();
-                writeln!(file_output, "- tau_b={tau_b}").unwrap();
-                writeln!(file_output, "- tau_c={tau_c}").unwrap();
+                writeln!(file_output, "- amplitude_a={amplitude_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- amplitude_b={amplitude_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_c={tau_c}").map_err(to_output_write_err)?;
+            }
+            DV::SatExp_StretchedExp(..) => {
+                type SelfF = InitialValues_SatExp_StretchedExp<float>;
+                let SelfF { amplitude, shift, tau_a, tau_b, beta } = SelfF::from_vec(params);
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_a={tau_a}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau_b={tau_b}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- beta={beta}").map_err(to_output_write_err)?;
+            }
+            DV::LogNormalDecExp(..) => {
+                type SelfF = InitialValues_LogNormalDecExp<float>;
+                let SelfF { amplitude, shift, tau, sigma } = SelfF::from_vec(params);
+                writeln!(file_output, "- amplitude={amplitude}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- shift={shift}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- tau={tau}").map_err(to_output_write_err)?;
+                writeln!(file_output, "- sigma={sigma}").map_err(to_output_write_err)?;
+                // mean of log-normal distribution is bigger than median:
+                writeln!(file_output, "- tau_mean={}", tau * (sigma * sigma / 2.).exp()).map_err(to_output_write_err)?;
+            }
+            DV::Custom(custom) => {
+                writeln!(file_output, "- expression: {}", custom.expression).map_err(to_output_write_err)?;
+                for (name, value) in custom.expression.get_params_names().iter().zip(&params.0) {
+                    writeln!(file_output, "- {name}={value}").map_err(to_output_write_err)?;
+                }
+            }
+        }
+        if !self.irf_params.is_empty() {
+            let IrfValues { shift, broadening } = self.get_irf_values(&deconvolution_results.params);
+            writeln!(file_output, "\ninstrument params:").map_err(to_output_write_err)?;
+            if self.irf_params.shift.is_some() {
+                writeln!(file_output, "- {name}={shift}", name=IrfParams::SHIFT_NAME).map_err(to_output_write_err)?;
+            }
+            if self.irf_params.broadening.is_some() {
+                writeln!(file_output, "- {name}={broadening}", name=IrfParams::BROADENING_NAME).map_err(to_output_write_err)?;
+            }
+        }
+        if !self.add_ons.is_empty() {
+            let AddOnsValues { background, scatter } = self.get_add_ons_values(&deconvolution_results.params);
+            writeln!(file_output, "\nadd-ons:").map_err(to_output_write_err)?;
+            if self.add_ons.background.is_some() {
+                writeln!(file_output, "- {name}={background}", name=AddOns::BACKGROUND_NAME).map_err(to_output_write_err)?;
+            }
+            if self.add_ons.scatter.is_some() {
+                writeln!(file_output, "- {name}={scatter}", name=AddOns::SCATTER_NAME).map_err(to_output_write_err)?;
             }
         }
+        if let Some(params_uncertainties_msg) = self.build_params_uncertainties_msg(deconvolution_results, None) {
+            writeln!(file_output, "\n{params_uncertainties_msg}").map_err(to_output_write_err)?;
+        }
         if let Ok(desmos_function_str) = desmos_function_str {
-            writeln!(file_output, "\ndesmos function:\n{desmos_function_str}").unwrap();
+            writeln!(file_output, "\ndesmos function:\n{desmos_function_str}").map_err(to_output_write_err)?;
         }
         if let Ok(origin_function_str) = origin_function_str {
-            writeln!(file_output, "\norigin function:\n{origin_function_str}").unwrap();
+            writeln!(file_output, "\norigin function:\n{origin_function_str}").map_err(to_output_write_err)?;
         }
-        writeln!(file_output, "\nfit_algorithm = {fit_algorithm:?}").unwrap();
+        writeln!(file_output, "\nfit_algorithm = {fit_algorithm:?}").map_err(to_output_write_err)?;
+        Ok(())
+    }
+}
+
+impl FitData for DeconvolutionData {
+    type Prepared = (InstrumentRevV, MeasuredV);
+
+    fn prepare(&self) -> Self::Prepared {
+        (
+            Instrument(self.instrument.points.clone()).into(),
+            Measured(self.measured.points.clone()).into(),
+        )
+    }
+
+    fn get_params_amount(&self) -> usize {
+        DeconvolutionData::get_params_amount(self)
+    }
+
+    fn get_free_params_amount(&self) -> usize {
+        DeconvolutionData::get_free_params_amount(self)
+    }
+
+    fn is_residuals_in_sigmas(&self) -> bool {
+        DeconvolutionData::is_residuals_in_sigmas(self)
+    }
+
+    fn get_initial_params_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV {
+        DeconvolutionData::get_initial_params_randomized_with_rng_v(self, initial_values_random_scale, rng)
+    }
+
+    fn is_params_ok_v(&self, params: &ParamsV) -> bool {
+        DeconvolutionData::is_params_ok_v(self, params)
+    }
+
+    fn clamp_params_v(&self, params: &ParamsV) -> ParamsV {
+        DeconvolutionData::clamp_params_v(self, params)
+    }
+
+    fn calc_residue_function_v(&self, params: &ParamsV, (instrument_rev, measured): &Self::Prepared) -> float {
+        DeconvolutionData::calc_residue_function_v(self, params, instrument_rev, measured)
+    }
+
+    fn calc_residuals_v(&self, params: &ParamsV, (instrument_rev, measured): &Self::Prepared) -> DVect {
+        DeconvolutionData::calc_residuals_v(self, params, instrument_rev, measured)
     }
 }
 
@@ -324,15 +885,106 @@ pub enum AlignStepsTo {
 
 impl Load for AlignStepsTo {
     const TOML_NAME: &'static str = "align_steps_to";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
         let align_steps_to_str = toml_value
             .as_str()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("string"));
-        match align_steps_to_str {
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match align_steps_to_str {
             "bigger"  => AlignStepsTo::Bigger,
             "smaller" => AlignStepsTo::Smaller,
-            _ => stacktrace.panic_unknown_type(align_steps_to_str, ["bigger", "smaller"])
+            _ => return Err(stacktrace.err_unknown_type(align_steps_to_str, ["bigger", "smaller"]))
+        })
+    }
+}
+
+
+/// Which measured points are used in fit residue and fit statistics, selected by their `x`.
+///
+/// Convolution always uses all points.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FitMask {
+    /// Only points with `x` in this `(min, max)` interval are used, all if `None`.
+    pub window: Option<(float, float)>,
+    /// Points with `x` in any of this `(min, max)` intervals aren't used.
+    pub excluded: Vec<(float, float)>,
+}
+
+impl FitMask {
+    pub fn contains(&self, x: float) -> bool {
+        let is_in = |(min, max): (float, float)| min <= x && x <= max;
+        self.window.map_or(true, is_in) && !self.excluded.iter().any(|&interval| is_in(interval))
+    }
+
+    /// Indices of `spectrum` points used in fit, `None` if all points are used.
+    pub fn get_indices(&self, spectrum: &Spectrum) -> Option<Vec<usize>> {
+        if self.window.is_none() && self.excluded.is_empty() { return None }
+        Some(
+            (0..spectrum.points.len())
+                .filter(|&i| self.contains(spectrum.get_x_from_index(i)))
+                .collect()
+        )
+    }
+
+    /// Loads `fit_window = [x_min, x_max]` and `fit_exclude = [[x_min, x_max], …]` from `input_params`, both are optional.
+    pub fn load_from_input_params(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        fn load_interval(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<(float, float)> {
+            let to_float = |v: &TomlValue| v.as_float().or_else(|| v.as_integer().map(|i| i as float));
+            let (min, max) = match toml_value.as_array().map(|array| array.as_slice()) {
+                Some([min, max]) => (to_float(min), to_float(max)),
+                _ => (None, None),
+            };
+            let (Some(min), Some(max)) = (min, max) else {
+                return Err(stacktrace.err_cant_parse_as("`[x_min, x_max]`"));
+            };
+            if min >= max {
+                return Err(stacktrace.err(&format!("expected `x_min` < `x_max`, but they are {min} and {max}")));
+            }
+            Ok((min, max))
         }
+        let window = toml_value
+            .get("fit_window")
+            .map(|window| load_interval(window, &stacktrace.pushed("fit_window")))
+            .transpose()?;
+        let excluded = match toml_value.get("fit_exclude") {
+            None => vec![],
+            Some(excluded) => {
+                let stacktrace = stacktrace.pushed("fit_exclude");
+                excluded
+                    .as_array()
+                    .ok_or_else(|| stacktrace.err_cant_parse_as("array"))?
+                    .iter()
+                    .map(|interval| load_interval(interval, &stacktrace))
+                    .collect::<Result<_>>()?
+            }
+        };
+        Ok(Self { window, excluded })
+    }
+}
+
+
+/// Where [`sigmas`](DeconvolutionData::sigmas) of measured points come from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weights {
+    /// All points have same weight.
+    None,
+    /// Third column of measured file.
+    Sigmas,
+    /// `sigma = sqrt(y)`, see [`DeconvolutionData::with_poisson_sigmas`].
+    Poisson,
+}
+
+impl Load for Weights {
+    const TOML_NAME: &'static str = "weights";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let weights_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match weights_str {
+            "none"    => Weights::None,
+            "sigmas"  => Weights::Sigmas,
+            "poisson" => Weights::Poisson,
+            _ => return Err(stacktrace.err_unknown_type(weights_str, ["none", "sigmas", "poisson"]))
+        })
     }
 }
 
@@ -345,10 +997,13 @@ mod deconvolution_data {
     use crate::{
         deconvolution::DeconvolutionVariant,
         spectrum::Spectrum,
-        diff_function::DiffFunction,
+        diff_function::{DiffFunction, calc_least_dists_sq},
     };
     use super::super::{
-        deconvolution_data::{AlignStepsTo, DeconvolutionData},
+        add_ons::AddOns,
+        convolution::ConvolutionMethod,
+        deconvolution_data::{AlignStepsTo, DeconvolutionData, FitMask},
+        irf_params::IrfParams,
         types::{
             per_points::{InitialValues_PerPoint, PerPoint},
             value_and_domain::ValueAndDomain,
@@ -370,11 +1025,16 @@ mod deconvolution_data {
                         step: 0.2,
                         x_start: 0.3,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 },
                 DeconvolutionData {
                     instrument: Spectrum {
@@ -387,11 +1047,16 @@ mod deconvolution_data {
                         step: 0.2,
                         x_start: 0.3,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 }.aligned_steps_to(AlignStepsTo::Smaller)
             );
         }
@@ -409,11 +1074,16 @@ mod deconvolution_data {
                         step: 0.2,
                         x_start: 0.9,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 },
                 DeconvolutionData {
                     instrument: Spectrum {
@@ -426,11 +1096,16 @@ mod deconvolution_data {
                         step: 0.4,
                         x_start: 0.9,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 }.aligned_steps_to(AlignStepsTo::Smaller)
             );
         }
@@ -451,11 +1126,16 @@ mod deconvolution_data {
                         step: 0.4,
                         x_start: 0.5,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 },
                 DeconvolutionData {
                     instrument: Spectrum {
@@ -468,11 +1148,16 @@ mod deconvolution_data {
                         step: 0.2,
                         x_start: 0.5,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 }.aligned_steps_to(AlignStepsTo::Bigger)
             );
         }
@@ -490,11 +1175,16 @@ mod deconvolution_data {
                         step: 0.4,
                         x_start: 0.9,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 },
                 DeconvolutionData {
                     instrument: Spectrum {
@@ -507,14 +1197,288 @@ mod deconvolution_data {
                         step: 0.4,
                         x_start: 0.9,
                     },
+                    sigmas: None,
+                    fit_mask: FitMask::default(),
+                    irf_params: IrfParams::default(),
+                    add_ons: AddOns::default(),
                     deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                         diff_function_type: DiffFunction::DySqr,
                         antispikes: None,
                         initial_vad: InitialValues_PerPoint::new(9, ValueAndDomain::free(0.)),
                     }),
+                    convolution_method: ConvolutionMethod::Direct,
                 }.aligned_steps_to(AlignStepsTo::Bigger)
             );
         }
     }
-}
 
+    #[test]
+    fn align_with_sigmas() {
+        let deconvolution_data = DeconvolutionData {
+            instrument: Spectrum {
+                points: vec![0., 1., 0.],
+                step: 0.2,
+                x_start: 0.,
+            },
+            measured: Spectrum {
+                points: vec![0., 1., 0.],
+                step: 0.4,
+                x_start: 0.,
+            },
+            sigmas: Some(vec![1., 3., 1.]),
+            fit_mask: FitMask::default(),
+            irf_params: IrfParams::default(),
+            add_ons: AddOns::default(),
+            deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
+                diff_function_type: DiffFunction::DySqr,
+                antispikes: None,
+                initial_vad: InitialValues_PerPoint::new(3, ValueAndDomain::free(0.)),
+            }),
+            convolution_method: ConvolutionMethod::Direct,
+        }.aligned_steps_to(AlignStepsTo::Smaller);
+        assert_eq!(vec![0., 0.5, 1., 0.4999999999999999, 0.], deconvolution_data.measured.points);
+        assert_eq!(Some(vec![1., 2., 3., 1.9999999999999996, 1.]), deconvolution_data.sigmas);
+    }
+
+    mod chi_square {
+        use crate::{fit_algorithms::Fit, types::{float::float, linalg::DVect, named_wrappers::{Instrument, Measured, ParamsG, ParamsV}}};
+        use super::*;
+
+        fn deconvolution_data(initial_vad: ValueAndDomain) -> DeconvolutionData {
+            DeconvolutionData {
+                instrument: Spectrum { points: vec![1.], step: 1., x_start: 0. },
+                measured: Spectrum { points: vec![0., 4., 9.], step: 1., x_start: 0. },
+                sigmas: None,
+                fit_mask: FitMask::default(),
+                irf_params: IrfParams::default(),
+                add_ons: AddOns::default(),
+                deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
+                    diff_function_type: DiffFunction::DySqr,
+                    antispikes: None,
+                    initial_vad: InitialValues_PerPoint::new(3, initial_vad),
+                }),
+                convolution_method: ConvolutionMethod::Direct,
+            }
+        }
+
+        fn fit(params: Vec<float>) -> Fit {
+            Fit { params: ParamsG(params), fit_residue: 0., fit_residue_evals: 0, covariance: None, regularization_lambda: None }
+        }
+
+        #[test]
+        fn unweighted() {
+            let deconvolution_data = deconvolution_data(ValueAndDomain::fixed(0.));
+            assert_eq!(97., deconvolution_data.calc_chi_square(&fit(vec![0., 0., 0.])));
+            assert_eq!(97. / 3., deconvolution_data.calc_reduced_chi_square(&fit(vec![0., 0., 0.])));
+            // all params are fixed, so it isn't adjusted:
+            assert_eq!(
+                deconvolution_data.calc_r_square(&fit(vec![1., 4., 8.])),
+                deconvolution_data.calc_adjusted_r_square(&fit(vec![1., 4., 8.])),
+            );
+        }
+
+        #[test]
+        fn poisson() {
+            let deconvolution_data = deconvolution_data(ValueAndDomain::fixed(0.)).with_poisson_sigmas();
+            assert_eq!(Some(vec![1., 2., 3.]), deconvolution_data.sigmas);
+            assert_eq!(0. + 4. + 9., deconvolution_data.calc_chi_square(&fit(vec![0., 0., 0.])));
+            assert_eq!(13. / 3., deconvolution_data.calc_reduced_chi_square(&fit(vec![0., 0., 0.])));
+            // r square isn't weighted:
+            assert_eq!(
+                DeconvolutionData { sigmas: None, ..deconvolution_data.clone() }.calc_r_square(&fit(vec![1., 4., 8.])),
+                deconvolution_data.calc_r_square(&fit(vec![1., 4., 8.])),
+            );
+        }
+
+        #[test]
+        fn masked() {
+            let deconvolution_data = DeconvolutionData {
+                fit_mask: FitMask { window: Some((0.5, 10.)), excluded: vec![(1.5, 2.5)] },
+                irf_params: IrfParams::default(),
+                add_ons: AddOns::default(),
+                ..deconvolution_data(ValueAndDomain::fixed(0.))
+            };
+            assert_eq!(1, deconvolution_data.get_fit_points_len());
+            assert_eq!(16., deconvolution_data.calc_chi_square(&fit(vec![0., 0., 0.])));
+            assert_eq!(16., deconvolution_data.calc_reduced_chi_square(&fit(vec![0., 0., 0.])));
+            // residue ignores excluded points:
+            assert_eq!(
+                4.,
+                deconvolution_data.calc_residue_function_v(
+                    &ParamsV(DVect::from_vec(vec![0., 0., 123.])),
+                    &Instrument(deconvolution_data.instrument.points.clone()).into(),
+                    &Measured(deconvolution_data.measured.points.clone()).into(),
+                ),
+            );
+        }
+
+        #[test]
+        fn least_dist_masked_and_weighted() {
+            let deconvolution_data = DeconvolutionData {
+                sigmas: Some(vec![1., 0.5, 1.]),
+                fit_mask: FitMask { window: Some((0.5, 1.5)), excluded: vec![] },
+                deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
+                    diff_function_type: DiffFunction::LeastDist { x_scale: 1., y_scale: 1. },
+                    antispikes: None,
+                    initial_vad: InitialValues_PerPoint::new(3, ValueAndDomain::free(0.)),
+                }),
+                ..deconvolution_data(ValueAndDomain::free(0.))
+            };
+            // distance from middle point to convolved curve, which is calculated using excluded neighbours:
+            let dist: float = calc_least_dists_sq(&[0., 4., 9.], &[0., 0., 9.], 1., 1.)[1].sqrt();
+            assert!(dist < 4.);
+            assert_eq!(
+                dist / 0.5,
+                deconvolution_data.calc_residue_function_v(
+                    &ParamsV(DVect::from_vec(vec![0., 0., 9.])),
+                    &Instrument(deconvolution_data.instrument.points.clone()).into(),
+                    &Measured(deconvolution_data.measured.points.clone()).into(),
+                ),
+            );
+        }
+
+        #[test]
+        fn r_square_of_constant_measured() {
+            let deconvolution_data = DeconvolutionData {
+                measured: Spectrum { points: vec![0., 0., 0.], step: 1., x_start: 0. },
+                ..deconvolution_data(ValueAndDomain::fixed(0.))
+            };
+            assert!(deconvolution_data.calc_r_square(&fit(vec![0., 0., 0.])).is_nan());
+            assert!(deconvolution_data.calc_r_square(&fit(vec![1., 0., 0.])).is_nan());
+            assert!(deconvolution_data.calc_adjusted_r_square(&fit(vec![1., 0., 0.])).is_nan());
+        }
+
+        #[test]
+        fn no_degrees_of_freedom() {
+            let deconvolution_data = deconvolution_data(ValueAndDomain::free(0.));
+            assert!(deconvolution_data.calc_reduced_chi_square(&fit(vec![0., 0., 0.])).is_nan());
+        }
+    }
+    mod fit_mask {
+        use toml::Value as TomlValue;
+        use crate::stacktrace::Stacktrace;
+        use super::*;
+
+        #[test]
+        fn get_indices() {
+            let spectrum = Spectrum { points: vec![0.; 10], step: 0.5, x_start: 1. };
+            assert_eq!(None, FitMask::default().get_indices(&spectrum));
+            assert_eq!(
+                Some(vec![1, 2, 6, 7]),
+                FitMask { window: Some((1.5, 4.5)), excluded: vec![(2.2, 3.8)] }.get_indices(&spectrum)
+            );
+        }
+
+        #[test]
+        fn load() {
+            let toml_value: TomlValue = toml::toml! {
+                fit_window = [1, 100.5]
+                fit_exclude = [[10.0, 12.0], [50, 51]]
+            }.into();
+            assert_eq!(
+                FitMask { window: Some((1., 100.5)), excluded: vec![(10., 12.), (50., 51.)] },
+                FitMask::load_from_input_params(&toml_value, &Stacktrace::empty()).unwrap()
+            );
+            assert_eq!(
+                FitMask::default(),
+                FitMask::load_from_input_params(&toml::toml! { x = 1 }.into(), &Stacktrace::empty()).unwrap()
+            );
+        }
+
+        #[test]
+        fn load_wrong_order() {
+            let toml_value: TomlValue = toml::toml! { fit_window = [5.0, 1.0] }.into();
+            assert_eq!(
+                "`fit_window`: expected `x_min` < `x_max`, but they are 5 and 1",
+                FitMask::load_from_input_params(&toml_value, &Stacktrace::empty()).unwrap_err().to_string()
+            );
+        }
+    }
+    mod deconvolve_directly {
+        use crate::fit_algorithms::{FitAlgorithmVariant, pattern_search::PatternSearch};
+        use super::super::super::types::fourier::{Fourier, FourierRegularization};
+        use super::*;
+
+        #[test]
+        fn fourier_with_background() {
+            let deconvolution_data = DeconvolutionData {
+                instrument: Spectrum { points: vec![0., 1., 0.], step: 1., x_start: -1. },
+                measured: Spectrum { points: vec![1., 2., 4., 3., 1.], step: 1., x_start: 0. },
+                sigmas: None,
+                fit_mask: FitMask::default(),
+                irf_params: IrfParams::default(),
+                add_ons: AddOns { background: Some(ValueAndDomain::fixed(1.)), scatter: None },
+                deconvolution: DeconvolutionVariant::Fourier(Fourier {
+                    diff_function_type: DiffFunction::DySqr,
+                    regularization: FourierRegularization::Wiener { k: 0. },
+                    apodization: None,
+                    initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+                }),
+                convolution_method: ConvolutionMethod::Direct,
+            }.sized_to_measured();
+            // fit algorithm isn't used:
+            let fit_algorithm = FitAlgorithmVariant::PatternSearch(PatternSearch {
+                fit_algorithm_min_step: 1e-4,
+                fit_residue_evals_max: 0,
+                initial_step: 1.,
+                alpha: 1.1,
+                beta: None,
+            });
+            let fit = deconvolution_data.deconvolve(&fit_algorithm, None).unwrap();
+            let params_expected = [0., 1., 3., 2., 0., 1.];
+            assert_eq!(params_expected.len(), fit.params.0.len());
+            for (expected, actual) in params_expected.iter().zip(&fit.params.0) {
+                assert!((expected - actual).abs() < 1e-12, "expected: {params_expected:?}, actual: {:?}", fit.params.0);
+            }
+            assert!(fit.fit_residue < 1e-12);
+            assert_eq!(0, fit.fit_residue_evals);
+        }
+    }
+    mod robust_scale {
+        use crate::{
+            diff_function::RobustScale,
+            fit_algorithms::{FitAlgorithmVariant, nelder_mead::NelderMead},
+            load::Load,
+            stacktrace::Stacktrace,
+            types::float::float,
+        };
+        use super::super::super::{
+            test_fixtures::deconvolution_data_sat_exp_dec_exp,
+            types::sat_exp__dec_exp::InitialValues_SatExp_DecExp,
+        };
+        use super::*;
+
+        #[test]
+        fn mad_with_many_zero_points() {
+            let params_true = [2., 60., 3., 15.];
+            let mut deconvolution_data = deconvolution_data_sat_exp_dec_exp(
+                InitialValues_SatExp_DecExp {
+                    amplitude: ValueAndDomain::free(1.),
+                    shift: ValueAndDomain::free(58.),
+                    tau_a: ValueAndDomain::range_with_min(5., 0.),
+                    tau_b: ValueAndDomain::range_with_min(10., 0.),
+                },
+                params_true,
+            );
+            // about half of measured points are zero, and others are noisy:
+            for (i, point) in deconvolution_data.measured.points.iter_mut().enumerate() {
+                if *point > 1e-6 {
+                    *point += 0.01 * ((i * 7 % 5) as float - 2.);
+                }
+            }
+            deconvolution_data.deconvolution.set_diff_function_type(DiffFunction::Huber(RobustScale::Mad));
+            let fit_algorithm = FitAlgorithmVariant::NelderMead(NelderMead::load_from_self(
+                &toml::toml! {
+                    fit_algorithm_min_step = 1e-8
+                    fit_residue_evals_max = 100_000
+                    initial_step = 1.0
+                }.into(),
+                &Stacktrace::empty(),
+            ).unwrap());
+            let fit = deconvolution_data.deconvolve(&fit_algorithm, None).unwrap();
+            for (expected, actual) in params_true.iter().zip(&fit.params.0) {
+                assert!((expected - actual).abs() < 0.1 * expected, "expected: {params_true:?}, actual: {:?}", fit.params.0);
+            }
+        }
+    }
+}
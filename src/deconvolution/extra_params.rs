@@ -0,0 +1,87 @@
+//! Extra params, available for any deconvolution variant, fitted together with its params.
+
+use std::collections::HashMap;
+
+use rand::rngs::ThreadRng;
+use toml::Value as TomlValue;
+
+use crate::{
+    error::Result,
+    stacktrace::Stacktrace,
+    types::float::float,
+};
+
+use super::types::value_and_domain::ValueAndDomain;
+
+
+// "have to be implemented" part
+pub trait ExtraParams {
+    /// Names and initial values of enabled params, in the same order as they are in params vector.
+    fn get_names_and_vads(&self) -> Vec<(&'static str, ValueAndDomain)>;
+}
+
+
+// "will be auto implemented" part
+pub trait ExtraParamsAutoImplFns {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    fn get_names(&self) -> Vec<String>;
+    fn get_vads(&self) -> Vec<ValueAndDomain>;
+    fn get_randomized_with_rng(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> Vec<float>;
+    fn is_params_ok(&self, params: &[float]) -> bool;
+    fn clamp_params(&self, params: &[float]) -> Vec<float>;
+}
+
+impl<T: ExtraParams> ExtraParamsAutoImplFns for T {
+    fn len(&self) -> usize {
+        self.get_names_and_vads().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get_names(&self) -> Vec<String> {
+        self.get_names_and_vads().into_iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    fn get_vads(&self) -> Vec<ValueAndDomain> {
+        self.get_names_and_vads().into_iter().map(|(_, vad)| vad).collect()
+    }
+
+    fn get_randomized_with_rng(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> Vec<float> {
+        self.get_vads().iter().map(|vad| vad.get_randomized_with_rng(initial_values_random_scale, rng)).collect()
+    }
+
+    fn is_params_ok(&self, params: &[float]) -> bool {
+        assert_eq!(self.len(), params.len());
+        self.get_vads().iter().zip(params).all(|(vad, &value)| vad.contains(value))
+    }
+
+    fn clamp_params(&self, params: &[float]) -> Vec<float> {
+        assert_eq!(self.len(), params.len());
+        self.get_vads().iter().zip(params).map(|(vad, &value)| vad.clamp(value)).collect()
+    }
+}
+
+
+/// Load extra params from string like `"name_1=1, name_2=0.1>0"`,
+/// where names are from `known_names` and each can be absent.
+pub fn load_vads_from_str<const N: usize>(
+    toml_value: &TomlValue,
+    known_names: [&str; N],
+    stacktrace: &Stacktrace,
+) -> Result<HashMap<String, ValueAndDomain>> {
+    let str = toml_value
+        .as_str()
+        .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+    let vads: HashMap<String, ValueAndDomain> = str
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
+        .collect::<Result<_>>()?;
+    if let Some(name) = vads.keys().find(|name| !known_names.contains(&name.as_str())) {
+        return Err(stacktrace.err_unknown_type(name, known_names));
+    }
+    Ok(vads)
+}
@@ -0,0 +1,124 @@
+//! Fast Fourier Transform.
+
+use nalgebra::Complex;
+
+use crate::types::float::{consts::TAU, float};
+
+
+#[allow(non_camel_case_types)]
+pub type complex = Complex<float>;
+
+
+/// Forward FFT (in place), `values.len()` must be power of two.
+pub fn fft(values: &mut [complex]) {
+    fft_radix2(values, false);
+}
+
+/// Inverse FFT (in place, normalized), `values.len()` must be power of two.
+pub fn ifft(values: &mut [complex]) {
+    fft_radix2(values, true);
+    let len = values.len() as float;
+    for value in values.iter_mut() {
+        *value /= len;
+    }
+}
+
+/// Iterative radix-2 Cooley–Tukey FFT.
+fn fft_radix2(values: &mut [complex], is_inverse: bool) {
+    let len: usize = values.len();
+    if len <= 1 { return }
+    assert!(len.is_power_of_two(), "FFT len must be power of two, but it is {len}");
+
+    // bit reversal permutation:
+    let mut j: usize = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let sign: float = if is_inverse { 1. } else { -1. };
+    // twiddle factors for the biggest stage, smaller stages use every `len / (2*half_len)`-th of them:
+    let twiddles: Vec<complex> = (0..len/2)
+        .map(|k| {
+            let angle: float = sign * TAU * (k as float) / (len as float);
+            complex::new(angle.cos(), angle.sin())
+        })
+        .collect();
+    let mut half_len: usize = 1;
+    while half_len < len {
+        let twiddles_stride: usize = len / (2 * half_len);
+        for chunk in values.chunks_exact_mut(2 * half_len) {
+            let (lhs, rhs) = chunk.split_at_mut(half_len);
+            for (k, (l, r)) in lhs.iter_mut().zip(rhs.iter_mut()).enumerate() {
+                let t = twiddles[k * twiddles_stride] * *r;
+                *r = *l - t;
+                *l += t;
+            }
+        }
+        half_len *= 2;
+    }
+}
+
+
+
+#[cfg(test)]
+mod fft_ifft {
+    use super::*;
+
+    fn dft(values: &[complex]) -> Vec<complex> {
+        let len = values.len();
+        (0..len)
+            .map(|k| {
+                values.iter().enumerate()
+                    .map(|(n, v)| {
+                        let angle = -TAU * ((k * n) as float) / (len as float);
+                        v * complex::new(angle.cos(), angle.sin())
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn assert_close(expected: &[complex], actual: &[complex]) {
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).norm_sqr().sqrt() < 1e-9, "expected: {expected:?}\nactual: {actual:?}");
+        }
+    }
+
+    #[test]
+    fn same_as_dft() {
+        for len in [1, 2, 4, 8, 16, 64] {
+            let values: Vec<complex> = (0..len)
+                .map(|i| complex::new((i as float).sin(), (i as float * 0.3).cos()))
+                .collect();
+            let mut values_fft = values.clone();
+            fft(&mut values_fft);
+            assert_close(&dft(&values), &values_fft);
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let values: Vec<complex> = (0..32)
+            .map(|i| complex::new((i as float).sqrt(), -(i as float)))
+            .collect();
+        let mut values_fft_ifft = values.clone();
+        fft(&mut values_fft_ifft);
+        ifft(&mut values_fft_ifft);
+        assert_close(&values, &values_fft_ifft);
+    }
+
+    #[should_panic]
+    #[test]
+    fn not_power_of_two() {
+        fft(&mut [complex::new(0., 0.); 3]);
+    }
+}
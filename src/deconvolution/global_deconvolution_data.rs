@@ -0,0 +1,390 @@
+//! Global Deconvolution Data: several measured spectra, fitted together.
+
+use std::{fs::File, io::{self, Write}, path::Path};
+
+use rand::rngs::ThreadRng;
+
+use crate::{
+    error::{Error, Result},
+    fit_algorithms::{Fit, FitAlgorithmVariant, FitData, FitDataAutoImplFns, FitResult},
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::{DMat, DVect},
+        named_wrappers::{Params, ParamsG, ParamsV},
+    },
+};
+
+use super::deconvolution_data::DeconvolutionData;
+
+
+/// Several measured spectra, fitted together by the same function,
+/// with some params shared between all of them (e.g. taus), and others local for each one (e.g. amplitudes and shifts).
+///
+/// Params are: shared ones, then local ones of the first dataset, then local ones of the second dataset and so on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalDeconvolutionData {
+    pub datasets: Vec<DeconvolutionData>,
+    /// For each param of dataset: is it shared between all datasets.
+    is_shared: Vec<bool>,
+}
+
+impl GlobalDeconvolutionData {
+    /// Param is shared, if it's name starts with any of `shared_params`.
+    ///
+    /// All `datasets` must have same params (e.g. `PerPoint` can't be used with spectra of different lengths).
+    pub fn new(datasets: Vec<DeconvolutionData>, shared_params: &[String]) -> Result<Self> {
+        assert!(!datasets.is_empty());
+        if !datasets[0].deconvolution.is_fitted() {
+            return Err(Stacktrace::new("global_fit").err("can't be used with deconvolution function, which isn't fitted"));
+        }
+        let params_names: Vec<String> = datasets[0].get_params_names();
+        if datasets.iter().any(|dataset| dataset.get_params_names() != params_names) {
+            return Err(Stacktrace::new("global_fit").err("all measured spectra must have same params"));
+        }
+        let stacktrace_shared_params = Stacktrace::new("global_fit").pushed("shared_params");
+        // probably misspelled, so it would be silently fitted as local:
+        for shared_param in shared_params {
+            if !params_names.iter().any(|name| name.starts_with(shared_param.as_str())) {
+                return Err(stacktrace_shared_params.err(&format!(
+                    "`{shared_param}` doesn't match any param, params are: {params_names}",
+                    params_names=params_names.join(", "),
+                )));
+            }
+        }
+        let is_shared: Vec<bool> = params_names.iter()
+            .map(|name| shared_params.iter().any(|shared_param| name.starts_with(shared_param.as_str())))
+            .collect();
+        if !is_shared.contains(&true) {
+            return Err(stacktrace_shared_params.err("must contain at least one param, else it's same as fitting each spectrum separately"));
+        }
+        Ok(Self { datasets, is_shared })
+    }
+
+    fn get_shared_params_amount(&self) -> usize {
+        self.is_shared.iter().filter(|&&is_shared| is_shared).count()
+    }
+
+    fn get_local_params_amount(&self) -> usize {
+        self.is_shared.len() - self.get_shared_params_amount()
+    }
+
+    pub fn get_shared_params_names(&self) -> Vec<String> {
+        self.datasets[0].get_params_names().into_iter()
+            .zip(&self.is_shared)
+            .filter_map(|(name, &is_shared)| is_shared.then_some(name))
+            .collect()
+    }
+
+    pub fn get_local_params_names(&self) -> Vec<String> {
+        self.datasets[0].get_params_names().into_iter()
+            .zip(&self.is_shared)
+            .filter_map(|(name, &is_shared)| (!is_shared).then_some(name))
+            .collect()
+    }
+
+    /// Index of `dataset_i`-th dataset's `param_i`-th param in global params.
+    fn get_global_index(&self, dataset_i: usize, param_i: usize) -> usize {
+        let shared_before = self.is_shared[..param_i].iter().filter(|&&is_shared| is_shared).count();
+        if self.is_shared[param_i] {
+            shared_before
+        } else {
+            let local_before = param_i - shared_before;
+            self.get_shared_params_amount() + dataset_i * self.get_local_params_amount() + local_before
+        }
+    }
+
+    /// Params of `dataset_i`-th dataset from global params.
+    pub fn params_to_dataset_params(&self, params: &[float], dataset_i: usize) -> Vec<float> {
+        assert_eq!(self.get_params_amount(), params.len());
+        (0..self.is_shared.len())
+            .map(|param_i| params[self.get_global_index(dataset_i, param_i)])
+            .collect()
+    }
+
+    /// Global params from params of each dataset, shared ones are taken from the first dataset.
+    pub fn datasets_params_to_params(&self, datasets_params: &[Vec<float>]) -> Vec<float> {
+        assert_eq!(self.datasets.len(), datasets_params.len());
+        let mut params: Vec<float> = vec![0.; self.get_params_amount()];
+        for (dataset_i, dataset_params) in datasets_params.iter().enumerate().rev() {
+            assert_eq!(self.is_shared.len(), dataset_params.len());
+            for (param_i, &param) in dataset_params.iter().enumerate() {
+                params[self.get_global_index(dataset_i, param_i)] = param;
+            }
+        }
+        params
+    }
+
+    pub fn get_initial_params(&self) -> Params {
+        let datasets_params: Vec<Vec<float>> = self.datasets.iter()
+            .map(|dataset| dataset.get_initial_params().0)
+            .collect();
+        ParamsG::<float>(self.datasets_params_to_params(&datasets_params))
+    }
+
+    pub fn deconvolve(
+        &self,
+        fit_algorithm: &FitAlgorithmVariant,
+        initial_values_random_scale: Option<float>,
+    ) -> FitResult {
+        let initial_params = if let Some(initial_values_random_scale) = initial_values_random_scale {
+            let mut rng = rand::thread_rng();
+            ParamsG::<float>(self.get_initial_params_randomized_with_rng_v(initial_values_random_scale, &mut rng).0.data.into())
+        } else {
+            self.get_initial_params()
+        };
+        // robust scale (if any) is estimated for each dataset separately, bc their noise can differ:
+        let global_deconvolution_data = Self {
+            datasets: self.datasets.iter().map(DeconvolutionData::with_robust_scale_estimated).collect(),
+            is_shared: self.is_shared.clone(),
+        };
+        let fit = fit_algorithm.fit(&global_deconvolution_data, initial_params)?;
+        let covariance = fit.covariance.or_else(|| global_deconvolution_data.calc_covariance(&fit.params));
+        Ok(Fit { covariance, ..fit })
+    }
+
+    /// Fit of `dataset_i`-th dataset from global fit: it's params, residue and covariance of it's params.
+    pub fn get_dataset_fit(&self, fit: &Fit, dataset_i: usize) -> Fit {
+        let dataset = &self.datasets[dataset_i];
+        let params: Params = ParamsG::<float>(self.params_to_dataset_params(&fit.params.0, dataset_i));
+        let fit_residue = FitData::calc_residue_function_v(dataset, &params.clone().into(), &dataset.prepare());
+        let global_indices: Vec<usize> = (0..self.is_shared.len())
+            .map(|param_i| self.get_global_index(dataset_i, param_i))
+            .collect();
+        let covariance: Option<DMat> = fit.covariance.as_ref().map(|covariance| {
+            DMat::from_fn(global_indices.len(), global_indices.len(), |i, j| covariance[(global_indices[i], global_indices[j])])
+        });
+        Fit {
+            params,
+            fit_residue,
+            fit_residue_evals: fit.fit_residue_evals,
+            covariance,
+            regularization_lambda: None,
+        }
+    }
+
+    /// Write shared params and fit goodness into `filepathstr_output`,
+    /// and local params of each dataset (decay-associated spectra, if amplitudes are local) into csv file near it.
+    pub fn write_result_to_file(
+        &self,
+        filepathstr_output: &str,
+        fit: &Fit,
+        filepathstrs_measured: &[String],
+    ) -> Result<()> {
+        assert_eq!(self.datasets.len(), filepathstrs_measured.len());
+        let filepath_output = Path::new(filepathstr_output);
+        let filepath_output_das = filepath_output.with_file_name(format!(
+            "{stem}_das.csv",
+            stem=filepath_output.file_stem().unwrap().to_str().unwrap(),
+        ));
+        let filepathstr_output_das: &str = filepath_output_das.to_str().unwrap();
+
+        let to_output_write_err = |filepath: &str| {
+            let filepath = filepath.to_string();
+            move |io_error: io::Error| Error::OutputWrite { filepath: filepath.clone(), io_error }
+        };
+        {
+            let to_output_write_err = to_output_write_err(filepathstr_output);
+            let mut file_output = File::create(filepathstr_output).map_err(&to_output_write_err)?;
+            writeln!(file_output, "name: {name}", name=self.datasets[0].deconvolution.get_name()).map_err(&to_output_write_err)?;
+            writeln!(file_output, "global fit of {n} measured spectra:", n=filepathstrs_measured.len()).map_err(&to_output_write_err)?;
+            for filepathstr_measured in filepathstrs_measured {
+                writeln!(file_output, "- {filepathstr_measured}").map_err(&to_output_write_err)?;
+            }
+            writeln!(file_output, "\nfit goodness (achieved after {fre} fit residue function evals):", fre=fit.fit_residue_evals).map_err(&to_output_write_err)?;
+            writeln!(file_output, "- fit residue: {fit_residue}", fit_residue=fit.fit_residue).map_err(&to_output_write_err)?;
+            let chi_square: float = self.datasets.iter().enumerate()
+                .map(|(dataset_i, dataset)| dataset.calc_chi_square(&self.get_dataset_fit(fit, dataset_i)))
+                .sum();
+            writeln!(file_output, "- chi square: {chi_square}").map_err(&to_output_write_err)?;
+            writeln!(file_output, "\nshared params:").map_err(&to_output_write_err)?;
+            let standard_errors: Option<Vec<float>> = fit.get_standard_errors();
+            for (param_i, name) in self.get_shared_params_names().iter().enumerate() {
+                let value = fit.params.0[param_i];
+                match &standard_errors {
+                    Some(standard_errors) => writeln!(file_output, "- {name}={value} ± {se}", se=standard_errors[param_i]),
+                    None => writeln!(file_output, "- {name}={value}"),
+                }.map_err(&to_output_write_err)?;
+            }
+            writeln!(file_output, "\nlocal params of each measured spectrum are in `{filepathstr_output_das}`").map_err(&to_output_write_err)?;
+        }
+        {
+            let to_output_write_err = to_output_write_err(filepathstr_output_das);
+            let mut file_output_das = File::create(filepathstr_output_das).map_err(&to_output_write_err)?;
+            let header: Vec<String> = ["measured".to_string()].into_iter().chain(self.get_local_params_names()).collect();
+            writeln!(file_output_das, "{}", header.join(",")).map_err(&to_output_write_err)?;
+            for (dataset_i, filepathstr_measured) in filepathstrs_measured.iter().enumerate() {
+                let local_params: Vec<String> = self.params_to_dataset_params(&fit.params.0, dataset_i).into_iter()
+                    .zip(&self.is_shared)
+                    .filter(|(_, &is_shared)| !is_shared)
+                    .map(|(param, _)| param.to_string())
+                    .collect();
+                writeln!(file_output_das, "{filepathstr_measured},{}", local_params.join(",")).map_err(&to_output_write_err)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FitData for GlobalDeconvolutionData {
+    type Prepared = Vec<<DeconvolutionData as FitData>::Prepared>;
+
+    fn prepare(&self) -> Self::Prepared {
+        self.datasets.iter().map(|dataset| dataset.prepare()).collect()
+    }
+
+    fn get_params_amount(&self) -> usize {
+        self.get_shared_params_amount() + self.datasets.len() * self.get_local_params_amount()
+    }
+
+    /// Shared params are counted once, local ones for each dataset.
+    fn get_free_params_amount(&self) -> usize {
+        let count_free = |dataset: &DeconvolutionData, shared: bool| -> usize {
+            dataset.get_initial_vads().iter().zip(&self.is_shared)
+                .filter(|(vad, &is_shared)| is_shared == shared && !vad.is_fixed())
+                .count()
+        };
+        count_free(&self.datasets[0], true) + self.datasets.iter().map(|dataset| count_free(dataset, false)).sum::<usize>()
+    }
+
+    fn is_residuals_in_sigmas(&self) -> bool {
+        self.datasets.iter().all(|dataset| dataset.is_residuals_in_sigmas())
+    }
+
+    fn get_initial_params_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV {
+        let datasets_params: Vec<Vec<float>> = self.datasets.iter()
+            .map(|dataset| dataset.get_initial_params_randomized_with_rng_v(initial_values_random_scale, rng).0.data.into())
+            .collect();
+        ParamsV(DVect::from_vec(self.datasets_params_to_params(&datasets_params)))
+    }
+
+    fn is_params_ok_v(&self, params: &ParamsV) -> bool {
+        self.datasets.iter().enumerate().all(|(dataset_i, dataset)| {
+            dataset.is_params_ok_v(&ParamsV(DVect::from_vec(self.params_to_dataset_params(params.0.as_slice(), dataset_i))))
+        })
+    }
+
+    fn clamp_params_v(&self, params: &ParamsV) -> ParamsV {
+        let datasets_params: Vec<Vec<float>> = self.datasets.iter().enumerate()
+            .map(|(dataset_i, dataset)| {
+                dataset.clamp_params_v(&ParamsV(DVect::from_vec(self.params_to_dataset_params(params.0.as_slice(), dataset_i)))).0.data.into()
+            })
+            .collect();
+        ParamsV(DVect::from_vec(self.datasets_params_to_params(&datasets_params)))
+    }
+
+    fn calc_residue_function_v(&self, params: &ParamsV, prepared: &Self::Prepared) -> float {
+        let diff_function = self.datasets[0].deconvolution.get_diff_function_type();
+        diff_function.combine_residues(
+            self.datasets.iter().zip(prepared).enumerate().map(|(dataset_i, (dataset, dataset_prepared))| {
+                let dataset_params = ParamsV(DVect::from_vec(self.params_to_dataset_params(params.0.as_slice(), dataset_i)));
+                FitData::calc_residue_function_v(dataset, &dataset_params, dataset_prepared)
+            })
+        )
+    }
+
+    fn calc_residuals_v(&self, params: &ParamsV, prepared: &Self::Prepared) -> DVect {
+        let residuals: Vec<float> = self.datasets.iter().zip(prepared).enumerate()
+            .flat_map(|(dataset_i, (dataset, dataset_prepared))| {
+                let dataset_params = ParamsV(DVect::from_vec(self.params_to_dataset_params(params.0.as_slice(), dataset_i)));
+                FitData::calc_residuals_v(dataset, &dataset_params, dataset_prepared).data.as_vec().clone()
+            })
+            .collect();
+        DVect::from_vec(residuals)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        deconvolution::{
+            test_fixtures::deconvolution_data_sat_exp_dec_exp,
+            types::{sat_exp__dec_exp::InitialValues_SatExp_DecExp, value_and_domain::ValueAndDomain},
+        },
+        fit_algorithms::levenberg_marquardt::LevenbergMarquardt,
+    };
+    use super::*;
+
+    fn deconvolution_data(params_true: [float; 4]) -> DeconvolutionData {
+        deconvolution_data_sat_exp_dec_exp(
+            InitialValues_SatExp_DecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::free(18.),
+                tau_a: ValueAndDomain::range_with_min(5., 0.),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+            },
+            params_true,
+        )
+    }
+
+    #[test]
+    fn params_mapping() {
+        let global_deconvolution_data = GlobalDeconvolutionData::new(
+            vec![deconvolution_data([2., 20., 3., 15.]), deconvolution_data([0.5, 18., 3., 15.])],
+            &["tau".to_string()],
+        ).unwrap();
+        assert_eq!(vec!["tau_a", "tau_b"], global_deconvolution_data.get_shared_params_names());
+        assert_eq!(vec!["amplitude", "shift"], global_deconvolution_data.get_local_params_names());
+        assert_eq!(6, global_deconvolution_data.get_params_amount());
+        let params = global_deconvolution_data.datasets_params_to_params(&[vec![2., 20., 3., 15.], vec![0.5, 18., 3., 15.]]);
+        assert_eq!(vec![3., 15., 2., 20., 0.5, 18.], params);
+        assert_eq!(vec![2., 20., 3., 15.], global_deconvolution_data.params_to_dataset_params(&params, 0));
+        assert_eq!(vec![0.5, 18., 3., 15.], global_deconvolution_data.params_to_dataset_params(&params, 1));
+    }
+
+    #[test]
+    fn fit_shared_taus() {
+        let global_deconvolution_data = GlobalDeconvolutionData::new(
+            vec![deconvolution_data([2., 20., 3., 15.]), deconvolution_data([0.5, 18., 3., 15.])],
+            &["tau".to_string()],
+        ).unwrap();
+        let fit_algorithm = FitAlgorithmVariant::LevenbergMarquardt(LevenbergMarquardt {
+            fit_residue_evals_max: 100_000,
+            initial_lambda: 1e-3,
+            lambda_increase: 10.,
+            lambda_decrease: None,
+            min_relative_improvement: 1e-12,
+            jacobian_relative_step: 1e-7,
+        });
+        let fit = global_deconvolution_data.deconvolve(&fit_algorithm, None).unwrap();
+        let params_expected = [3., 15., 2., 20., 0.5, 18.];
+        for (expected, actual) in params_expected.iter().zip(&fit.params.0) {
+            assert!((expected - actual).abs() < 1e-4, "expected: {params_expected:?}, actual: {:?}", fit.params.0);
+        }
+        let dataset_fit = global_deconvolution_data.get_dataset_fit(&fit, 1);
+        assert_eq!(4, dataset_fit.params.0.len());
+        assert!(dataset_fit.fit_residue < 1e-4);
+    }
+
+    #[test]
+    fn different_params_err() {
+        let mut deconvolution_data_other = deconvolution_data([2., 20., 3., 15.]);
+        deconvolution_data_other.add_ons.background = Some(ValueAndDomain::free(0.));
+        assert!(GlobalDeconvolutionData::new(
+            vec![deconvolution_data([2., 20., 3., 15.]), deconvolution_data_other],
+            &["tau".to_string()],
+        ).is_err());
+    }
+
+    #[test]
+    fn shared_params_err() {
+        let new = |shared_params: &[&str]| -> Result<GlobalDeconvolutionData> {
+            GlobalDeconvolutionData::new(
+                vec![deconvolution_data([2., 20., 3., 15.]), deconvolution_data([0.5, 18., 3., 15.])],
+                &shared_params.iter().map(|shared_param| shared_param.to_string()).collect::<Vec<_>>(),
+            )
+        };
+        assert!(new(&["tau", "shift"]).is_ok());
+        assert_eq!(
+            "`global_fit` -> `shared_params`: `taus` doesn't match any param, params are: amplitude, shift, tau_a, tau_b",
+            new(&["tau", "taus"]).unwrap_err().to_string(),
+        );
+        assert_eq!(
+            "`global_fit` -> `shared_params`: must contain at least one param, else it's same as fitting each spectrum separately",
+            new(&[]).unwrap_err().to_string(),
+        );
+    }
+}
@@ -47,6 +47,17 @@ where Self: Sized + InitialValuesGeneric<ValueAndDomain>
             .all(|(vad, &value)| vad.contains(value))
     }
 
+    /// Move each param into it's domain (to the closest boundary)
+    fn clamp_params_v(&self, params: &ParamsV) -> ParamsV {
+        let v = self.to_vec();
+        ParamsV(
+            DVect::from_iterator(
+                v.0.len(),
+                v.0.iter().zip(&params.0).map(|(vad, &value)| vad.clamp(value))
+            )
+        )
+    }
+
     /// Get randomized initial values with given `ThreadRng`
     fn get_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV {
         let v = self.to_vec();
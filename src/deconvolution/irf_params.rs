@@ -0,0 +1,172 @@
+//! Instrument (IRF) params: shift and broadening, fitted together with model params.
+
+use toml::Value as TomlValue;
+
+use crate::{
+    error::Result,
+    load::Load,
+    spectrum::Spectrum,
+    stacktrace::Stacktrace,
+    types::float::float,
+};
+
+use super::{
+    extra_params::{ExtraParams, ExtraParamsAutoImplFns, load_vads_from_str},
+    types::value_and_domain::ValueAndDomain,
+};
+
+
+/// Optional params of instrument, which are appended after model params in params vector (in this order).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IrfParams {
+    /// Shift of instrument along `x` (in units of `x`), can be fraction of step, applied by linear interpolation.
+    pub shift: Option<ValueAndDomain>,
+    /// Sigma of gaussian (in units of `x`), which instrument is convolved with.
+    pub broadening: Option<ValueAndDomain>,
+}
+
+/// Values of [`IrfParams`], `0` for disabled ones.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IrfValues {
+    pub shift: float,
+    pub broadening: float,
+}
+
+impl IrfParams {
+    pub const SHIFT_NAME: &'static str = "irf_shift";
+    pub const BROADENING_NAME: &'static str = "irf_broadening";
+
+    /// `params` are only params of instrument, in the same order as [`get_names`](ExtraParamsAutoImplFns::get_names).
+    pub fn params_to_values(&self, params: &[float]) -> IrfValues {
+        assert_eq!(self.len(), params.len());
+        let mut params = params.iter().copied();
+        IrfValues {
+            shift: self.shift.and_then(|_| params.next()).unwrap_or(0.),
+            broadening: self.broadening.and_then(|_| params.next()).unwrap_or(0.),
+        }
+    }
+}
+
+impl ExtraParams for IrfParams {
+    fn get_names_and_vads(&self) -> Vec<(&'static str, ValueAndDomain)> {
+        [(Self::SHIFT_NAME, self.shift), (Self::BROADENING_NAME, self.broadening)]
+            .into_iter()
+            .filter_map(|(name, vad)| vad.map(|vad| (name, vad)))
+            .collect()
+    }
+}
+
+impl IrfValues {
+    /// Shifted and broadened instrument points, on the same grid as `instrument`.
+    ///
+    /// Points shifted from outside of instrument are zero.
+    /// Broadening isn't applied if it's not positive.
+    pub fn apply_to_instrument(&self, instrument: &Spectrum) -> Vec<float> {
+        let points = &instrument.points;
+        let len = points.len();
+        let shift_in_points = self.shift / instrument.step;
+        let get_point = |i: isize| -> float {
+            if 0 <= i && (i as usize) < len { points[i as usize] } else { 0. }
+        };
+        let points_shifted: Vec<float> = (0..len)
+            .map(|i| {
+                let x = i as float - shift_in_points;
+                let i_lhs = x.floor();
+                let t = x - i_lhs;
+                let i_lhs = i_lhs as isize;
+                (1. - t) * get_point(i_lhs) + t * get_point(i_lhs + 1)
+            })
+            .collect();
+        let sigma_in_points = self.broadening / instrument.step;
+        if sigma_in_points <= 0. { return points_shifted }
+        let kernel_half_len = (4. * sigma_in_points).ceil() as isize;
+        let kernel: Vec<float> = (-kernel_half_len ..= kernel_half_len)
+            .map(|m| (-(m as float).powi(2) / (2. * sigma_in_points.powi(2))).exp())
+            .collect();
+        let kernel_sum: float = kernel.iter().sum();
+        (0..len as isize)
+            .map(|i| {
+                kernel.iter().enumerate()
+                    .map(|(k, kernel_value)| {
+                        let j = i + k as isize - kernel_half_len;
+                        if 0 <= j && (j as usize) < len { kernel_value * points_shifted[j as usize] } else { 0. }
+                    })
+                    .sum::<float>() / kernel_sum
+            })
+            .collect()
+    }
+}
+
+
+impl Load for IrfParams {
+    const TOML_NAME: &'static str = "irf_params";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let mut vads = load_vads_from_str(toml_value, ["shift", "broadening"], stacktrace)?;
+        Ok(Self {
+            shift: vads.remove("shift"),
+            broadening: vads.remove("broadening"),
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod apply_to_instrument {
+    use super::*;
+
+    fn instrument() -> Spectrum {
+        Spectrum { points: vec![0., 0., 0., 0., 1., 0., 0., 0., 0.], step: 0.5, x_start: 0. }
+    }
+
+    #[test]
+    fn nothing() {
+        assert_eq!(instrument().points, IrfValues::default().apply_to_instrument(&instrument()));
+    }
+
+    #[test]
+    fn shift_by_one_point() {
+        assert_eq!(
+            vec![0., 0., 0., 0., 0., 1., 0., 0., 0.],
+            IrfValues { shift: 0.5, broadening: 0. }.apply_to_instrument(&instrument())
+        );
+    }
+
+    #[test]
+    fn shift_by_half_point() {
+        assert_eq!(
+            vec![0., 0., 0., 0.5, 0.5, 0., 0., 0., 0.],
+            IrfValues { shift: -0.25, broadening: 0. }.apply_to_instrument(&instrument())
+        );
+    }
+
+    #[test]
+    fn broadening_keeps_sum_and_symmetry() {
+        let points = IrfValues { shift: 0., broadening: 0.3 }.apply_to_instrument(&instrument());
+        assert!((1. - points.iter().sum::<float>()).abs() < 1e-12);
+        assert_eq!(points[3], points[5]);
+        assert!(points[4] < 1.);
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn both() {
+        assert_eq!(
+            IrfParams { shift: Some(ValueAndDomain::free(0.)), broadening: Some(ValueAndDomain::range_with_min(0.1, 0.)) },
+            IrfParams::load_from_self(&TomlValue::String("shift=0, broadening=0.1>0".to_string()), &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            "`irf_params`: unkown type: `width`, known types: [`shift`, `broadening`]",
+            IrfParams::load_from_self(&TomlValue::String("width=1".to_string()), &Stacktrace::new("irf_params")).unwrap_err().to_string()
+        );
+    }
+}
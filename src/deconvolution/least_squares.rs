@@ -0,0 +1,203 @@
+//! Linear Least Squares helpers for deconvolutions, which aren't fitted.
+
+use crate::types::{float::float, linalg::{DMat, DVect}};
+
+
+/// Matrix `K`, such that `K * deconvolved` is same as
+/// [`convolve_by_points_v`](super::convolution::convolve_by_points_v) of `deconvolved` by `points_instrument`.
+///
+/// `points_instrument` must be centered, with odd len.
+pub fn convolution_matrix(points_instrument: &[float], len: usize) -> DMat {
+    let instrument_len: usize = points_instrument.len();
+    assert!(instrument_len % 2 == 1, "instrument_len = {}", instrument_len);
+    let ilh = instrument_len / 2;
+    DMat::from_fn(len, len, |i, j| {
+        // index in instrument, if it's in it:
+        match (ilh + i).checked_sub(j) {
+            Some(ii) if ii < instrument_len => points_instrument[ii],
+            _ => 0.,
+        }
+    })
+}
+
+/// Minimize `x^T Q x / 2 - c^T x` with `x >= 0`, `q` must be symmetric positive (semi)definite.
+///
+/// Starts from `x` (clamped to be non-negative), so if it's close to solution, only few iterations are needed.
+pub fn solve_non_negative(q: &DMat, c: &DVect, x: DVect) -> DVect {
+    assert_eq!((x.len(), x.len()), q.shape());
+    assert_eq!(x.len(), c.len());
+    lawson_hanson(
+        x,
+        c.amax(),
+        |indices| {
+            let q_passive: DMat = q.select_rows(indices).select_columns(indices);
+            let c_passive: DVect = c.select_rows(indices);
+            match q_passive.clone().cholesky() {
+                Some(cholesky) => cholesky.solve(&c_passive),
+                None => q_passive.svd(true, true).solve(&c_passive, float::EPSILON).unwrap(),
+            }
+        },
+        |x| c - q * x,
+    )
+}
+
+/// Non-Negative Least Squares: minimize `|A x - b|^2` with `x >= 0`.
+pub fn nnls(a: &DMat, b: &DVect) -> DVect {
+    assert_eq!(a.nrows(), b.len());
+    let atb: DVect = a.transpose() * b;
+    lawson_hanson(
+        DVect::zeros(a.ncols()),
+        atb.amax(),
+        |indices| {
+            // least squares by SVD, bc `A` columns can be almost collinear:
+            a.select_columns(indices).svd(true, true).solve(b, float::EPSILON).unwrap()
+        },
+        |x| a.transpose() * (b - a * x),
+    )
+}
+
+/// Lawson–Hanson active set method, starting from `x` (clamped to be non-negative) with `passive` set of its positive points.
+///
+/// - `solve_passive(indices)`: unconstrained minimum, when only `indices` points are non-zero,
+/// - `calc_gradient_neg(x)`: negative gradient, positive ones mean that objective decreases if that point is increased,
+/// - `scale`: typical gradient value, to compare with zero.
+fn lawson_hanson(
+    x: DVect,
+    scale: float,
+    solve_passive: impl Fn(&[usize]) -> DVect,
+    calc_gradient_neg: impl Fn(&DVect) -> DVect,
+) -> DVect {
+    const TOLERANCE: float = 1e-12;
+    let len = x.len();
+    let tolerance: float = TOLERANCE * scale.max(float::MIN_POSITIVE);
+    let solve_passive = |passive: &[bool]| -> DVect {
+        let indices: Vec<usize> = (0..len).filter(|&i| passive[i]).collect();
+        let mut solution: DVect = DVect::zeros(len);
+        if indices.is_empty() { return solution }
+        for (&i, &solution_i) in indices.iter().zip(solve_passive(&indices).iter()) {
+            solution[i] = solution_i;
+        }
+        solution
+    };
+    let mut x: DVect = x.map(|x_i| x_i.max(0.));
+    let mut passive: Vec<bool> = x.iter().map(|&x_i| x_i > 0.).collect();
+    for _ in 0..3*len+1 {
+        // make `x` feasible solution on `passive`, by moving to it from current `x` until some point becomes zero,
+        // at least one point is removed from `passive` each time, so `len+1` iterations is enough:
+        for _ in 0..len+1 {
+            let solution: DVect = solve_passive(&passive);
+            if (0..len).all(|i| !passive[i] || solution[i] > 0.) {
+                x = solution;
+                break
+            }
+            let (index_alpha, alpha): (usize, float) = (0..len)
+                .filter(|&i| passive[i] && solution[i] <= 0.)
+                .map(|i| (i, (x[i] / (x[i] - solution[i])).max(0.))) // `max` also maps `0/0` to `0`
+                .min_by(|(_, alpha_i), (_, alpha_j)| alpha_i.total_cmp(alpha_j))
+                .unwrap(); // there is such point, bc otherwise loop would break above
+            x += alpha * (solution - &x);
+            // always remove point that reached zero, even if rounding left it above `tolerance`:
+            passive[index_alpha] = false;
+            x[index_alpha] = 0.;
+            for i in 0..len {
+                if passive[i] && x[i] <= tolerance {
+                    passive[i] = false;
+                    x[i] = 0.;
+                }
+            }
+        }
+        let gradient_neg: DVect = calc_gradient_neg(&x);
+        let index_max = (0..len)
+            .filter(|&i| !passive[i] && gradient_neg[i] > tolerance)
+            .max_by(|&i, &j| gradient_neg[i].total_cmp(&gradient_neg[j]));
+        match index_max {
+            Some(index_max) => { passive[index_max] = true }
+            None => break
+        }
+    }
+    x
+}
+
+
+
+#[cfg(test)]
+mod convolution_matrix {
+    use crate::{
+        deconvolution::convolution::convolve_by_points_v,
+        types::named_wrappers::{DeconvolvedV, Instrument},
+    };
+    use super::*;
+
+    #[test]
+    fn same_as_convolve_by_points() {
+        let points_instrument: Vec<float> = vec![0.1, 0.5, 1., 0.3, 0.2];
+        let points_deconvolved: Vec<float> = (0..12).map(|i| ((i * 7) % 5) as float + 0.5 * i as float).collect();
+        let expected: DVect = convolve_by_points_v(
+            &Instrument(points_instrument.clone()).into(),
+            DeconvolvedV(DVect::from_vec(points_deconvolved.clone())),
+        ).0;
+        let actual: DVect = convolution_matrix(&points_instrument, points_deconvolved.len()) * DVect::from_vec(points_deconvolved);
+        assert!((&expected - &actual).amax() < 1e-12, "expected: {expected}\nactual: {actual}");
+    }
+}
+
+
+#[cfg(test)]
+mod solve_non_negative {
+    use super::*;
+
+    #[test]
+    fn same_as_unconstrained_if_solution_is_positive() {
+        let q: DMat = DMat::from_row_slice(3, 3, &[
+            4., 1., 0.,
+            1., 3., 1.,
+            0., 1., 2.,
+        ]);
+        let x_expected: DVect = DVect::from_vec(vec![1., 2., 3.]);
+        let c: DVect = &q * &x_expected;
+        let x_actual: DVect = solve_non_negative(&q, &c, DVect::zeros(3));
+        assert!((&x_expected - &x_actual).amax() < 1e-9, "actual: {x_actual}");
+    }
+
+    #[test]
+    fn negative_is_clamped() {
+        // minimum of `(x0 - 1)^2 + (x1 + 1)^2` with `x >= 0` is at `(1, 0)`:
+        let q: DMat = DMat::identity(2, 2) * 2.;
+        let c: DVect = DVect::from_vec(vec![2., -2.]);
+        let x_actual: DVect = solve_non_negative(&q, &c, DVect::from_vec(vec![5., 5.]));
+        assert!((DVect::from_vec(vec![1., 0.]) - &x_actual).amax() < 1e-12, "actual: {x_actual}");
+    }
+}
+
+
+#[cfg(test)]
+mod nnls {
+    use super::*;
+
+    #[test]
+    fn same_as_least_squares_if_solution_is_positive() {
+        let a: DMat = DMat::from_row_slice(4, 2, &[
+            1., 0.,
+            1., 1.,
+            0., 1.,
+            1., 2.,
+        ]);
+        let x_expected: DVect = DVect::from_vec(vec![2., 3.]);
+        let x_actual: DVect = nnls(&a, &(&a * &x_expected));
+        assert!((&x_expected - &x_actual).amax() < 1e-12, "actual: {x_actual}");
+    }
+
+    #[test]
+    fn negative_is_zero() {
+        // `b` is `2 * a0 - 1 * a1`, so best with `x1 = 0`:
+        let a: DMat = DMat::from_row_slice(3, 2, &[
+            1., 0.,
+            0., 1.,
+            1., 1.,
+        ]);
+        let b: DVect = DVect::from_vec(vec![2., -1., 1.]);
+        let x_actual: DVect = nnls(&a, &b);
+        // `x0 = argmin (x0-2)^2 + 1 + (x0-1)^2 = 1.5`:
+        assert!((DVect::from_vec(vec![1.5, 0.]) - &x_actual).amax() < 1e-12, "actual: {x_actual}");
+    }
+}
@@ -1,18 +1,28 @@
 //! Deconvolution
 
+pub mod add_ons;
+pub mod convolution;
 pub mod deconvolution_data;
+pub mod extra_params;
+pub mod global_deconvolution_data;
 pub mod initial_values;
+pub mod irf_params;
 pub mod types;
 
-pub(self) mod convolution;
+mod fft;
+mod least_squares;
+#[cfg(test)]
+pub mod test_fixtures;
 
 
 use std::cmp::Ordering;
 
-use rand::{rngs::ThreadRng, thread_rng};
+use rand::rngs::ThreadRng;
 use toml::Value as TomlValue;
 
 use crate::{
+    diff_function::DiffFunction,
+    error::Result,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
     types::{
@@ -26,16 +36,24 @@ use self::{
     types::{
         Function,
         FunctionAutoImplFns,
+        custom::{Custom, InitialValues_Custom},
         exponents::{Exponents, InitialValues_Exponents},
+        fourier::Fourier,
+        lifetime_distribution::LifetimeDistribution,
+        log_normal_dec_exp::{InitialValues_LogNormalDecExp, LogNormalDecExp},
         per_points::PerPoint,
+        regularized::Regularized,
+        richardson_lucy::RichardsonLucy,
         sat_exp__dec_exp::{InitialValues_SatExp_DecExp, SatExp_DecExp},
         sat_exp__dec_exp_plus_const::{InitialValues_SatExp_DecExpPlusConst, SatExp_DecExpPlusConst},
+        sat_exp__stretched_exp::{InitialValues_SatExp_StretchedExp, SatExp_StretchedExp},
         sat_exp__two_dec_exp::{InitialValues_SatExp_TwoDecExp, SatExp_TwoDecExp},
         sat_exp__two_dec_exp__constrained_consts::{InitialValues_SatExp_TwoDecExp_ConstrainedConsts, SatExp_TwoDecExp_ConstrainedConsts},
         sat_exp__two_dec_exp__separate_consts::{InitialValues_SatExp_TwoDecExp_SeparateConsts, SatExp_TwoDecExp_SeparateConsts},
         sat_exp__two_dec_exp_plus_const::{InitialValues_SatExp_TwoDecExpPlusConst, SatExp_TwoDecExpPlusConst},
         sigmoid__two_dec_exp__constrained_consts::{InitialValues_Sigmoid_TwoDecExp_ConstrainedConsts, Sigmoid_TwoDecExp_ConstrainedConsts},
         two__sat_exp__dec_exp::{InitialValues_Two_SatExp_DecExp, Two_SatExp_DecExp},
+        value_and_domain::ValueAndDomain,
     },
 };
 
@@ -54,7 +72,13 @@ pub enum DeconvolutionVariant {
     SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts),
     SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts),
     Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts),
-    // Fourier { unimplemented },
+    SatExp_StretchedExp(SatExp_StretchedExp),
+    LogNormalDecExp(LogNormalDecExp),
+    Custom(Custom),
+    Fourier(Fourier),
+    RichardsonLucy(RichardsonLucy),
+    Regularized(Regularized),
+    LifetimeDistribution(LifetimeDistribution),
 }
 
 impl DeconvolutionVariant {
@@ -72,9 +96,24 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(_) => SatExp_TwoDecExp_SeparateConsts::NAME,
             Self::SatExp_TwoDecExp_ConstrainedConsts(_) => SatExp_TwoDecExp_ConstrainedConsts::NAME,
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(_) => Sigmoid_TwoDecExp_ConstrainedConsts::NAME,
+            Self::SatExp_StretchedExp(_) => SatExp_StretchedExp::NAME,
+            Self::LogNormalDecExp(_) => LogNormalDecExp::NAME,
+            Self::Custom(_) => Custom::NAME,
+            Self::Fourier(_) => Fourier::NAME,
+            Self::RichardsonLucy(_) => RichardsonLucy::NAME,
+            Self::Regularized(_) => Regularized::NAME,
+            Self::LifetimeDistribution(_) => LifetimeDistribution::NAME,
         }
     }
 
+    /// Is params fitted by fit algorithm, else they are calculated directly (e.g. [`Fourier`], [`RichardsonLucy`], [`Regularized`], [`LifetimeDistribution`]).
+    ///
+    /// If they aren't fitted, `fit_algorithm` isn't used, randomized initial values aren't tried,
+    /// and `diff_function_type` doesn't affect params, so it's used only to show fit residue in output.
+    pub const fn is_fitted(&self) -> bool {
+        !matches!(self, Self::Fourier(..) | Self::RichardsonLucy(..) | Self::Regularized(..) | Self::LifetimeDistribution(..))
+    }
+
     pub fn get_initial_values_len(&self) -> usize {
         match self {
             Self::PerPoint(PerPoint { initial_vad, .. }) => initial_vad.len(),
@@ -87,12 +126,101 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.len(),
             Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.len(),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.len(),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.len(),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.len(),
+            Self::Custom(Custom { initial_vads, .. }) => initial_vads.len(),
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.len(),
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.len(),
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.len(),
+            Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. }) => initial_vad.len(),
+        }
+    }
+
+    /// Names of params, in the same order as in params vector.
+    pub fn get_params_names(&self) -> Vec<String> {
+        let names_from_strs = |names: &[&str]| -> Vec<String> {
+            names.iter().map(|name| name.to_string()).collect()
+        };
+        match self {
+            Self::PerPoint(PerPoint { initial_vad, .. })
+            | Self::Fourier(Fourier { initial_vad, .. })
+            | Self::RichardsonLucy(RichardsonLucy { initial_vad, .. })
+            | Self::Regularized(Regularized { initial_vad, .. })
+            => (0..initial_vad.len()).map(|i| format!("point_{i}")).collect(),
+            Self::LifetimeDistribution(lifetime_distribution) => (0..lifetime_distribution.get_taus_len()).map(|i| format!("amplitude_{i}")).collect(),
+            Self::Exponents(Exponents { initial_vads, .. }) => (0..initial_vads.len()/3)
+                .flat_map(|i| [format!("amplitude_{i}"), format!("shift_{i}"), format!("tau_{i}")])
+                .collect(),
+            Self::SatExp_DecExp(..) => names_from_strs(&["amplitude", "shift", "tau_a", "tau_b"]),
+            Self::SatExp_TwoDecExp(..) => names_from_strs(&["amplitude", "shift", "tau_a", "tau_b", "tau_c"]),
+            Self::Two_SatExp_DecExp(..) => names_from_strs(&["amplitude_1", "shift_1", "tau_a1", "tau_b1", "amplitude_2", "shift_2", "tau_a2", "tau_b2"]),
+            Self::SatExp_DecExpPlusConst(..) => names_from_strs(&["amplitude", "shift", "height", "tau_a", "tau_b"]),
+            Self::SatExp_TwoDecExpPlusConst(..) => names_from_strs(&["amplitude", "shift", "height", "tau_a", "tau_b", "tau_c"]),
+            Self::SatExp_TwoDecExp_SeparateConsts(..) => names_from_strs(&["amplitude_b", "amplitude_c", "shift", "tau_a", "tau_b", "tau_c"]),
+            Self::SatExp_TwoDecExp_ConstrainedConsts(..) => names_from_strs(&["amplitude_a", "amplitude_b", "shift", "tau_a", "tau_b", "tau_c"]),
+            Self::Sigmoid_TwoDecExp_ConstrainedConsts(..) => names_from_strs(&["amplitude_a", "amplitude_b", "shift", "tau_a", "tau_b", "tau_c"]),
+            Self::SatExp_StretchedExp(..) => names_from_strs(&["amplitude", "shift", "tau_a", "tau_b", "beta"]),
+            Self::LogNormalDecExp(..) => names_from_strs(&["amplitude", "shift", "tau", "sigma"]),
+            Self::Custom(Custom { expression, .. }) => expression.get_params_names().to_vec(),
+        }
+    }
+
+    /// Initial values with domains, in the same order as in params vector.
+    pub fn get_initial_vads(&self) -> Vec<ValueAndDomain> {
+        match self {
+            Self::PerPoint(PerPoint { initial_vad, .. }) => initial_vad.to_vec().0,
+            Self::Exponents(Exponents { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_DecExp(SatExp_DecExp { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_TwoDecExp(SatExp_TwoDecExp { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::Two_SatExp_DecExp(Two_SatExp_DecExp { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::Custom(Custom { initial_vads, .. }) => initial_vads.to_vec().0,
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.to_vec().0,
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.to_vec().0,
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.to_vec().0,
+            Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. }) => initial_vad.to_vec().0,
+        }
+    }
+
+    /// Set number of points for variants, that depends on it (now it's [`PerPoint`] and ones, which aren't fitted).
+    pub fn set_points_len(&mut self, points_len: usize) {
+        match self {
+            Self::PerPoint(PerPoint { initial_vad, .. })
+            | Self::Fourier(Fourier { initial_vad, .. })
+            | Self::RichardsonLucy(RichardsonLucy { initial_vad, .. })
+            | Self::Regularized(Regularized { initial_vad, .. })
+            => initial_vad.set_len(points_len),
+            Self::Exponents(..)
+            | Self::SatExp_DecExp(..)
+            | Self::SatExp_TwoDecExp(..)
+            | Self::Two_SatExp_DecExp(..)
+            | Self::SatExp_DecExpPlusConst(..)
+            | Self::SatExp_TwoDecExpPlusConst(..)
+            | Self::SatExp_TwoDecExp_SeparateConsts(..)
+            | Self::SatExp_TwoDecExp_ConstrainedConsts(..)
+            | Self::Sigmoid_TwoDecExp_ConstrainedConsts(..)
+            | Self::SatExp_StretchedExp(..)
+            | Self::LogNormalDecExp(..)
+            | Self::Custom(..)
+            | Self::LifetimeDistribution(..)
+            => {}
         }
     }
 
     pub fn get_initial_values(&self) -> Params {
         match self {
-            Self::PerPoint(PerPoint { initial_vad, .. }) => ParamsG::<float>(vec![initial_vad.vad.value; initial_vad.len]),
+            Self::PerPoint(PerPoint { initial_vad, .. })
+            | Self::Fourier(Fourier { initial_vad, .. })
+            | Self::RichardsonLucy(RichardsonLucy { initial_vad, .. })
+            | Self::Regularized(Regularized { initial_vad, .. })
+            | Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. })
+            => ParamsG::<float>(vec![initial_vad.vad.value; initial_vad.len]),
             Self::Exponents(Exponents { initial_vads, .. }) => InitialValues_Exponents::<float>::from(initial_vads.clone()).to_vec(),
             Self::SatExp_DecExp(SatExp_DecExp { initial_vads, .. }) => InitialValues_SatExp_DecExp::<float>::from(*initial_vads).to_vec(),
             Self::SatExp_TwoDecExp(SatExp_TwoDecExp { initial_vads, .. }) => InitialValues_SatExp_TwoDecExp::<float>::from(*initial_vads).to_vec(),
@@ -102,14 +230,12 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => InitialValues_SatExp_TwoDecExp_SeparateConsts::<float>::from(*initial_vads).to_vec(),
             Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => InitialValues_SatExp_TwoDecExp_ConstrainedConsts::<float>::from(*initial_vads).to_vec(),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => InitialValues_Sigmoid_TwoDecExp_ConstrainedConsts::<float>::from(*initial_vads).to_vec(),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => InitialValues_SatExp_StretchedExp::<float>::from(*initial_vads).to_vec(),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => InitialValues_LogNormalDecExp::<float>::from(*initial_vads).to_vec(),
+            Self::Custom(Custom { initial_vads, .. }) => InitialValues_Custom::<float>::from(initial_vads.clone()).to_vec(),
         }
     }
 
-    pub fn get_initial_values_randomized_v(&self, initial_values_random_scale: float) -> ParamsV {
-        let mut rng = thread_rng();
-        self.get_initial_values_randomized_with_rng_v(initial_values_random_scale, &mut rng)
-    }
-
     pub fn get_initial_values_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV {
         match self {
             Self::PerPoint(PerPoint { initial_vad, .. }) => initial_vad.get_randomized_with_rng_v(initial_values_random_scale, rng),
@@ -122,6 +248,13 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
             Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::Custom(Custom { initial_vads, .. }) => initial_vads.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.get_randomized_with_rng_v(initial_values_random_scale, rng),
+            Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. }) => initial_vad.get_randomized_with_rng_v(initial_values_random_scale, rng),
         }
     }
 
@@ -137,6 +270,35 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
             Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
+            Self::Custom(Custom { initial_vads, .. }) => initial_vads.is_params_ok_v(params),
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.is_params_ok_v(params),
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.is_params_ok_v(params),
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.is_params_ok_v(params),
+            Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. }) => initial_vad.is_params_ok_v(params),
+        }
+    }
+
+    pub fn clamp_params_v(&self, params: &ParamsV) -> ParamsV {
+        match self {
+            Self::PerPoint(PerPoint { initial_vad, .. }) => initial_vad.clamp_params_v(params),
+            Self::Exponents(Exponents { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_DecExp(SatExp_DecExp { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_TwoDecExp(SatExp_TwoDecExp { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::Two_SatExp_DecExp(Two_SatExp_DecExp { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::Custom(Custom { initial_vads, .. }) => initial_vads.clamp_params_v(params),
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.clamp_params_v(params),
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.clamp_params_v(params),
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.clamp_params_v(params),
+            Self::LifetimeDistribution(LifetimeDistribution { initial_vad, .. }) => initial_vad.clamp_params_v(params),
         }
     }
 
@@ -159,6 +321,59 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { initial_vads, .. }) => initial_vads.params_to_points_v(params, points_len, x_start_end),
             Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.params_to_points_v(params, points_len, x_start_end),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { initial_vads, .. }) => initial_vads.params_to_points_v(params, points_len, x_start_end),
+            Self::SatExp_StretchedExp(SatExp_StretchedExp { initial_vads, .. }) => initial_vads.params_to_points_v(params, points_len, x_start_end),
+            Self::LogNormalDecExp(LogNormalDecExp { initial_vads, .. }) => initial_vads.params_to_points_v(params, points_len, x_start_end),
+            Self::Custom(custom) => custom.params_to_points_v(params, points_len, x_start_end),
+            Self::Fourier(Fourier { initial_vad, .. }) => initial_vad.params_to_points_v(params, points_len, x_start_end),
+            Self::RichardsonLucy(RichardsonLucy { initial_vad, .. }) => initial_vad.params_to_points_v(params, points_len, x_start_end),
+            Self::Regularized(Regularized { initial_vad, .. }) => initial_vad.params_to_points_v(params, points_len, x_start_end),
+            Self::LifetimeDistribution(lifetime_distribution) => lifetime_distribution.params_to_points_v(params, points_len, x_start_end),
+        }
+    }
+
+    pub fn get_diff_function_type(&self) -> DiffFunction {
+        match self {
+            Self::PerPoint(PerPoint { diff_function_type, .. })
+            | Self::Exponents(Exponents { diff_function_type, .. })
+            | Self::SatExp_DecExp(SatExp_DecExp { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp(SatExp_TwoDecExp { diff_function_type, .. })
+            | Self::Two_SatExp_DecExp(Two_SatExp_DecExp { diff_function_type, .. })
+            | Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst { diff_function_type, .. })
+            | Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
+            | Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
+            | Self::SatExp_StretchedExp(SatExp_StretchedExp { diff_function_type, .. })
+            | Self::LogNormalDecExp(LogNormalDecExp { diff_function_type, .. })
+            | Self::Custom(Custom { diff_function_type, .. })
+            | Self::Fourier(Fourier { diff_function_type, .. })
+            | Self::RichardsonLucy(RichardsonLucy { diff_function_type, .. })
+            | Self::Regularized(Regularized { diff_function_type, .. })
+            | Self::LifetimeDistribution(LifetimeDistribution { diff_function_type, .. })
+            => *diff_function_type
+        }
+    }
+
+    pub fn set_diff_function_type(&mut self, diff_function_type_new: DiffFunction) {
+        match self {
+            Self::PerPoint(PerPoint { diff_function_type, .. })
+            | Self::Exponents(Exponents { diff_function_type, .. })
+            | Self::SatExp_DecExp(SatExp_DecExp { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp(SatExp_TwoDecExp { diff_function_type, .. })
+            | Self::Two_SatExp_DecExp(Two_SatExp_DecExp { diff_function_type, .. })
+            | Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst { diff_function_type, .. })
+            | Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { diff_function_type, .. })
+            | Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
+            | Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
+            | Self::SatExp_StretchedExp(SatExp_StretchedExp { diff_function_type, .. })
+            | Self::LogNormalDecExp(LogNormalDecExp { diff_function_type, .. })
+            | Self::Custom(Custom { diff_function_type, .. })
+            | Self::Fourier(Fourier { diff_function_type, .. })
+            | Self::RichardsonLucy(RichardsonLucy { diff_function_type, .. })
+            | Self::Regularized(Regularized { diff_function_type, .. })
+            | Self::LifetimeDistribution(LifetimeDistribution { diff_function_type, .. })
+            => *diff_function_type = diff_function_type_new
         }
     }
 
@@ -176,6 +391,13 @@ impl DeconvolutionVariant {
             | Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts { diff_function_type, .. })
             | Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
             | Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts { diff_function_type, .. })
+            | Self::SatExp_StretchedExp(SatExp_StretchedExp { diff_function_type, .. })
+            | Self::LogNormalDecExp(LogNormalDecExp { diff_function_type, .. })
+            | Self::Custom(Custom { diff_function_type, .. })
+            | Self::Fourier(Fourier { diff_function_type, .. })
+            | Self::RichardsonLucy(RichardsonLucy { diff_function_type, .. })
+            | Self::Regularized(Regularized { diff_function_type, .. })
+            | Self::LifetimeDistribution(LifetimeDistribution { diff_function_type, .. })
             => {
                 diff_function_type.calc_diff_v(&points_measured.0, &points_convolved.0)
             }
@@ -186,7 +408,7 @@ impl DeconvolutionVariant {
     pub fn to_desmos_function(&self, params: &Params, significant_digits: u8) -> Result<String, &'static str> {
         let sd = significant_digits;
         Ok(format!("y=") + &match self {
-            Self::PerPoint(_) => { return Err("not plottable") },
+            Self::PerPoint(_) | Self::Fourier(_) | Self::RichardsonLucy(_) | Self::Regularized(_) | Self::LifetimeDistribution(_) => { return Err("not plottable") },
             Self::Exponents(self_) => self_.to_desmos_function(params, sd),
             Self::SatExp_DecExp(self_) => self_.to_desmos_function(params, sd),
             Self::SatExp_TwoDecExp(self_) => self_.to_desmos_function(params, sd),
@@ -196,6 +418,9 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(self_) => self_.to_desmos_function(params, sd),
             Self::SatExp_TwoDecExp_ConstrainedConsts(self_) => self_.to_desmos_function(params, sd),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(self_) => self_.to_desmos_function(params, sd),
+            Self::SatExp_StretchedExp(self_) => self_.to_desmos_function(params, sd),
+            Self::LogNormalDecExp(self_) => self_.to_desmos_function(params, sd),
+            Self::Custom(self_) => self_.to_desmos_function(params, sd),
         })
     }
 
@@ -203,7 +428,7 @@ impl DeconvolutionVariant {
     pub fn to_origin_function(&self, params: &Params, significant_digits: u8) -> Result<String, &'static str> {
         let sd = significant_digits;
         Ok(match self {
-            Self::PerPoint(_) => { return Err("not plottable") },
+            Self::PerPoint(_) | Self::Fourier(_) | Self::RichardsonLucy(_) | Self::Regularized(_) | Self::LifetimeDistribution(_) => { return Err("not plottable") },
             Self::Exponents(self_) => self_.to_origin_function(params, sd),
             Self::SatExp_DecExp(self_) => self_.to_origin_function(params, sd),
             Self::SatExp_TwoDecExp(self_) => self_.to_origin_function(params, sd),
@@ -213,6 +438,9 @@ impl DeconvolutionVariant {
             Self::SatExp_TwoDecExp_SeparateConsts(self_) => self_.to_origin_function(params, sd),
             Self::SatExp_TwoDecExp_ConstrainedConsts(self_) => self_.to_origin_function(params, sd),
             Self::Sigmoid_TwoDecExp_ConstrainedConsts(self_) => self_.to_origin_function(params, sd),
+            Self::SatExp_StretchedExp(self_) => self_.to_origin_function(params, sd),
+            Self::LogNormalDecExp(self_) => self_.to_origin_function(params, sd),
+            Self::Custom(self_) => self_.to_origin_function(params, sd),
         })
     }
 }
@@ -220,8 +448,8 @@ impl DeconvolutionVariant {
 
 impl Load for DeconvolutionVariant {
     const TOML_NAME: &'static str = "deconvolution_function";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        const DECONVOLUTION_FUNCTIONS_NAMES: [&'static str; 10] = [
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        const DECONVOLUTION_FUNCTIONS_NAMES: [&'static str; 17] = [
             PerPoint::TOML_NAME,
             Exponents::TOML_NAME,
             SatExp_DecExp::TOML_NAME,
@@ -232,6 +460,13 @@ impl Load for DeconvolutionVariant {
             SatExp_TwoDecExp_SeparateConsts::TOML_NAME,
             SatExp_TwoDecExp_ConstrainedConsts::TOML_NAME,
             Sigmoid_TwoDecExp_ConstrainedConsts::TOML_NAME,
+            SatExp_StretchedExp::TOML_NAME,
+            LogNormalDecExp::TOML_NAME,
+            Custom::TOML_NAME,
+            Fourier::TOML_NAME,
+            RichardsonLucy::TOML_NAME,
+            Regularized::TOML_NAME,
+            LifetimeDistribution::TOML_NAME,
         ];
         let deconvolution_functions = DECONVOLUTION_FUNCTIONS_NAMES
             .map(|df_name| toml_value.get(df_name));
@@ -240,16 +475,16 @@ impl Load for DeconvolutionVariant {
             .flatten() // flatten on Iter<Option/Result> gives only Some/Ok variants and unwraps them
             .count();
         match deconvolution_functions_number.cmp(&1) {
-            // TODO: maybe somehow get first entry from table and use `panic_unknown_type` with value
-            Ordering::Less    => stacktrace.panic_unknown_type_without_value(DECONVOLUTION_FUNCTIONS_NAMES),
-            Ordering::Greater => stacktrace.panic_more_than_one_found(
+            // TODO: maybe somehow get first entry from table and use `err_unknown_type` with value
+            Ordering::Less    => return Err(stacktrace.err_unknown_type_without_value(DECONVOLUTION_FUNCTIONS_NAMES)),
+            Ordering::Greater => return Err(stacktrace.err_more_than_one_found(
                 deconvolution_functions
                     .iter()
                     .zip(DECONVOLUTION_FUNCTIONS_NAMES)
                     .map(|(odf, dfn)| odf.map(|_| dfn))
                     .flatten()
                     .collect::<Vec<_>>()
-            ),
+            )),
             Ordering::Equal => {}
         }
         let deconvolution_function_index = deconvolution_functions
@@ -259,19 +494,26 @@ impl Load for DeconvolutionVariant {
         let toml_value = deconvolution_functions[deconvolution_function_index].unwrap();
         // TODO(refactor): dont use numbers, bc they must be kept in sync with `DECONVOLUTION_FUNCTIONS_NAMES`
         // - maybe create vec of [PerPoint, Exponents, ...] and try load by them?
-        match deconvolution_function_index {
-            0 => Self::PerPoint(PerPoint::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            1 => Self::Exponents(Exponents::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            2 => Self::SatExp_DecExp(SatExp_DecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            3 => Self::SatExp_TwoDecExp(SatExp_TwoDecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            4 => Self::Two_SatExp_DecExp(Two_SatExp_DecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            5 => Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            6 => Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            7 => Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            8 => Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            9 => Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)),
+        Ok(match deconvolution_function_index {
+            0 => Self::PerPoint(PerPoint::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            1 => Self::Exponents(Exponents::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            2 => Self::SatExp_DecExp(SatExp_DecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            3 => Self::SatExp_TwoDecExp(SatExp_TwoDecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            4 => Self::Two_SatExp_DecExp(Two_SatExp_DecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            5 => Self::SatExp_DecExpPlusConst(SatExp_DecExpPlusConst::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            6 => Self::SatExp_TwoDecExpPlusConst(SatExp_TwoDecExpPlusConst::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            7 => Self::SatExp_TwoDecExp_SeparateConsts(SatExp_TwoDecExp_SeparateConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            8 => Self::SatExp_TwoDecExp_ConstrainedConsts(SatExp_TwoDecExp_ConstrainedConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            9 => Self::Sigmoid_TwoDecExp_ConstrainedConsts(Sigmoid_TwoDecExp_ConstrainedConsts::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            10 => Self::SatExp_StretchedExp(SatExp_StretchedExp::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            11 => Self::LogNormalDecExp(LogNormalDecExp::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            12 => Self::Custom(Custom::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            13 => Self::Fourier(Fourier::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            14 => Self::RichardsonLucy(RichardsonLucy::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            15 => Self::Regularized(Regularized::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            16 => Self::LifetimeDistribution(LifetimeDistribution::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
             _ => unreachable!()
-        }
+        })
     }
 }
 
@@ -287,6 +529,10 @@ mod deconvolve {
             Spectrum,
             deconvolution::{
                 DeconvolutionVariant,
+                add_ons::AddOns,
+                convolution::ConvolutionMethod,
+                deconvolution_data::FitMask,
+                irf_params::IrfParams,
                 types::{
                     per_points::{InitialValues_PerPoint, PerPoint},
                     value_and_domain::ValueAndDomain,
@@ -318,11 +564,16 @@ mod deconvolve {
             let deconvolution_data: DeconvolutionData = DeconvolutionData {
                 instrument,
                 measured,
+                sigmas: None,
+                fit_mask: FitMask::default(),
+                irf_params: IrfParams::default(),
+                add_ons: AddOns::default(),
                 deconvolution: DeconvolutionVariant::PerPoint(PerPoint {
                     diff_function_type: DiffFunction::DySqr,
                     antispikes: None,
                     initial_vad: InitialValues_PerPoint::new(points_spectrum.len(), ValueAndDomain::free(0.)),
                 }),
+                convolution_method: ConvolutionMethod::Direct,
             };
             deconvolution_data.deconvolve(&FIT_ALGORITHM, None)
         }
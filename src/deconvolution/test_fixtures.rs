@@ -0,0 +1,84 @@
+//! Fixtures shared by tests of deconvolutions and fit algorithms.
+
+use crate::{
+    diff_function::DiffFunction,
+    spectrum::Spectrum,
+    types::{float::float, linalg::DVect, named_wrappers::{DeconvolvedV, Instrument, ParamsV}},
+};
+
+use super::{
+    DeconvolutionVariant,
+    add_ons::AddOns,
+    convolution::{ConvolutionMethod, convolve_by_points_v},
+    deconvolution_data::{DeconvolutionData, FitMask},
+    irf_params::IrfParams,
+    types::{
+        sat_exp__dec_exp::{InitialValues_SatExp_DecExp, SatExp_DecExp},
+        value_and_domain::ValueAndDomain,
+    },
+};
+
+
+/// Gaussian instrument, centered, with odd len.
+pub fn points_instrument() -> Vec<float> {
+    (-10..=10).map(|i| (-(i as float).powi(2) / 4.).exp()).collect()
+}
+
+/// Smooth (gaussian) peak of `len` points with max at `center`,
+/// which is zero at the edges, so it's convolution isn't truncated.
+pub fn points_smooth_peak(len: usize, center: float) -> Vec<float> {
+    (0..len).map(|i| (-(i as float - center).powi(2) / 50.).exp()).collect()
+}
+
+/// `points_deconvolved` convolved by [`points_instrument`].
+pub fn points_convolved(points_deconvolved: Vec<float>) -> Vec<float> {
+    convolve_by_points_v(
+        &Instrument(points_instrument()).into(),
+        DeconvolvedV(points_deconvolved.into()),
+    ).0.data.into()
+}
+
+/// Non-negative smooth peak of 100 points, deconvolved from [`points_measured`].
+pub fn points_deconvolved() -> Vec<float> {
+    points_smooth_peak(100, 40.)
+}
+
+/// [`points_deconvolved`] convolved by [`points_instrument`], without noise.
+pub fn points_measured() -> Vec<float> {
+    points_convolved(points_deconvolved())
+}
+
+/// Assert, that each point of `points_actual` differs from same point of `points_expected` less than by `tolerance`.
+pub fn assert_points_close(points_expected: &[float], points_actual: &[float], tolerance: float) {
+    assert_eq!(points_expected.len(), points_actual.len());
+    for (expected, actual) in points_expected.iter().zip(points_actual) {
+        assert!((expected - actual).abs() < tolerance, "expected: {points_expected:?}\nactual: {points_actual:?}");
+    }
+}
+
+/// [`SatExp_DecExp`] with gaussian instrument, and measured (100 points, without noise) convolved from `params_true`.
+pub fn deconvolution_data_sat_exp_dec_exp(initial_vads: InitialValues_SatExp_DecExp<ValueAndDomain>, params_true: [float; 4]) -> DeconvolutionData {
+    let instrument = Spectrum {
+        points: (-10..=10).map(|i| (-(i as float).powi(2) / 8.).exp()).collect(),
+        step: 1.,
+        x_start: -10.,
+    };
+    let mut deconvolution_data = DeconvolutionData {
+        instrument,
+        measured: Spectrum { points: vec![0.; 100], step: 1., x_start: 0. },
+        sigmas: None,
+        fit_mask: FitMask::default(),
+        irf_params: IrfParams::default(),
+        add_ons: AddOns::default(),
+        deconvolution: DeconvolutionVariant::SatExp_DecExp(SatExp_DecExp {
+            diff_function_type: DiffFunction::DySqr,
+            initial_vads,
+        }),
+        convolution_method: ConvolutionMethod::Direct,
+    };
+    deconvolution_data.measured.points = deconvolution_data.convolve_from_params_v(
+        &ParamsV(DVect::from_vec(params_true.to_vec())),
+        &Instrument(deconvolution_data.instrument.points.clone()).into(),
+    ).0.data.into();
+    deconvolution_data
+}
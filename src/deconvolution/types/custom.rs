@@ -0,0 +1,238 @@
+//! Custom
+
+use std::collections::HashMap;
+
+use toml::Value as TomlValue;
+
+use crate::{
+    diff_function::DiffFunction,
+    error::Result,
+    expression::Expression,
+    load::{LoadAutoImplFns, Load},
+    stacktrace::Stacktrace,
+    types::{float::float, linalg::DVect, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
+    utils_io::format_by_dollar_str,
+};
+
+use super::super::initial_values::{InitialValuesGeneric, InitialValuesVAD};
+
+use super::{Function, ValueAndDomain, i_to_x::i_to_x};
+
+
+/// User defined function of `x`, see [`Expression`] for syntax, e.g. `a*(1-exp(-(x-s)/ta))*exp(-(x-s)/tb)`.
+///
+/// Params are all names in expression (except `x` and functions), in order of first appearance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Custom {
+    pub diff_function_type: DiffFunction,
+    pub expression: Expression,
+    pub initial_vads: InitialValues_Custom<ValueAndDomain>,
+}
+
+impl Custom {
+    pub fn params_to_points_v(&self, params: &ParamsV, points_len: usize, x_start_end: (float, float)) -> DeconvolvedV {
+        let params: &[float] = params.0.as_slice();
+        let mut points = DVect::zeros(points_len);
+        for i in 0..points_len {
+            let x: float = i_to_x(i, points_len, x_start_end);
+            points[i] = self.expression.eval(x, params);
+        }
+        DeconvolvedV(points)
+    }
+}
+
+impl Function for Custom {
+    const NAME: &'static str = "custom";
+
+    // expression syntax is same for Desmos and Origin:
+    const FORMAT_FOR_DESMOS: &'static str = "$f";
+    const FORMAT_FOR_ORIGIN: &'static str = "$f";
+
+    fn to_plottable_function(&self, params: &Params, significant_digits: u8, format: &'static str) -> String {
+        format_by_dollar_str(
+            format,
+            vec![
+                ("f", &self.expression.to_string_with_params(&params.0, significant_digits)),
+            ]
+        )
+    }
+}
+
+impl Load for Custom {
+    const TOML_NAME: &'static str = stringify!(Custom);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let expression: Expression = {
+            let stacktrace = stacktrace.pushed("expression");
+            let expression_str = toml_value
+                .get("expression")
+                .ok_or_else(|| stacktrace.err_not_found())?
+                .as_str()
+                .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+            Expression::parse(expression_str)
+                .map_err(|msg| stacktrace.err(&msg))?
+        };
+        let initial_vads = InitialValues_Custom::load_by_names(
+            toml_value.get(InitialValues_Custom::TOML_NAME).ok_or_else(|| stacktrace.pushed(InitialValues_Custom::TOML_NAME).err_not_found())?,
+            expression.get_params_names(),
+            &stacktrace.pushed(InitialValues_Custom::TOML_NAME),
+        )?;
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            expression,
+            initial_vads,
+        })
+    }
+}
+
+
+/// Initial values in the same order as params in expression.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitialValues_Custom<T> (
+    Vec<T>
+);
+
+impl InitialValues_Custom<ValueAndDomain> {
+    const TOML_NAME: &'static str = "initial_values";
+
+    /// Loads `name=…` for each of `params_names` (in any order).
+    fn load_by_names(toml_value: &TomlValue, params_names: &[String], stacktrace: &Stacktrace) -> Result<Self> {
+        let str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        let mut ivs: HashMap<String, ValueAndDomain> = str
+            .trim_matches(|c: char| c.is_whitespace() || c == ',')
+            .split(',')
+            // TODO: add index to stacktrace
+            .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
+            .collect::<Result<_>>()?;
+        let vads: Vec<ValueAndDomain> = params_names.iter()
+            .map(|name| {
+                ivs
+                    .remove(name)
+                    .ok_or_else(|| stacktrace.err(&format!("`{name}` not found")))
+            })
+            .collect::<Result<_>>()?;
+        if !ivs.is_empty() {
+            let mut unknown_names: Vec<&str> = ivs.keys().map(|name| name.as_str()).collect();
+            unknown_names.sort();
+            return Err(stacktrace.err(&format!(
+                "unexpected values: [{}], expected only params from expression: [{}]",
+                unknown_names.iter().map(|name| format!("`{name}`")).collect::<Vec<_>>().join(", "),
+                params_names.iter().map(|name| format!("`{name}`")).collect::<Vec<_>>().join(", "),
+            )));
+        }
+        Ok(Self(vads))
+    }
+}
+
+impl<T: Clone> InitialValuesGeneric<T> for InitialValues_Custom<T> {
+    const LEN: usize = unreachable!();
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn from_vec(params: &ParamsG<T>) -> Self {
+        Self(params.0.clone())
+    }
+
+    fn to_vec(&self) -> ParamsG<T> {
+        ParamsG::<T>(self.0.clone())
+    }
+
+    fn params_to_points_v(&self, _params: &ParamsV, _points_len: usize, _x_start_end: (float, float)) -> DeconvolvedV {
+        unreachable!("expression is needed, so use `Custom::params_to_points_v`")
+    }
+}
+
+impl InitialValuesVAD for InitialValues_Custom<ValueAndDomain> {}
+
+impl From<InitialValues_Custom<ValueAndDomain>> for InitialValues_Custom<float> {
+    fn from(value: InitialValues_Custom<ValueAndDomain>) -> Self {
+        Self::from_vec(&ParamsG::<float>(value.to_vec().0.iter().map(|v| v.value).collect::<Vec<float>>()))
+    }
+}
+
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    fn load(toml_str: &str) -> Result<Custom> {
+        Custom::load_from_self(&toml_str.parse::<TomlValue>().unwrap(), &Stacktrace::new(Custom::TOML_NAME))
+    }
+
+    #[test]
+    fn ok() {
+        assert_eq!(
+            Custom {
+                diff_function_type: DiffFunction::DySqr,
+                expression: Expression::parse("a*exp(-(x-s)/t)").unwrap(),
+                initial_vads: InitialValues_Custom(vec![ValueAndDomain::free(1.), ValueAndDomain::fixed(2.), ValueAndDomain::range_with_min(3., 0.)]),
+            },
+            load(r#"
+                diff_function_type = "DySqr"
+                expression = "a*exp(-(x-s)/t)"
+                initial_values = "t=3>0, a=1, s==2"
+            "#).unwrap(),
+        );
+    }
+
+    #[test]
+    fn wrong_expression() {
+        assert_eq!(
+            "`Custom` -> `expression`: unknown function `foo`, known functions: [exp, ln, sqrt, abs, sin, cos, tan, sinh, cosh, tanh, max, min]",
+            load(r#"
+                diff_function_type = "DySqr"
+                expression = "a*foo(x)"
+                initial_values = "a=1"
+            "#).unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn missing_param() {
+        assert_eq!(
+            "`Custom` -> `initial_values`: `t` not found",
+            load(r#"
+                diff_function_type = "DySqr"
+                expression = "a*exp(-(x-s)/t)"
+                initial_values = "a=1, s=2"
+            "#).unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn unexpected_param() {
+        assert_eq!(
+            "`Custom` -> `initial_values`: unexpected values: [`b`], expected only params from expression: [`a`, `s`]",
+            load(r#"
+                diff_function_type = "DySqr"
+                expression = "a*exp(-(x-s))"
+                initial_values = "a=1, s=2, b=3"
+            "#).unwrap_err().to_string(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod params_to_points_v {
+    use crate::deconvolution::types::sat_exp__dec_exp::InitialValues_SatExp_DecExp;
+    use super::*;
+
+    #[test]
+    fn same_as_sat_exp_dec_exp() {
+        let custom = Custom {
+            diff_function_type: DiffFunction::DySqr,
+            expression: Expression::parse("max(0, a*(1-exp(-(x-s)/ta))*exp(-(x-s)/tb))").unwrap(),
+            initial_vads: InitialValues_Custom(vec![ValueAndDomain::free(0.); 4]),
+        };
+        let params = ParamsV(DVect::from_vec(vec![2., 1., 0.5, 3.]));
+        let points_expected = InitialValues_SatExp_DecExp::from_vec(&ParamsG(vec![ValueAndDomain::free(0.); 4]))
+            .params_to_points_v(&params, 11, (0., 5.)).0;
+        let points_actual = custom.params_to_points_v(&params, 11, (0., 5.)).0;
+        assert!((points_expected.clone() - points_actual.clone()).abs().max() < 1e-12, "expected: {points_expected}, actual: {points_actual}");
+    }
+}
@@ -1,20 +1,23 @@
 //! Exponents
 
+use std::collections::HashMap;
+
 use toml::Value as TomlValue;
 
 use crate::{
     aliases_method_to_function::exp,
     diff_function::DiffFunction,
+    error::Result,
     extensions::ToStringWithSignificantDigits,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
-    types::{float::float, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
+    types::{float::float, linalg::DVect, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
     utils_io::format_by_dollar_str,
 };
 
 use super::super::initial_values::{InitialValuesGeneric, InitialValuesVAD};
 
-use super::{Function, ValueAndDomain};
+use super::{Function, ValueAndDomain, i_to_x::i_to_x};
 
 
 /// a1*exp(-(x-s1)/t1) + …
@@ -27,29 +30,28 @@ pub struct Exponents {
 impl Function for Exponents {
     const NAME: &'static str = "exponents";
 
-    const FORMAT_FOR_DESMOS: &'static str = r"max\left(0,x$comp$ns:0,$a\exp\left(-\frac{x$p$ns}{$t}\right)\right)";
-    const FORMAT_FOR_ORIGIN: &'static str = r"max($a*exp(-(x$p$ns)/($t)))";
+    // exponent is "cut" before shift (after shift, if tau is negative), same as in [`ExponentFunction::eval_at`].
+    const FORMAT_FOR_DESMOS: &'static str = r"\left\{x$comp$s:$a\exp\left(-\frac{x$pm$sa}{$t}\right),0\right\}";
+    const FORMAT_FOR_ORIGIN: &'static str = r"$a*exp(-max(0,(x$pm$sa)/($t)))*((x$pm$sa)/($t)>=0)";
 
     fn to_plottable_function(&self, params: &Params, significant_digits: u8, format: &'static str) -> String {
         let sd = significant_digits;
         params.0
             .chunks(3)
-            .into_iter()
             .map(|parts| {
                 let params = ExponentFunction::from_slice(parts);
-                let neg_shift = -params.shift;
                 assert_ne!(0., params.tau);
                 format_by_dollar_str(
                     format,
                     vec![
                         ("a", &params.amplitude.to_string_with_significant_digits(sd)),
-                        ("comp", if params.tau > 0. { "<" } else { ">" }),
-                        ("ns", &neg_shift.to_string_with_significant_digits(sd)),
-                        ("p", if neg_shift.is_sign_positive() { "+" } else { "" }),
+                        ("comp", if params.tau > 0. { r"\ge " } else { r"\le " }),
+                        ("pm", if !params.shift.is_sign_positive() { "+" } else { "-" }),
+                        ("s", &params.shift.to_string_with_significant_digits(sd)),
+                        ("sa", &params.shift.abs().to_string_with_significant_digits(sd)),
                         ("t", &params.tau.to_string_with_significant_digits(sd)),
                     ]
-                );
-                todo!("rewrite using `max(0,…)`");
+                )
             })
             .reduce(|acc, el| format!("{acc}+{el}"))
             .unwrap()
@@ -58,11 +60,11 @@ impl Function for Exponents {
 
 impl Load for Exponents {
     const TOML_NAME: &'static str = stringify!(Exponents);
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            initial_vads: InitialValues_Exponents::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_Exponents::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
     }
 }
 
@@ -74,7 +76,7 @@ pub struct InitialValues_Exponents<T> (
     Vec<T>
 );
 
-impl<T> InitialValuesGeneric<T> for InitialValues_Exponents<T> {
+impl<T: Clone> InitialValuesGeneric<T> for InitialValues_Exponents<T> {
     const LEN: usize = unreachable!();
 
     fn len(&self) -> usize {
@@ -82,30 +84,30 @@ impl<T> InitialValuesGeneric<T> for InitialValues_Exponents<T> {
     }
 
     fn from_vec(params: &ParamsG<T>) -> Self {
-        todo!()
+        assert_eq!(0, params.0.len() % 3);
+        Self(params.0.clone())
     }
 
     fn to_vec(&self) -> ParamsG<T> {
-        todo!()
+        ParamsG::<T>(self.0.clone())
     }
 
     fn params_to_points_v(&self, params: &ParamsV, points_len: usize, x_start_end: (float, float)) -> DeconvolvedV {
         assert_eq!(0, params.0.len() % 3);
-        unimplemented!()
-        // let exponents: Vec<ExponentFunction> = params.0
-        //     .chunks(3)
-        //     .into_iter()
-        //     .map(|parts| ExponentFunction::from_slice(parts))
-        //     .collect();
-        // let mut points = Vec::<float>::with_capacity(points_len);
-        // for i in 0..points_len {
-        //     let x: float = i_to_x(i, points_len, (x_start, x_end));
-        //     let sum: float = exponents.iter()
-        //         .map(|exponent| exponent.eval_at(x))
-        //         .sum();
-        //     points.push(sum);
-        // }
-        // Deconvolved(points)
+        let exponents: Vec<ExponentFunction> = params.0
+            .as_slice()
+            .chunks(3)
+            .map(ExponentFunction::from_slice)
+            .collect();
+        let mut points = DVect::zeros(points_len);
+        for i in 0..points_len {
+            let x: float = i_to_x(i, points_len, x_start_end);
+            let sum: float = exponents.iter()
+                .map(|exponent| exponent.eval_at(x))
+                .sum();
+            points[i] = sum;
+        }
+        DeconvolvedV(points)
     }
 }
 
@@ -120,21 +122,42 @@ impl From<InitialValues_Exponents<ValueAndDomain>> for InitialValues_Exponents<f
 
 impl Load for InitialValues_Exponents<ValueAndDomain> {
     const TOML_NAME: &'static str = "initial_values";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        // let initial_values = toml_value
-        //     .get("initial_values")
-        //     .expect("deconvolution_function -> Exponents: `initial_values` not found")
-        //     .as_array()
-        //     .expect("deconvolution_function -> Exponents -> initial_values: can't parse as list")
-        //     .iter()
-        //     .enumerate()
-        //     .map(|(i, initial_value)| {
-        //         initial_value
-        //             .as_float()
-        //             .expect(&format!("deconvolution_function -> Exponents -> initial_values[{i}]: can't parse as float"))
-        //     })
-        //     .collect::<Vec<_>>();
-        todo!()
+    /// Loads `a0=…, s0=…, t0=…, a1=…, s1=…, t1=…, …` (in any order),
+    /// indices must go from `0` without gaps.
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        let mut ivs: HashMap<String, ValueAndDomain> = str
+            .trim_matches(|c: char| c.is_whitespace() || c == ',')
+            .split(',')
+            // TODO: add index to stacktrace
+            .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
+            .collect::<Result<_>>()?;
+        let mut vads: Vec<ValueAndDomain> = vec![];
+        for i in 0.. {
+            let [name_a, name_s, name_t] = ["a", "s", "t"].map(|n| format!("{n}{i}"));
+            if ![&name_a, &name_s, &name_t].iter().any(|name| ivs.contains_key(*name)) { break }
+            for name in [name_a, name_s, name_t] {
+                let vad = ivs
+                    .remove(&name)
+                    .ok_or_else(|| stacktrace.err(&format!("`{name}` not found")))?;
+                vads.push(vad);
+            }
+        }
+        if vads.is_empty() {
+            return Err(stacktrace.pushed("a0").err_not_found());
+        }
+        if !ivs.is_empty() {
+            let mut unknown_names: Vec<&str> = ivs.keys().map(|name| name.as_str()).collect();
+            unknown_names.sort();
+            return Err(stacktrace.err(&format!(
+                "unexpected values: [{}], expected only `a{{i}}`, `s{{i}}`, `t{{i}}` with `i` from 0 to {}",
+                unknown_names.iter().map(|name| format!("`{name}`")).collect::<Vec<_>>().join(", "),
+                vads.len() / 3 - 1,
+            )));
+        }
+        Ok(Self(vads))
     }
 }
 
@@ -175,3 +198,94 @@ impl ExponentFunction {
     }
 }
 
+
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    fn load(initial_values: &str) -> Result<InitialValues_Exponents<ValueAndDomain>> {
+        let toml_value: TomlValue = toml::toml! { initial_values = initial_values }.into();
+        InitialValues_Exponents::load_from_parent_handle_stacktrace(&toml_value, &Stacktrace::new("Exponents"))
+    }
+
+    #[test]
+    fn one() {
+        assert_eq!(
+            InitialValues_Exponents(vec![ValueAndDomain::free(1.), ValueAndDomain::fixed(2.), ValueAndDomain::range_with_min(3., 0.)]),
+            load("a0=1, s0==2, t0=3>0").unwrap(),
+        );
+    }
+
+    #[test]
+    fn two_unordered() {
+        assert_eq!(
+            InitialValues_Exponents(vec![
+                ValueAndDomain::free(1.), ValueAndDomain::free(2.), ValueAndDomain::free(3.),
+                ValueAndDomain::free(4.), ValueAndDomain::free(5.), ValueAndDomain::range_closed(6., (0., 10.)),
+            ]),
+            load("
+                0 < t1 = 6 < 10, s1=5, a1=4,
+                a0=1, s0=2, t0=3,
+            ").unwrap(),
+        );
+    }
+
+    #[test]
+    fn missing_tau() {
+        assert_eq!(
+            "`Exponents` -> `initial_values`: `t1` not found",
+            load("a0=1, s0=2, t0=3, a1=4, s1=5").unwrap_err().to_string(),
+        );
+    }
+
+    #[test]
+    fn gap_in_indices() {
+        let err_msg = load("a0=1, s0=2, t0=3, a2=4, s2=5, t2=6").unwrap_err().to_string();
+        assert!(err_msg.contains("unexpected values: [`a2`, `s2`, `t2`]"), "actual: {err_msg}");
+    }
+}
+
+#[cfg(test)]
+mod params_to_points_v {
+    use super::*;
+
+    #[test]
+    fn sum_of_two() {
+        let initial_vads = InitialValues_Exponents(vec![ValueAndDomain::free(0.); 6]);
+        let params = ParamsV(DVect::from_vec(vec![1., 1., 2., 0.5, 2., -1.]));
+        let points_expected = DVect::from_vec(vec![0.5*exp(-2.), 1.+0.5*exp(-1.), exp(-0.5)+0.5, exp(-1.), exp(-1.5)]);
+        let points_actual = initial_vads.params_to_points_v(&params, 5, (0., 4.)).0;
+        assert!((points_expected.clone() - points_actual.clone()).abs().max() < 1e-12, "expected: {points_expected}, actual: {points_actual}");
+    }
+}
+
+#[cfg(test)]
+mod to_plottable_function {
+    use super::*;
+    use crate::deconvolution::types::FunctionAutoImplFns;
+
+    fn exponents() -> Exponents {
+        Exponents {
+            diff_function_type: DiffFunction::DySqr,
+            initial_vads: InitialValues_Exponents(vec![ValueAndDomain::free(0.); 6]),
+        }
+    }
+
+    #[test]
+    fn desmos() {
+        assert_eq!(
+            r"\left\{x\ge 1.000:2.000\exp\left(-\frac{x-1.000}{3.000}\right),0\right\}+\left\{x\le -4.000:5.000\exp\left(-\frac{x+4.000}{-6.000}\right),0\right\}",
+            exponents().to_desmos_function(&ParamsG(vec![2., 1., 3., 5., -4., -6.]), 4),
+        );
+    }
+
+    #[test]
+    fn origin() {
+        assert_eq!(
+            r"2.000*exp(-max(0,(x-1.000)/(3.000)))*((x-1.000)/(3.000)>=0)",
+            exponents().to_origin_function(&ParamsG(vec![2., 1., 3.]), 4),
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Fourier
+
+use toml::Value as TomlValue;
+
+use crate::{
+    config::ExtTomlValueLoadPrimitives,
+    diff_function::DiffFunction,
+    error::Result,
+    load::{LoadAutoImplFns, Load},
+    stacktrace::Stacktrace,
+    types::{float::{consts::PI, float}, named_wrappers::Params},
+};
+
+use super::super::fft::{complex, fft, ifft};
+
+use super::{Function, ValueAndDomain, per_points::InitialValues_PerPoint};
+
+
+/// Non-iterative deconvolution in Fourier space: `deconvolved = measured / instrument`,
+/// with regularization, so noise at frequencies, where instrument is almost zero, isn't amplified.
+///
+/// Params are deconvolved points, as in [`PerPoint`](super::per_points::PerPoint), calculated by single division,
+/// see [`is_fitted`](super::super::DeconvolutionVariant::is_fitted).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fourier {
+    /// Division of spectra doesn't minimize any residue, so it's ignored by deconvolution.
+    pub diff_function_type: DiffFunction,
+    pub regularization: FourierRegularization,
+    pub apodization: Option<Apodization>,
+    /// Values aren't used, it's len is number of frequencies, same as of measured points.
+    pub initial_vad: InitialValues_PerPoint<ValueAndDomain>,
+}
+
+impl Fourier {
+    /// Divide spectrum of `points_measured` by regularized spectrum of `points_instrument` (centered, with odd len).
+    ///
+    /// Result is on the grid of `points_measured`.
+    pub fn deconvolve(&self, points_instrument: &[float], points_measured: &[float]) -> Vec<float> {
+        let instrument_len = points_instrument.len();
+        assert!(instrument_len % 2 == 1, "instrument_len = {}", instrument_len);
+        let ilh = instrument_len / 2;
+        let measured_len = points_measured.len();
+        // zero padded, so circular convolution is same as linear one:
+        let fft_len: usize = (measured_len + instrument_len - 1).next_power_of_two();
+
+        let mut values_measured: Vec<complex> = vec![complex::new(0., 0.); fft_len];
+        for (value, &point) in values_measured.iter_mut().zip(points_measured) {
+            *value = complex::new(point, 0.);
+        }
+        // instrument is centered, so it's center is at zero index, and left half is at the end:
+        let mut values_instrument: Vec<complex> = vec![complex::new(0., 0.); fft_len];
+        for (i, &point) in points_instrument.iter().enumerate() {
+            values_instrument[(i + fft_len - ilh) % fft_len] = complex::new(point, 0.);
+        }
+        fft(&mut values_measured);
+        fft(&mut values_instrument);
+
+        let instrument_norm_sqr_max: float = values_instrument.iter()
+            .map(|value| value.norm_sqr())
+            .fold(0., float::max);
+        let mut values_deconvolved: Vec<complex> = values_measured.iter().zip(&values_instrument).enumerate()
+            .map(|(k, (value_measured, value_instrument))| {
+                // frequency relative to Nyquist one, from 0 to 1:
+                let frequency: float = (k.min(fft_len - k) as float) / (fft_len as float / 2.);
+                let regularization: float = instrument_norm_sqr_max * self.regularization.calc(frequency);
+                let apodization: float = self.apodization.as_ref().map_or(1., |apodization| apodization.calc(frequency));
+                let denominator: float = value_instrument.norm_sqr() + regularization;
+                if denominator == 0. { return complex::new(0., 0.) }
+                value_measured * value_instrument.conj() * (apodization / denominator)
+            })
+            .collect();
+        ifft(&mut values_deconvolved);
+        values_deconvolved[..measured_len].iter().map(|value| value.re).collect()
+    }
+}
+
+impl Function for Fourier {
+    const NAME: &'static str = "fourier";
+
+    const FORMAT_FOR_DESMOS: &'static str = unreachable!();
+    const FORMAT_FOR_ORIGIN: &'static str = unreachable!();
+
+    fn to_plottable_function(&self, _params: &Params, _significant_digits: u8, _format: &'static str) -> String {
+        unreachable!()
+    }
+}
+
+impl Load for Fourier {
+    const TOML_NAME: &'static str = stringify!(Fourier);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_optional_or_dy_sqr(toml_value, stacktrace)?,
+            regularization: FourierRegularization::load_from_fourier(toml_value, stacktrace)?,
+            // `apodization` is optional, so it's loaded only if present.
+            apodization: toml_value
+                .get(Apodization::TOML_NAME)
+                .map(|apodization| Apodization::load_from_self_handle_stacktrace(apodization, stacktrace))
+                .transpose()?,
+            // measured isn't loaded yet, so `len` is set by `sized_to_measured`.
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        })
+    }
+}
+
+
+/// Regularization constant `k` is relative to max of `|instrument|^2` in Fourier space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FourierRegularization {
+    /// `|I|^2 + k`: constant noise-to-signal ratio.
+    Wiener { k: float },
+    /// `|I|^2 + k * sin(π f/2)^4`: penalty on second derivative of deconvolved,
+    /// so low frequencies are almost not regularized.
+    Tikhonov { k: float },
+}
+
+impl FourierRegularization {
+    const TYPE_NAME: &'static str = "regularization_type";
+    const K_NAME: &'static str = "regularization_k";
+
+    /// `frequency` is relative to Nyquist one.
+    fn calc(&self, frequency: float) -> float {
+        match self {
+            Self::Wiener { k } => *k,
+            Self::Tikhonov { k } => k * (PI * frequency / 2.).sin().powi(4),
+        }
+    }
+}
+
+impl FourierRegularization {
+    /// Load from `regularization_type` and `regularization_k`, which are in [`Fourier`] table.
+    fn load_from_fourier(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let stacktrace_type = stacktrace.pushed(Self::TYPE_NAME);
+        let regularization_type_str = toml_value
+            .get(Self::TYPE_NAME)
+            .ok_or_else(|| stacktrace_type.err_not_found())?
+            .as_str()
+            .ok_or_else(|| stacktrace_type.err_cant_parse_as("string"))?;
+        let k = toml_value.load_float(Self::K_NAME, stacktrace)?;
+        if k < 0. {
+            return Err(stacktrace.pushed(Self::K_NAME).err("must be non-negative"));
+        }
+        Ok(match regularization_type_str {
+            "Wiener" | "wiener" => Self::Wiener { k },
+            "Tikhonov" | "tikhonov" => Self::Tikhonov { k },
+            _ => return Err(stacktrace_type.err_unknown_type(regularization_type_str, ["Wiener", "wiener", "Tikhonov", "tikhonov"]))
+        })
+    }
+}
+
+
+/// Window, which deconvolved is multiplied by in Fourier space, to suppress high frequencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Apodization {
+    apodization_type: ApodizationType,
+    /// Frequency relative to Nyquist one (`0..=1`), see [`ApodizationType`].
+    apodization_cutoff: float,
+}
+
+impl Apodization {
+    #[allow(dead_code)] // for tests
+    pub const fn new(apodization_type: ApodizationType, apodization_cutoff: float) -> Self {
+        Self { apodization_type, apodization_cutoff }
+    }
+
+    /// `frequency` is relative to Nyquist one.
+    fn calc(&self, frequency: float) -> float {
+        let cutoff = self.apodization_cutoff;
+        match self.apodization_type {
+            ApodizationType::Hann => if frequency < cutoff { (PI * frequency / (2. * cutoff)).cos().powi(2) } else { 0. },
+            ApodizationType::Gauss => (-frequency.powi(2) / (2. * cutoff.powi(2))).exp(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApodizationType {
+    /// `cos^2`, which is zero at and above cutoff.
+    Hann,
+    /// Gaussian with sigma equal to cutoff.
+    Gauss,
+}
+
+impl Load for Apodization {
+    const TOML_NAME: &'static str = "apodization";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let apodization_cutoff = toml_value.load_float("apodization_cutoff", stacktrace)?;
+        if apodization_cutoff <= 0. {
+            return Err(stacktrace.pushed("apodization_cutoff").err("must be positive"));
+        }
+        Ok(Self {
+            apodization_type: ApodizationType::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            apodization_cutoff,
+        })
+    }
+}
+
+impl Load for ApodizationType {
+    const TOML_NAME: &'static str = "apodization_type";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let apodization_type_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match apodization_type_str {
+            "Hann" | "hann" => Self::Hann,
+            "Gauss" | "gauss" => Self::Gauss,
+            _ => return Err(stacktrace.err_unknown_type(apodization_type_str, ["Hann", "hann", "Gauss", "gauss"]))
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod deconvolve {
+    use super::super::super::test_fixtures::{assert_points_close, points_deconvolved, points_instrument, points_measured};
+    use super::*;
+
+    fn fourier(regularization: FourierRegularization, apodization: Option<Apodization>) -> Fourier {
+        Fourier {
+            diff_function_type: DiffFunction::DySqr,
+            regularization,
+            apodization,
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        }
+    }
+
+    #[test]
+    fn recovers_deconvolved() {
+        // measured is without noise, so with small regularization deconvolved is recovered almost exactly:
+        let points_actual = fourier(FourierRegularization::Wiener { k: 1e-12 }, None)
+            .deconvolve(&points_instrument(), &points_measured());
+        assert_points_close(&points_deconvolved(), &points_actual, 1e-3);
+    }
+
+    #[test]
+    fn tikhonov_recovers_deconvolved() {
+        let points_actual = fourier(FourierRegularization::Tikhonov { k: 1e-8 }, None)
+            .deconvolve(&points_instrument(), &points_measured());
+        assert_points_close(&points_deconvolved(), &points_actual, 1e-3);
+    }
+
+    #[test]
+    fn delta_instrument_with_zero_regularization() {
+        let points_instrument = vec![0., 1., 0.];
+        let points_measured = vec![0., 1., 3., 2., 0.];
+        let points_actual = fourier(FourierRegularization::Wiener { k: 0. }, None)
+            .deconvolve(&points_instrument, &points_measured);
+        assert_points_close(&points_measured, &points_actual, 1e-12);
+    }
+
+    #[test]
+    fn strong_regularization_and_apodization_smooth() {
+        let points_sharp = fourier(FourierRegularization::Wiener { k: 1e-12 }, None)
+            .deconvolve(&points_instrument(), &points_measured());
+        for fourier in [
+            fourier(FourierRegularization::Wiener { k: 1. }, None),
+            fourier(FourierRegularization::Wiener { k: 1e-12 }, Some(Apodization::new(ApodizationType::Hann, 0.05))),
+            fourier(FourierRegularization::Wiener { k: 1e-12 }, Some(Apodization::new(ApodizationType::Gauss, 0.02))),
+        ] {
+            let points_smooth = fourier.deconvolve(&points_instrument(), &points_measured());
+            let max = |points: &[float]| points.iter().copied().fold(float::MIN, float::max);
+            assert!(max(&points_smooth) < max(&points_sharp), "{fourier:?}");
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn wiener_with_apodization() {
+        assert_eq!(
+            Fourier {
+                diff_function_type: DiffFunction::DySqr,
+                regularization: FourierRegularization::Wiener { k: 1e-3 },
+                apodization: Some(Apodization::new(ApodizationType::Hann, 0.5)),
+                initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+            },
+            Fourier::load_from_self(
+                &r#"
+                    regularization_type = "Wiener"
+                    regularization_k = 1e-3
+                    apodization = { apodization_type = "Hann", apodization_cutoff = 0.5 }
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::empty(),
+            ).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_regularization() {
+        assert_eq!(
+            "`Fourier` -> `regularization_type`: unkown type: `Lasso`, known types: [`Wiener`, `wiener`, `Tikhonov`, `tikhonov`]",
+            Fourier::load_from_self(
+                &r#"
+                    regularization_type = "Lasso"
+                    regularization_k = 1e-3
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::new("Fourier"),
+            ).unwrap_err().to_string()
+        );
+    }
+}
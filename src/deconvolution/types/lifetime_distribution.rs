@@ -0,0 +1,494 @@
+//! Lifetime Distribution
+
+use toml::Value as TomlValue;
+
+use crate::{
+    aliases_method_to_function::exp,
+    config::ExtTomlValueLoadPrimitives,
+    diff_function::DiffFunction,
+    error::Result,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::{DMat, DVect},
+        named_wrappers::{DeconvolvedV, Instrument, Params, ParamsV},
+    },
+};
+
+use super::super::{convolution::convolve_by_points_v, least_squares::nnls};
+
+use super::{Function, ValueAndDomain, i_to_x::i_to_x, per_points::InitialValues_PerPoint};
+
+
+/// Distribution of lifetimes: `sum a_i * exp(-(x-shift)/tau_i)`, with log-spaced `tau_i` from `tau_min` to `tau_max`.
+///
+/// Non-negative amplitudes are found by [`LifetimeDistributionMethod`] directly,
+/// see [`is_fitted`](super::super::DeconvolutionVariant::is_fitted).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifetimeDistribution {
+    /// Ignored by deconvolution, bc both NNLS and MEM use squared residuals (weighted).
+    pub diff_function_type: DiffFunction,
+    pub tau_min: float,
+    pub tau_max: float,
+    /// Start of all exponents.
+    pub shift: float,
+    pub method: LifetimeDistributionMethod,
+    /// Amplitudes, one for each tau.
+    pub initial_vad: InitialValues_PerPoint<ValueAndDomain>,
+}
+
+impl LifetimeDistribution {
+    pub fn get_taus_len(&self) -> usize {
+        self.initial_vad.len
+    }
+
+    /// Step between `log10` of taus.
+    pub fn get_log10_tau_step(&self) -> float {
+        (self.tau_max.log10() - self.tau_min.log10()) / ((self.get_taus_len() - 1) as float)
+    }
+
+    pub fn get_taus(&self) -> Vec<float> {
+        let log10_tau_step = self.get_log10_tau_step();
+        (0..self.get_taus_len())
+            .map(|i| float::powf(10., self.tau_min.log10() + i as float * log10_tau_step))
+            .collect()
+    }
+
+    pub fn params_to_points_v(&self, params: &ParamsV, points_len: usize, x_start_end: (float, float)) -> DeconvolvedV {
+        assert_eq!(self.get_taus_len(), params.0.len());
+        let taus = self.get_taus();
+        let mut points = DVect::zeros(points_len);
+        for i in 0..points_len {
+            let x: float = i_to_x(i, points_len, x_start_end);
+            if x < self.shift { continue }
+            points[i] = params.0.iter().zip(&taus)
+                .map(|(amplitude, tau)| amplitude * exp(-(x - self.shift) / tau))
+                .sum();
+        }
+        DeconvolvedV(points)
+    }
+
+    /// Amplitudes for `points_measured`, deconvolved by `points_instrument` (centered, with odd len),
+    /// number of iterations (zero for NNLS) and absolute `lambda` of regularization (if any).
+    ///
+    /// Residuals are multiplied by `weights` (`1/sigma`) and only `fit_indices` points are used (if given).
+    pub fn deconvolve(
+        &self,
+        points_instrument: &[float],
+        points_measured: &[float],
+        x_start_end: (float, float),
+        weights: Option<&DVect>,
+        fit_indices: Option<&[usize]>,
+    ) -> (Vec<float>, u64, Option<float>) {
+        let taus_len = self.get_taus_len();
+        let measured_len = points_measured.len();
+        // columns are exponents (with unit amplitude) convolved by instrument:
+        let mut a: DMat = DMat::zeros(measured_len, taus_len);
+        for j in 0..taus_len {
+            let params = ParamsV(DVect::from_fn(taus_len, |i, _| if i == j { 1. } else { 0. }));
+            let points_deconvolved = self.params_to_points_v(&params, measured_len, x_start_end);
+            let points_convolved = convolve_by_points_v(&Instrument(points_instrument.to_vec()).into(), points_deconvolved).0;
+            a.set_column(j, &points_convolved);
+        }
+        let mut measured: DVect = DVect::from_column_slice(points_measured);
+        if let Some(weights) = weights {
+            for (mut row, weight) in a.row_iter_mut().zip(weights.iter()) {
+                row *= *weight;
+            }
+            measured.component_mul_assign(weights);
+        }
+        if let Some(fit_indices) = fit_indices {
+            a = a.select_rows(fit_indices);
+            measured = measured.select_rows(fit_indices);
+        }
+        match self.method {
+            LifetimeDistributionMethod::Nnls { smoothing: None } => (nnls(&a, &measured).data.into(), 0, None),
+            LifetimeDistributionMethod::Nnls { smoothing: Some(smoothing) } => {
+                let (amplitudes, lambda) = Self::nnls_smoothed(&a, &measured, smoothing);
+                (amplitudes.data.into(), 0, Some(lambda))
+            }
+            LifetimeDistributionMethod::MaxEntropy { target_reduced_chi_square } => {
+                // relative to number of points, not degrees of freedom, bc effective number of params is much less than `taus_len`:
+                let chi_square_target: float = target_reduced_chi_square.unwrap_or(1.) * a.nrows() as float;
+                let (amplitudes, iterations, alpha) = Self::max_entropy(&a, &measured, chi_square_target);
+                (amplitudes.data.into(), iterations, Some(alpha))
+            }
+        }
+    }
+
+    /// NNLS with penalty `lambda |L x|^2` on second derivative, returns solution and absolute `lambda`.
+    fn nnls_smoothed(a: &DMat, measured: &DVect, smoothing: float) -> (DVect, float) {
+        let (rows, taus_len) = a.shape();
+        // second derivative:
+        let l: DMat = DMat::from_fn(taus_len.saturating_sub(2), taus_len, |i, j| {
+            match j.checked_sub(i) {
+                Some(0 | 2) => 1.,
+                Some(1) => -2.,
+                _ => 0.,
+            }
+        });
+        let lambda: float = smoothing * (a.transpose() * a).trace() / (l.transpose() * &l).trace().max(float::MIN_POSITIVE);
+        // `|A x - b|^2 + lambda |L x|^2` is `|[A; sqrt(lambda) L] x - [b; 0]|^2`:
+        let a_augmented: DMat = DMat::from_fn(rows + l.nrows(), taus_len, |i, j| {
+            if i < rows { a[(i, j)] } else { lambda.sqrt() * l[(i - rows, j)] }
+        });
+        let measured_augmented: DVect = measured.clone().resize_vertically(rows + l.nrows(), 0.);
+        (nnls(&a_augmented, &measured_augmented), lambda)
+    }
+
+    /// Maximum entropy: maximize entropy `S = sum (x_i - m - x_i ln(x_i/m))` with `|A x - b|^2 = chi_square_target`,
+    /// where `m` is flat default model (mean of NNLS solution).
+    ///
+    /// For each `alpha` minimum of `|A x - b|^2 / 2 - alpha S` is found by Newton method,
+    /// `|A x - b|^2` increases with `alpha`, so `alpha` is decreased by decades from max one
+    /// (where solution is almost default model), with previous solution as start, until target is reached,
+    /// and then it's found by bisection (in log scale).
+    /// If target can't be reached, best fit (min `alpha`) or flattest (max `alpha`) is returned.
+    ///
+    /// Returns solution, number of Newton iterations and `alpha`.
+    fn max_entropy(a: &DMat, measured: &DVect, chi_square_target: float) -> (DVect, u64, float) {
+        const BISECTION_ITERATIONS: usize = 40;
+        const ALPHA_DECADES: (i32, i32) = (-10, 6);
+        let taus_len = a.ncols();
+        let ata: DMat = a.transpose() * a;
+        let atb: DVect = a.transpose() * measured;
+        let default_model: float = nnls(a, measured).mean();
+        if default_model <= 0. { return (DVect::zeros(taus_len), 0, 0.) }
+        let log_alpha_scale: float = (ata.trace() / taus_len as float * default_model).log10();
+        let (decade_min, decade_max) = ALPHA_DECADES;
+
+        let mut iterations: u64 = 0;
+        let mut solve = |log_alpha: float, x: &mut DVect| -> float {
+            iterations += Self::max_entropy_newton(&ata, &atb, measured, float::powf(10., log_alpha), default_model, x);
+            (a * &*x - measured).norm_squared()
+        };
+
+        // `x_high` is solution at `log_alpha_high`, where `chi^2 > target`, and `x_low` is where `chi^2 <= target`:
+        let mut log_alpha_high: float = log_alpha_scale + decade_max as float;
+        let mut x_high: DVect = DVect::repeat(taus_len, default_model);
+        if solve(log_alpha_high, &mut x_high) <= chi_square_target {
+            return (x_high, iterations, float::powf(10., log_alpha_high))
+        }
+        let (mut log_alpha_low, mut x_low) = loop {
+            let log_alpha = log_alpha_high - 1.;
+            let mut x = x_high.clone();
+            let chi_square = solve(log_alpha, &mut x);
+            if chi_square <= chi_square_target { break (log_alpha, x) }
+            if log_alpha <= log_alpha_scale + decade_min as float {
+                return (x, iterations, float::powf(10., log_alpha))
+            }
+            (log_alpha_high, x_high) = (log_alpha, x);
+        };
+        for _ in 0..BISECTION_ITERATIONS {
+            let log_alpha_mid = (log_alpha_low + log_alpha_high) / 2.;
+            let mut x_mid = x_high.clone();
+            if solve(log_alpha_mid, &mut x_mid) <= chi_square_target {
+                (log_alpha_low, x_low) = (log_alpha_mid, x_mid);
+            } else {
+                (log_alpha_high, x_high) = (log_alpha_mid, x_mid);
+            }
+        }
+        (x_low, iterations, float::powf(10., log_alpha_low))
+    }
+
+    /// Minimize `|A x - b|^2 / 2 - alpha S` by Newton method, starting from `x` (must be positive), keeping it positive.
+    ///
+    /// Returns number of iterations.
+    fn max_entropy_newton(ata: &DMat, atb: &DVect, measured: &DVect, alpha: float, default_model: float, x: &mut DVect) -> u64 {
+        const ITERATIONS_MAX: u64 = 200;
+        const TOLERANCE: float = 1e-12;
+        // `|A x - b|^2 / 2` is `x^T A^T A x / 2 - x^T A^T b + b^T b / 2`:
+        let calc_objective = |x: &DVect| -> float {
+            let entropy: float = x.iter().map(|&x_i| x_i - default_model - x_i * (x_i / default_model).ln()).sum();
+            x.dot(&(ata * x)) / 2. - x.dot(atb) + measured.norm_squared() / 2. - alpha * entropy
+        };
+        let mut objective: float = calc_objective(x);
+        for iteration in 1..=ITERATIONS_MAX {
+            let gradient: DVect = ata * &*x - atb + x.map(|x_i| alpha * (x_i / default_model).ln());
+            let hessian: DMat = ata + DMat::from_diagonal(&x.map(|x_i| alpha / x_i));
+            let direction: DVect = -hessian.cholesky().expect("must be positive definite").solve(&gradient);
+            // step is limited, so `x` stays positive:
+            let mut step: float = (0..x.len())
+                .filter(|&i| direction[i] < 0.)
+                .map(|i| 0.9 * x[i] / -direction[i])
+                .fold(1., float::min);
+            let (x_new, objective_new) = loop {
+                let x_new: DVect = &*x + step * &direction;
+                let objective_new = calc_objective(&x_new);
+                if objective_new <= objective || step < TOLERANCE { break (x_new, objective_new) }
+                step /= 2.;
+            };
+            let change_max: float = (&x_new - &*x).amax();
+            *x = x_new;
+            let objective_change: float = objective - objective_new;
+            objective = objective_new;
+            if change_max <= TOLERANCE * x.amax() || objective_change.abs() <= TOLERANCE * objective.abs() {
+                return iteration
+            }
+        }
+        ITERATIONS_MAX
+    }
+}
+
+impl Function for LifetimeDistribution {
+    const NAME: &'static str = "lifetime_distribution";
+
+    const FORMAT_FOR_DESMOS: &'static str = unreachable!();
+    const FORMAT_FOR_ORIGIN: &'static str = unreachable!();
+
+    fn to_plottable_function(&self, _params: &Params, _significant_digits: u8, _format: &'static str) -> String {
+        unreachable!()
+    }
+}
+
+impl Load for LifetimeDistribution {
+    const TOML_NAME: &'static str = stringify!(LifetimeDistribution);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let tau_min = toml_value.load_float("tau_min", stacktrace)?;
+        let tau_max = toml_value.load_float("tau_max", stacktrace)?;
+        if !(0. < tau_min && tau_min < tau_max) {
+            return Err(stacktrace.err("`tau_min` and `tau_max` must be `0 < tau_min < tau_max`"));
+        }
+        let taus_len = toml_value.load_u64("taus_len", stacktrace)?;
+        if taus_len < 2 {
+            return Err(stacktrace.pushed("taus_len").err("must be at least 2"));
+        }
+        Ok(Self {
+            diff_function_type: DiffFunction::load_optional_or_dy_sqr(toml_value, stacktrace)?,
+            tau_min,
+            tau_max,
+            shift: toml_value.load_float("shift", stacktrace)?,
+            method: LifetimeDistributionMethod::load_from_lifetime_distribution(toml_value, stacktrace)?,
+            initial_vad: InitialValues_PerPoint::new(taus_len as usize, ValueAndDomain::range_with_min(0., 0.)),
+        })
+    }
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifetimeDistributionMethod {
+    /// Non-Negative Least Squares, with optional `smoothing`: penalty on second derivative of amplitudes,
+    /// relative to ratio of traces of `A^T A` and `L^T L`.
+    Nnls { smoothing: Option<float> },
+    /// Maximum Entropy Method: smoothest (max entropy) non-negative distribution with given reduced chi square
+    /// (`chi^2 / N`, where `N` is number of points used in fit).
+    ///
+    /// If it isn't given, it's `1`, which makes sense only if measured points are weighted by their sigmas.
+    MaxEntropy { target_reduced_chi_square: Option<float> },
+}
+
+impl LifetimeDistributionMethod {
+    const TYPE_NAME: &'static str = "method";
+    const SMOOTHING_NAME: &'static str = "smoothing";
+    const TARGET_REDUCED_CHI_SQUARE_NAME: &'static str = "target_reduced_chi_square";
+
+    /// Load from `method` (optional, default is `nnls`) and it's params, which are in [`LifetimeDistribution`] table.
+    fn load_from_lifetime_distribution(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let stacktrace_type = stacktrace.pushed(Self::TYPE_NAME);
+        let method_str: &str = match toml_value.get(Self::TYPE_NAME) {
+            None => "nnls",
+            Some(method) => method.as_str().ok_or_else(|| stacktrace_type.err_cant_parse_as("string"))?,
+        };
+        let smoothing = toml_value.load_optional_non_negative_float(Self::SMOOTHING_NAME, stacktrace)?;
+        let target_reduced_chi_square = toml_value.load_optional_non_negative_float(Self::TARGET_REDUCED_CHI_SQUARE_NAME, stacktrace)?;
+        Ok(match method_str {
+            "nnls" => {
+                if target_reduced_chi_square.is_some() {
+                    return Err(stacktrace.pushed(Self::TARGET_REDUCED_CHI_SQUARE_NAME).err("can be used only with `method = \"max_entropy\"`"));
+                }
+                Self::Nnls { smoothing }
+            }
+            "max_entropy" => {
+                if smoothing.is_some() {
+                    return Err(stacktrace.pushed(Self::SMOOTHING_NAME).err("can be used only with `method = \"nnls\"`"));
+                }
+                // optional, see `Config::check_sections_consistency` for default
+                Self::MaxEntropy { target_reduced_chi_square }
+            }
+            _ => return Err(stacktrace_type.err_unknown_type(method_str, ["nnls", "max_entropy"]))
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod deconvolve {
+    use super::super::super::test_fixtures::points_instrument;
+    use super::*;
+
+    fn lifetime_distribution(taus_len: usize, method: LifetimeDistributionMethod) -> LifetimeDistribution {
+        LifetimeDistribution {
+            diff_function_type: DiffFunction::DySqr,
+            tau_min: 1.,
+            tau_max: 100.,
+            shift: 20.,
+            method,
+            initial_vad: InitialValues_PerPoint::new(taus_len, ValueAndDomain::range_with_min(0., 0.)),
+        }
+    }
+
+    const X_START_END: (float, float) = (0., 199.);
+
+    #[test]
+    fn taus() {
+        let taus = lifetime_distribution(3, LifetimeDistributionMethod::Nnls { smoothing: None }).get_taus();
+        for (expected, actual) in [1., 10., 100.].iter().zip(&taus) {
+            assert!((expected - actual).abs() < 1e-12, "actual: {taus:?}");
+        }
+    }
+
+    #[test]
+    fn recovers_two_taus() {
+        let lifetime_distribution = lifetime_distribution(5, LifetimeDistributionMethod::Nnls { smoothing: None });
+        // taus are 1, ~3.16, 10, ~31.6, 100:
+        let params_expected: Vec<float> = vec![0., 0., 2., 0., 0.5];
+        let points_measured: Vec<float> = convolve_by_points_v(
+            &Instrument(points_instrument()).into(),
+            lifetime_distribution.params_to_points_v(&ParamsV(DVect::from_vec(params_expected.clone())), 200, X_START_END),
+        ).0.data.into();
+        let (params_actual, _, lambda) = lifetime_distribution.deconvolve(&points_instrument(), &points_measured, X_START_END, None, None);
+        assert_eq!(None, lambda);
+        for (expected, actual) in params_expected.iter().zip(&params_actual) {
+            assert!((expected - actual).abs() < 1e-6, "actual: {params_actual:?}");
+        }
+    }
+
+    /// Exponent with `tau = 10`, starting at `shift`, convolved by instrument.
+    fn points_measured_tau_10() -> Vec<float> {
+        let points_deconvolved: Vec<float> = (0..200)
+            .map(|i| if i < 20 { 0. } else { 2. * (-(i as float - 20.) / 10.).exp() })
+            .collect();
+        convolve_by_points_v(
+            &Instrument(points_instrument()).into(),
+            DeconvolvedV(points_deconvolved.into()),
+        ).0.data.into()
+    }
+
+    fn assert_max_amplitude_near_tau_10(lifetime_distribution: &LifetimeDistribution, params_actual: &[float]) {
+        let taus = lifetime_distribution.get_taus();
+        let (tau_max_amplitude, _) = taus.iter().zip(params_actual)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        assert!(5. < *tau_max_amplitude && *tau_max_amplitude < 20., "actual: {params_actual:?}");
+    }
+
+    #[test]
+    fn smoothing_is_non_negative_and_fits() {
+        let lifetime_distribution = lifetime_distribution(30, LifetimeDistributionMethod::Nnls { smoothing: Some(1e-3) });
+        let (params_actual, _, lambda) = lifetime_distribution.deconvolve(&points_instrument(), &points_measured_tau_10(), X_START_END, None, None);
+        assert!(lambda.unwrap() > 0.);
+        assert!(params_actual.iter().all(|&a| a >= 0.), "actual: {params_actual:?}");
+        assert_max_amplitude_near_tau_10(&lifetime_distribution, &params_actual);
+    }
+
+    #[test]
+    fn max_entropy_reaches_target_chi_square() {
+        // best fit have reduced chi square a bit more than `1`, bc of "noise" and discrete taus:
+        let lifetime_distribution = lifetime_distribution(30, LifetimeDistributionMethod::MaxEntropy { target_reduced_chi_square: Some(1.1) });
+        const SIGMA: float = 0.01;
+        // "noise" is `±sigma`, so reduced chi square of perfect fit is `1`:
+        let points_measured: Vec<float> = points_measured_tau_10().iter().enumerate()
+            .map(|(i, p)| p + if i % 2 == 0 { SIGMA } else { -SIGMA })
+            .collect();
+        let weights = DVect::repeat(points_measured.len(), 1. / SIGMA);
+        let (params_actual, iterations, alpha) = lifetime_distribution.deconvolve(&points_instrument(), &points_measured, X_START_END, Some(&weights), None);
+        assert!(iterations > 0);
+        assert!(alpha.unwrap() > 0.);
+        assert!(params_actual.iter().all(|&a| a > 0.), "actual: {params_actual:?}");
+        assert_max_amplitude_near_tau_10(&lifetime_distribution, &params_actual);
+        let points_convolved: Vec<float> = convolve_by_points_v(
+            &Instrument(points_instrument()).into(),
+            lifetime_distribution.params_to_points_v(&ParamsV(DVect::from_vec(params_actual.clone())), 200, X_START_END),
+        ).0.data.into();
+        let chi_square: float = points_measured.iter().zip(&points_convolved)
+            .map(|(measured, convolved)| ((measured - convolved) / SIGMA).powi(2))
+            .sum();
+        let reduced_chi_square: float = chi_square / 200.;
+        assert!((reduced_chi_square - 1.1).abs() < 1e-3, "reduced_chi_square = {reduced_chi_square}");
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn with_smoothing() {
+        assert_eq!(
+            LifetimeDistribution {
+                diff_function_type: DiffFunction::DySqr,
+                tau_min: 0.1,
+                tau_max: 1000.,
+                shift: 296.,
+                method: LifetimeDistributionMethod::Nnls { smoothing: Some(1e-3) },
+                initial_vad: InitialValues_PerPoint::new(50, ValueAndDomain::range_with_min(0., 0.)),
+            },
+            LifetimeDistribution::load_from_self(
+                &r#"
+                    tau_min = 0.1
+                    tau_max = 1000.0
+                    taus_len = 50
+                    shift = 296.0
+                    smoothing = 1e-3
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::empty(),
+            ).unwrap()
+        );
+    }
+
+    #[test]
+    fn max_entropy() {
+        assert_eq!(
+            LifetimeDistributionMethod::MaxEntropy { target_reduced_chi_square: Some(1.1) },
+            LifetimeDistribution::load_from_self(
+                &r#"
+                    tau_min = 0.1
+                    tau_max = 1000.0
+                    taus_len = 50
+                    shift = 296.0
+                    method = "max_entropy"
+                    target_reduced_chi_square = 1.1
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::empty(),
+            ).unwrap().method
+        );
+    }
+
+    #[test]
+    fn max_entropy_with_smoothing() {
+        assert_eq!(
+            "`LifetimeDistribution` -> `smoothing`: can be used only with `method = \"nnls\"`",
+            LifetimeDistribution::load_from_self(
+                &r#"
+                    tau_min = 0.1
+                    tau_max = 1000.0
+                    taus_len = 50
+                    shift = 296.0
+                    method = "max_entropy"
+                    smoothing = 1e-3
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::new(LifetimeDistribution::TOML_NAME),
+            ).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn wrong_taus() {
+        assert_eq!(
+            "`LifetimeDistribution`: `tau_min` and `tau_max` must be `0 < tau_min < tau_max`",
+            LifetimeDistribution::load_from_self(
+                &r#"
+                    tau_min = 10.0
+                    tau_max = 1.0
+                    taus_len = 50
+                    shift = 0.0
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::new(LifetimeDistribution::TOML_NAME),
+            ).unwrap_err().to_string()
+        );
+    }
+}
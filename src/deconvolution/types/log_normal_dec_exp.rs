@@ -0,0 +1,232 @@
+//! LogNormalDecExp
+
+use std::collections::HashMap;
+
+use toml::Value as TomlValue;
+
+use crate::{
+    aliases_method_to_function::exp,
+    diff_function::DiffFunction,
+    error::Result,
+    extensions::ToStringWithSignificantDigits,
+    load::{LoadAutoImplFns, Load},
+    stacktrace::Stacktrace,
+    types::{float::float, linalg::DVect, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
+    utils_io::format_by_dollar_str,
+};
+
+use super::super::initial_values::{InitialValuesGeneric, InitialValuesVAD};
+
+use super::{Function, ValueAndDomain, i_to_x::i_to_x};
+
+
+/// Half of number of quadrature nodes (without central one), see [`LogNormalDecExp::calc_weights_and_taus`].
+const QUADRATURE_HALF_LEN: i32 = 8;
+/// Step of quadrature nodes in units of `sigma`, so nodes are in `±4 sigma`.
+const QUADRATURE_STEP: float = 0.5;
+
+
+// a * ∫ p(τ) exp(-(x-s)/τ) dτ, for x >= s
+/// Decaying exponents with log-normal distribution of taus:
+/// `a * ∫ p(τ) exp(-(x-s)/τ) dτ` for `x >= s`, where `ln(τ)` is normal with mean `ln(t)` and standard deviation `sigma`.
+///
+/// Integral is calculated by fixed quadrature, so same sum of exponents is used for plottable functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogNormalDecExp {
+    pub diff_function_type: DiffFunction,
+    pub initial_vads: InitialValues_LogNormalDecExp<ValueAndDomain>,
+}
+
+impl LogNormalDecExp {
+    /// Quadrature weights (their sum is `1`) and taus, at which exponents are summed.
+    pub fn calc_weights_and_taus(tau: float, sigma: float) -> Vec<(float, float)> {
+        let zs: Vec<float> = (-QUADRATURE_HALF_LEN..=QUADRATURE_HALF_LEN)
+            .map(|k| k as float * QUADRATURE_STEP)
+            .collect();
+        let weights_sum: float = zs.iter().map(|z| exp(-z*z/2.)).sum();
+        zs.iter()
+            .map(|z| (exp(-z*z/2.) / weights_sum, tau * exp(sigma * z)))
+            .collect()
+    }
+}
+
+impl Function for LogNormalDecExp {
+    const NAME: &'static str = "log-normal decaying exponential";
+
+    // exponent is "cut" before shift, same as in [`Exponents`](super::exponents::Exponents).
+    const FORMAT_FOR_DESMOS: &'static str = r"\left\{x\ge $s:$a\exp\left(-\frac{x$pm$sa}{$t}\right),0\right\}";
+    const FORMAT_FOR_ORIGIN: &'static str = r"$a*exp(-max(0,(x$pm$sa)/($t)))*((x$pm$sa)/($t)>=0)";
+
+    fn to_plottable_function(&self, params: &Params, significant_digits: u8, format: &'static str) -> String {
+        let params = InitialValues_LogNormalDecExp::from_vec(params);
+        let sd = significant_digits;
+        Self::calc_weights_and_taus(params.tau, params.sigma)
+            .into_iter()
+            .map(|(weight, tau)| {
+                format_by_dollar_str(
+                    format,
+                    vec![
+                        ("a", &(params.amplitude * weight).to_string_with_significant_digits(sd)),
+                        ("pm", if !params.shift.is_sign_positive() { "+" } else { "-" }),
+                        ("s", &params.shift.to_string_with_significant_digits(sd)),
+                        ("sa", &params.shift.abs().to_string_with_significant_digits(sd)),
+                        ("t", &tau.to_string_with_significant_digits(sd)),
+                    ]
+                )
+            })
+            .reduce(|acc, el| format!("{acc}+{el}"))
+            .unwrap()
+    }
+}
+
+impl Load for LogNormalDecExp {
+    const TOML_NAME: &'static str = stringify!(LogNormalDecExp);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_LogNormalDecExp::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
+    }
+}
+
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InitialValues_LogNormalDecExp<T> {
+    pub amplitude: T,
+    pub shift: T,
+    /// Median tau.
+    pub tau: T,
+    /// Width of distribution, standard deviation of `ln(tau)`.
+    pub sigma: T,
+}
+
+impl InitialValues_LogNormalDecExp<float> {
+    fn from_vec_vf(params: &ParamsV) -> Self {
+        match params.0.as_slice()[..] {
+            [      amplitude, shift, tau, sigma ] =>
+            Self { amplitude, shift, tau, sigma },
+            _ => unreachable!()
+        }
+    }
+}
+
+impl<T: Copy> InitialValuesGeneric<T> for InitialValues_LogNormalDecExp<T> {
+    const LEN: usize = 4;
+
+    fn from_vec(params: &ParamsG<T>) -> Self {
+        match params.0[..] {
+            [amplitude, shift, tau, sigma] => Self { amplitude, shift, tau, sigma },
+            _ => unreachable!()
+        }
+    }
+
+    fn to_vec(&self) -> ParamsG<T> {
+        let Self { amplitude, shift, tau, sigma } = *self;
+        ParamsG::<T>(vec![amplitude, shift, tau, sigma])
+    }
+
+    fn params_to_points_v(&self, params: &ParamsV, points_len: usize, x_start_end: (float, float)) -> DeconvolvedV {
+        type SelfF = InitialValues_LogNormalDecExp<float>;
+        let SelfF { amplitude, shift, tau, sigma } = SelfF::from_vec_vf(params);
+        let weights_and_taus = LogNormalDecExp::calc_weights_and_taus(tau, sigma);
+        let mut points = DVect::zeros(points_len);
+        for i in 0..points_len {
+            let x: float = i_to_x(i, points_len, x_start_end);
+            let x_m_shift = x - shift;
+            if x_m_shift < 0. { continue }
+            let y: float = weights_and_taus.iter()
+                .map(|(weight, tau)| weight * exp(-x_m_shift/tau))
+                .sum();
+            points[i] = amplitude * y;
+        }
+        DeconvolvedV(points)
+    }
+}
+
+impl InitialValuesVAD for InitialValues_LogNormalDecExp<ValueAndDomain> {}
+
+impl From<InitialValues_LogNormalDecExp<ValueAndDomain>> for InitialValues_LogNormalDecExp<float> {
+    fn from(value: InitialValues_LogNormalDecExp<ValueAndDomain>) -> Self {
+        Self::from_vec(&ParamsG::<float>(value.to_vec().0.iter().map(|v| v.value).collect::<Vec<float>>()))
+    }
+}
+
+
+impl Load for InitialValues_LogNormalDecExp<ValueAndDomain> {
+    const TOML_NAME: &'static str = "initial_values";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        let ivs: HashMap<String, ValueAndDomain> = str
+            .trim_matches(|c: char| c.is_whitespace() || c == ',')
+            .split(',')
+            // TODO: add index to stacktrace
+            .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
+            .collect::<Result<_>>()?;
+        let try_get = |name: &'static str| -> Result<ValueAndDomain> {
+            ivs
+                .get(name)
+                .copied()
+                .ok_or_else(|| stacktrace.pushed(name).err_not_found())
+        };
+        Ok(Self {
+            amplitude: try_get("a")?,
+            shift: try_get("s")?,
+            tau: try_get("t")?,
+            sigma: try_get("sigma")?,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod params_to_points_v {
+    use super::*;
+
+    fn initial_vads() -> InitialValues_LogNormalDecExp<ValueAndDomain> {
+        InitialValues_LogNormalDecExp::from_vec(&ParamsG(vec![ValueAndDomain::free(0.); 4]))
+    }
+
+    #[test]
+    fn sigma_0_is_dec_exp() {
+        let params = ParamsV(DVect::from_vec(vec![2., 1., 3., 0.]));
+        let points_expected = DVect::from_vec(vec![0., 2., 2.*exp(-1./3.), 2.*exp(-2./3.), 2.*exp(-1.)]);
+        let points_actual = initial_vads().params_to_points_v(&params, 5, (0., 4.)).0;
+        assert!((points_expected.clone() - points_actual.clone()).abs().max() < 1e-12, "expected: {points_expected}, actual: {points_actual}");
+    }
+
+    #[test]
+    fn mean_of_distribution() {
+        // integral over `x` is `a * mean(τ) = a * t * exp(sigma^2/2)`:
+        let (tau, sigma) = (2., 0.5);
+        let params = ParamsV(DVect::from_vec(vec![1., 0., tau, sigma]));
+        let (points_len, x_end) = (100_001, 1000.);
+        let points = initial_vads().params_to_points_v(&params, points_len, (0., x_end)).0;
+        let step = x_end / (points_len - 1) as float;
+        let integral: float = step * (points.sum() - (points[0] + points[points_len-1]) / 2.);
+        let integral_expected: float = tau * exp(sigma*sigma/2.);
+        assert!((integral_expected - integral).abs() < 1e-3 * integral_expected, "expected: {integral_expected}, actual: {integral}");
+    }
+}
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn all() {
+        let toml_value: TomlValue = toml::toml! { initial_values = "a=1, s=-2, t=10>0, sigma=0.5>0" }.into();
+        assert_eq!(
+            InitialValues_LogNormalDecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::free(-2.),
+                tau: ValueAndDomain::range_with_min(10., 0.),
+                sigma: ValueAndDomain::range_with_min(0.5, 0.),
+            },
+            InitialValues_LogNormalDecExp::load_from_parent_handle_stacktrace(&toml_value, &Stacktrace::new("LogNormalDecExp")).unwrap(),
+        );
+    }
+}
@@ -3,13 +3,21 @@
 pub mod value_and_domain;
 
 // functions:
+pub mod custom;
 pub mod exponents;
+pub mod fourier;
+pub mod lifetime_distribution;
+pub mod log_normal_dec_exp;
 pub mod per_points;
+pub mod regularized;
+pub mod richardson_lucy;
 #[allow(non_snake_case)]
 pub mod sat_exp__dec_exp;
 #[allow(non_snake_case)]
 pub mod sat_exp__dec_exp_plus_const;
 #[allow(non_snake_case)]
+pub mod sat_exp__stretched_exp;
+#[allow(non_snake_case)]
 pub mod sat_exp__two_dec_exp;
 #[allow(non_snake_case)]
 pub mod sat_exp__two_dec_exp__constrained_consts;
@@ -7,6 +7,7 @@ use toml::Value as TomlValue;
 use crate::{
     antispikes::Antispikes,
     diff_function::DiffFunction,
+    error::Result,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
     types::{float::float, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
@@ -38,12 +39,16 @@ impl Function for PerPoint {
 
 impl Load for PerPoint {
     const TOML_NAME: &'static str = stringify!(PerPoint);
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            antispikes: Some(Antispikes::load_from_parent_handle_stacktrace(toml_value, stacktrace)),
-            initial_vad: InitialValues_PerPoint::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            // `antispikes` are optional, so they are loaded only if present.
+            antispikes: toml_value
+                .get(Antispikes::TOML_NAME)
+                .map(|antispikes| Antispikes::load_from_self_handle_stacktrace(antispikes, stacktrace))
+                .transpose()?,
+            initial_vad: InitialValues_PerPoint::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
     }
 }
 
@@ -60,18 +65,28 @@ impl<T> InitialValues_PerPoint<T> {
     pub const fn new(len: usize, vad: T) -> Self {
         Self { len, vad }
     }
+
+    /// Set number of points, must be called after [`DeconvolutionData::aligned_steps_to`],
+    /// bc it's unknown at config loading stage.
+    ///
+    /// [`DeconvolutionData::aligned_steps_to`]: crate::deconvolution::deconvolution_data::DeconvolutionData::aligned_steps_to
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
 }
 
-impl<T: Copy + Debug> InitialValuesGeneric<T> for InitialValues_PerPoint<T> {
+impl<T: Copy + Debug + PartialEq> InitialValuesGeneric<T> for InitialValues_PerPoint<T> {
     const LEN: usize = unreachable!();
 
     fn len(&self) -> usize {
         self.len
     }
 
+    /// Only inverse of [`to_vec`](InitialValuesGeneric::to_vec), so all `params` must be equal.
     fn from_vec(params: &ParamsG<T>) -> Self {
-        dbg!(params);
-        unreachable!()
+        let vad: T = *params.0.first().expect("PerPoint: params must not be empty");
+        assert!(params.0.iter().all(|&p| p == vad), "PerPoint: all params must be equal, but params = {params:?}");
+        Self::new(params.0.len(), vad)
     }
 
     fn to_vec(&self) -> ParamsG<T> {
@@ -85,16 +100,26 @@ impl<T: Copy + Debug> InitialValuesGeneric<T> for InitialValues_PerPoint<T> {
 
 impl InitialValuesVAD for InitialValues_PerPoint<ValueAndDomain> {}
 
+impl From<InitialValues_PerPoint<ValueAndDomain>> for InitialValues_PerPoint<float> {
+    fn from(value: InitialValues_PerPoint<ValueAndDomain>) -> Self {
+        Self::new(value.len, value.vad.value)
+    }
+}
+
 
 impl Load for InitialValues_PerPoint<ValueAndDomain> {
-    const TOML_NAME: &'static str = "initial_values";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        // let initial_vad = toml_value
-        //     .get("initial_value")
-        //     .expect("deconvolution_function -> PerPoint: `initial_value` not found")
-        //     .as_float()
-        //     .expect("deconvolution_function -> PerPoint -> initial_value: can't parse as float");
-        todo!()
+    const TOML_NAME: &'static str = "initial_value";
+    /// `len` is set to `0` here, bc number of points is known only after measured spectrum is loaded,
+    /// so [`InitialValues_PerPoint::set_len`] must be used later.
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        let (name, vad) = ValueAndDomain::load_from_str(str.trim_matches(|c: char| c.is_whitespace() || c == ','), stacktrace)?;
+        if name != "v" {
+            return Err(stacktrace.err_unknown_type(&name, ["v"]));
+        }
+        Ok(Self::new(0, vad))
     }
 }
 
@@ -0,0 +1,463 @@
+//! Regularized Least Squares
+
+use nalgebra::{Cholesky, Dyn, SymmetricEigen};
+use toml::Value as TomlValue;
+
+use crate::{
+    config::ExtTomlValueLoadPrimitives,
+    diff_function::DiffFunction,
+    error::Result,
+    load::{LoadAutoImplFns, Load},
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::{DMat, DVect},
+        named_wrappers::Params,
+    },
+};
+
+use super::super::least_squares::{convolution_matrix, solve_non_negative};
+
+use super::{Function, ValueAndDomain, per_points::InitialValues_PerPoint};
+
+
+/// Non-iterative deconvolution: minimize `|K x - measured|^2 + lambda * penalty(x)`,
+/// where `K` is convolution by instrument, and `lambda` is chosen automatically.
+///
+/// Params are deconvolved points, as in [`PerPoint`](super::per_points::PerPoint), at the minimum for chosen `lambda`,
+/// see [`is_fitted`](super::super::DeconvolutionVariant::is_fitted).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regularized {
+    /// Ignored by deconvolution, bc it always minimizes squared residuals (weighted) plus penalty.
+    pub diff_function_type: DiffFunction,
+    pub penalty: Penalty,
+    pub non_negative: bool,
+    pub lambda_selection: LambdaSelection,
+    /// Values aren't used, it's len is size of `K` (same as number of measured points).
+    pub initial_vad: InitialValues_PerPoint<ValueAndDomain>,
+}
+
+impl Regularized {
+    /// Number of `lambda`s per decade, at which selection criterion is calculated.
+    const LAMBDAS_PER_DECADE: usize = 10;
+    /// Range of `lambda`s (in decades), relative to ratio of traces of `K^T K` and `L^T L`.
+    const LAMBDAS_DECADES: (i32, i32) = (-12, 4);
+    const TOTAL_VARIATION_ITERATIONS_MAX: usize = 100;
+    /// Ridge added to singular matrix before Cholesky decomposition, relative to mean of its diagonal.
+    const RIDGE_RELATIVE: float = 1e-12;
+
+    /// Solve regularized least squares for `points_measured` and `points_instrument` (centered, with odd len).
+    ///
+    /// Residuals are multiplied by `weights` (`1/sigma`) and only `fit_indices` points are used (if given),
+    /// but deconvolved points are found at all points.
+    ///
+    /// Returns deconvolved points and chosen `lambda`.
+    ///
+    /// `lambda` is chosen for unconstrained problem with quadratic penalty `|L x|^2`
+    /// (for [`TotalVariation`](Penalty::TotalVariation) `L` is first derivative),
+    /// and then non-negativity and total variation are applied with chosen `lambda`.
+    pub fn deconvolve(
+        &self,
+        points_instrument: &[float],
+        points_measured: &[float],
+        weights: Option<&DVect>,
+        fit_indices: Option<&[usize]>,
+    ) -> (Vec<float>, float) {
+        let len: usize = points_measured.len();
+        let mut k: DMat = convolution_matrix(points_instrument, len);
+        let mut measured: DVect = DVect::from_column_slice(points_measured);
+        if let Some(weights) = weights {
+            for (mut row, weight) in k.row_iter_mut().zip(weights.iter()) {
+                row *= *weight;
+            }
+            measured.component_mul_assign(weights);
+        }
+        if let Some(fit_indices) = fit_indices {
+            k = k.select_rows(fit_indices);
+            measured = measured.select_rows(fit_indices);
+        }
+        let l: DMat = self.penalty.matrix(len);
+        let ktk: DMat = k.transpose() * &k;
+        let ltl: DMat = l.transpose() * &l;
+        let ktm: DVect = k.transpose() * &measured;
+
+        let (lambda, deconvolved) = self.select_lambda(&k, &l, &ktk, &ltl, &ktm, &measured);
+
+        let (lambda, deconvolved) = match self.penalty {
+            Penalty::TotalVariation => self.solve_total_variation(&l, &ktk, &ktm, lambda, deconvolved),
+            Penalty::Zeroth | Penalty::FirstDerivative | Penalty::SecondDerivative if self.non_negative => {
+                (lambda, solve_non_negative(&(ktk + lambda * ltl), &ktm, deconvolved))
+            }
+            Penalty::Zeroth | Penalty::FirstDerivative | Penalty::SecondDerivative => (lambda, deconvolved),
+        };
+        (deconvolved.data.into(), lambda)
+    }
+
+    /// Choose `lambda` by [`LambdaSelection`], returns it and solution for it.
+    ///
+    /// `K^T K` and `L^T L` are simultaneously diagonalized, so solution for each `lambda` is `O(len^2)`:
+    /// if `K^T K + L^T L = R R^T` and `R^-1 K^T K R^-T = Q diag(g) Q^T`,
+    /// then `K^T K + lambda L^T L = R Q diag(g + lambda (1 - g)) Q^T R^T`.
+    fn select_lambda(&self, k: &DMat, l: &DMat, ktk: &DMat, ltl: &DMat, ktm: &DVect, measured: &DVect) -> (float, DVect) {
+        let len: usize = ktm.len();
+        let measured_len: usize = measured.len();
+        let r: DMat = Self::cholesky_with_ridge(ktk + ltl).unpack();
+        let r_inv: DMat = r.solve_lower_triangular(&DMat::identity(len, len))
+            .expect("diagonal of Cholesky factor is positive");
+        let eigen = SymmetricEigen::new(&r_inv * ktk * r_inv.transpose());
+        let g: DVect = eigen.eigenvalues.map(|g| g.clamp(0., 1.));
+        // solution is `w * (z / (g + lambda (1 - g)))`:
+        let w: DMat = r_inv.transpose() * &eigen.eigenvectors;
+        let z: DVect = eigen.eigenvectors.transpose() * (&r_inv * ktm);
+        let solve = |lambda: float| -> DVect {
+            &w * z.zip_map(&g, |z_i, g_i| z_i / (g_i + lambda * (1. - g_i)))
+        };
+
+        // trace of `K^T K` is zero, if instrument is zero:
+        let lambda_scale: float = match ktk.trace() / ltl.trace() {
+            lambda_scale if lambda_scale.is_finite() && lambda_scale > 0. => lambda_scale,
+            _ => 1.,
+        };
+        let (decade_min, decade_max) = Self::LAMBDAS_DECADES;
+        let lambdas: Vec<float> = (0..=((decade_max - decade_min) as usize * Self::LAMBDAS_PER_DECADE))
+            .map(|i| lambda_scale * float::powf(10., decade_min as float + i as float / Self::LAMBDAS_PER_DECADE as float))
+            .collect();
+        let select_by_gcv = || -> Option<float> {
+            let gcv = |lambda: float| -> float {
+                let residue: float = (k * solve(lambda) - measured).norm_squared();
+                let influence_trace: float = g.iter().map(|&g_i| g_i / (g_i + lambda * (1. - g_i))).sum();
+                measured_len as float * residue / (measured_len as float - influence_trace).powi(2)
+            };
+            lambdas.iter()
+                .map(|&lambda| (lambda, gcv(lambda)))
+                .filter(|(_, gcv)| gcv.is_finite())
+                .min_by(|(_, gcv_a), (_, gcv_b)| gcv_a.total_cmp(gcv_b))
+                .map(|(lambda, _)| lambda)
+        };
+        let lambda: Option<float> = match self.lambda_selection {
+            LambdaSelection::Gcv => select_by_gcv(),
+            LambdaSelection::LCurve => {
+                // `log |K x - measured|` and `log |L x|`:
+                let (rhos, etas): (Vec<float>, Vec<float>) = lambdas.iter()
+                    .map(|&lambda| {
+                        let solution = solve(lambda);
+                        ((k * &solution - measured).norm().ln(), (l * &solution).norm().ln())
+                    })
+                    .unzip();
+                // curvature of L-curve, derivatives are by `log lambda`, so step cancels out:
+                (1..lambdas.len()-1)
+                    .map(|i| {
+                        let (rho_d1, eta_d1) = ((rhos[i+1] - rhos[i-1]) / 2., (etas[i+1] - etas[i-1]) / 2.);
+                        let (rho_d2, eta_d2) = (rhos[i+1] - 2.*rhos[i] + rhos[i-1], etas[i+1] - 2.*etas[i] + etas[i-1]);
+                        let curvature = (rho_d1 * eta_d2 - rho_d2 * eta_d1) / (rho_d1.powi(2) + eta_d1.powi(2)).powf(1.5);
+                        (lambdas[i], curvature)
+                    })
+                    .filter(|(_, curvature)| curvature.is_finite())
+                    .max_by(|(_, curvature_a), (_, curvature_b)| curvature_a.total_cmp(curvature_b))
+                    .map(|(lambda, _)| lambda)
+                    // e.g. if measured is zero, then `log |K x - measured|` is `-inf` for all `lambda`s:
+                    .or_else(select_by_gcv)
+            }
+        };
+        let lambda: float = lambda.unwrap_or(lambda_scale);
+        (lambda, solve(lambda))
+    }
+
+    /// Cholesky decomposition of symmetric positive semidefinite `m`.
+    ///
+    /// If `m` is singular (e.g. `K^T K + L^T L`, if instrument is zero), small ridge is added to it.
+    fn cholesky_with_ridge(m: DMat) -> Cholesky<float, Dyn> {
+        let len: usize = m.nrows();
+        let diagonal_mean: float = match m.trace() / len as float {
+            diagonal_mean if diagonal_mean > 0. => diagonal_mean,
+            _ => 1.,
+        };
+        m.clone().cholesky()
+            .or_else(|| (m + Self::RIDGE_RELATIVE * diagonal_mean * DMat::identity(len, len)).cholesky())
+            .expect("matrix with ridge is positive definite, if it's finite")
+    }
+
+    /// Minimize `|K x - measured|^2 + lambda_tv * sum |x[i+1] - x[i]|` by iteratively reweighted least squares,
+    /// starting from `deconvolved`, with `lambda_tv = lambda * s`, where `s` is RMS of differences in `deconvolved`,
+    /// so at start penalty is same as quadratic one.
+    ///
+    /// Returns `lambda_tv` and solution.
+    fn solve_total_variation(&self, d: &DMat, ktk: &DMat, ktm: &DVect, lambda: float, mut deconvolved: DVect) -> (float, DVect) {
+        const TOLERANCE: float = 1e-9;
+        let diffs_rms: float = (d * &deconvolved).norm() / (d.nrows() as float).sqrt();
+        let lambda_tv: float = lambda * diffs_rms;
+        // already flat, so there is nothing to reweight:
+        if diffs_rms == 0. { return (lambda_tv, deconvolved) }
+        // to avoid division by zero at flat regions:
+        let epsilon: float = 1e-3 * diffs_rms;
+        for _ in 0..Self::TOTAL_VARIATION_ITERATIONS_MAX {
+            // `|diff| ≈ diff^2 / |diff_prev|`:
+            let weights: DVect = (d * &deconvolved).map(|diff| 1. / (diff.powi(2) + epsilon.powi(2)).sqrt());
+            let q: DMat = ktk + lambda_tv / 2. * d.transpose() * DMat::from_diagonal(&weights) * d;
+            let deconvolved_new: DVect = if self.non_negative {
+                solve_non_negative(&q, ktm, deconvolved.clone())
+            } else {
+                Self::cholesky_with_ridge(q).solve(ktm)
+            };
+            let change_max: float = (&deconvolved_new - &deconvolved).amax();
+            deconvolved = deconvolved_new;
+            if change_max <= TOLERANCE * deconvolved.amax() { break }
+        }
+        (lambda_tv, deconvolved)
+    }
+}
+
+impl Function for Regularized {
+    const NAME: &'static str = "regularized";
+
+    const FORMAT_FOR_DESMOS: &'static str = unreachable!();
+    const FORMAT_FOR_ORIGIN: &'static str = unreachable!();
+
+    fn to_plottable_function(&self, _params: &Params, _significant_digits: u8, _format: &'static str) -> String {
+        unreachable!()
+    }
+}
+
+impl Load for Regularized {
+    const TOML_NAME: &'static str = stringify!(Regularized);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_optional_or_dy_sqr(toml_value, stacktrace)?,
+            penalty: Penalty::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            // optional, default is `false`
+            non_negative: toml_value
+                .get("non_negative")
+                .map(|_| toml_value.load_bool("non_negative", stacktrace))
+                .transpose()?
+                .unwrap_or(false),
+            lambda_selection: LambdaSelection::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            // size of `K` depends on measured, so `len` is set by `sized_to_measured`.
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Penalty {
+    /// `|x|^2`
+    Zeroth,
+    /// `sum (x[i+1] - x[i])^2`
+    FirstDerivative,
+    /// `sum (x[i+1] - 2 x[i] + x[i-1])^2`
+    SecondDerivative,
+    /// `sum |x[i+1] - x[i]|`, preserves steps.
+    TotalVariation,
+}
+
+impl Penalty {
+    /// Matrix `L` of quadratic penalty `|L x|^2` (for [`TotalVariation`](Penalty::TotalVariation) it's first derivative).
+    fn matrix(&self, len: usize) -> DMat {
+        let stencil: &[float] = match self {
+            Self::Zeroth => &[1.],
+            Self::FirstDerivative | Self::TotalVariation => &[-1., 1.],
+            Self::SecondDerivative => &[1., -2., 1.],
+        };
+        let rows: usize = len + 1 - stencil.len();
+        DMat::from_fn(rows, len, |i, j| {
+            match j.checked_sub(i) {
+                Some(si) if si < stencil.len() => stencil[si],
+                _ => 0.,
+            }
+        })
+    }
+}
+
+impl Load for Penalty {
+    const TOML_NAME: &'static str = "penalty";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let penalty_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match penalty_str {
+            "zeroth" => Self::Zeroth,
+            "first_derivative" => Self::FirstDerivative,
+            "second_derivative" => Self::SecondDerivative,
+            "total_variation" => Self::TotalVariation,
+            _ => return Err(stacktrace.err_unknown_type(penalty_str, ["zeroth", "first_derivative", "second_derivative", "total_variation"]))
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LambdaSelection {
+    /// Corner (max curvature) of `log |K x - measured|` vs `log |L x|`.
+    LCurve,
+    /// Generalized Cross-Validation: min of `n * |K x - measured|^2 / (n - trace(influence))^2`, where `n` is number of measured points.
+    Gcv,
+}
+
+impl Load for LambdaSelection {
+    const TOML_NAME: &'static str = "lambda_selection";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let lambda_selection_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match lambda_selection_str {
+            "l_curve" => Self::LCurve,
+            "gcv" => Self::Gcv,
+            _ => return Err(stacktrace.err_unknown_type(lambda_selection_str, ["l_curve", "gcv"]))
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod deconvolve {
+    use super::super::super::test_fixtures::{assert_points_close, points_deconvolved, points_instrument, points_measured};
+    use super::*;
+
+    fn regularized(penalty: Penalty, non_negative: bool, lambda_selection: LambdaSelection) -> Regularized {
+        Regularized {
+            diff_function_type: DiffFunction::DySqr,
+            penalty,
+            non_negative,
+            lambda_selection,
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        }
+    }
+
+    /// With deterministic "noise", so there is something to select `lambda` by.
+    fn points_measured_noisy() -> Vec<float> {
+        points_measured().iter().enumerate()
+            .map(|(i, p)| p + 0.01 * ((i * 37 % 17) as float / 8. - 1.))
+            .collect()
+    }
+
+    fn assert_recovers(regularized: Regularized, tolerance: float) {
+        let (points_actual, lambda) = regularized.deconvolve(&points_instrument(), &points_measured_noisy(), None, None);
+        assert!(lambda > 0., "{regularized:?}\nlambda = {lambda}");
+        assert_points_close(&points_deconvolved(), &points_actual, tolerance);
+    }
+
+    #[test]
+    fn second_derivative_gcv() {
+        assert_recovers(regularized(Penalty::SecondDerivative, false, LambdaSelection::Gcv), 0.1);
+    }
+
+    #[test]
+    fn first_derivative_l_curve() {
+        assert_recovers(regularized(Penalty::FirstDerivative, false, LambdaSelection::LCurve), 0.2);
+    }
+
+    #[test]
+    fn zeroth_gcv() {
+        assert_recovers(regularized(Penalty::Zeroth, false, LambdaSelection::Gcv), 0.2);
+    }
+
+    #[test]
+    fn total_variation_gcv() {
+        assert_recovers(regularized(Penalty::TotalVariation, false, LambdaSelection::Gcv), 0.2);
+    }
+
+    #[test]
+    fn non_negative() {
+        for penalty in [Penalty::Zeroth, Penalty::SecondDerivative, Penalty::TotalVariation] {
+            let (points_actual, _) = regularized(penalty, true, LambdaSelection::Gcv)
+                .deconvolve(&points_instrument(), &points_measured_noisy(), None, None);
+            assert!(points_actual.iter().all(|&p| p >= 0.), "{penalty:?}\nactual: {points_actual:?}");
+        }
+    }
+
+    #[test]
+    fn spike_excluded_or_weighted() {
+        let mut points_measured = points_measured_noisy();
+        points_measured[60] += 5.;
+        let regularized = regularized(Penalty::SecondDerivative, false, LambdaSelection::Gcv);
+        let fit_indices: Vec<usize> = (0..points_measured.len()).filter(|&i| i != 60).collect();
+        let weights = DVect::from_fn(points_measured.len(), |i, _| if i == 60 { 1e-2 } else { 1. });
+        for (weights, fit_indices) in [(None, Some(fit_indices.as_slice())), (Some(&weights), None)] {
+            let (points_actual, _) = regularized.deconvolve(&points_instrument(), &points_measured, weights, fit_indices);
+            assert_points_close(&points_deconvolved(), &points_actual, 0.1);
+        }
+    }
+
+    #[test]
+    fn zero_measured() {
+        for penalty in [Penalty::Zeroth, Penalty::SecondDerivative, Penalty::TotalVariation] {
+            for lambda_selection in [LambdaSelection::Gcv, LambdaSelection::LCurve] {
+                let (points_actual, lambda) = regularized(penalty, false, lambda_selection)
+                    .deconvolve(&points_instrument(), &[0.; 80], None, None);
+                assert!(lambda.is_finite(), "{penalty:?}, {lambda_selection:?}\nlambda = {lambda}");
+                assert!(points_actual.iter().all(|&p| p == 0.), "{penalty:?}, {lambda_selection:?}\nactual: {points_actual:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn zero_instrument() {
+        for penalty in [Penalty::Zeroth, Penalty::SecondDerivative, Penalty::TotalVariation] {
+            for non_negative in [false, true] {
+                let (points_actual, lambda) = regularized(penalty, non_negative, LambdaSelection::LCurve)
+                    .deconvolve(&[0.; 21], &points_measured_noisy(), None, None);
+                assert!(lambda.is_finite(), "{penalty:?}, non_negative = {non_negative}\nlambda = {lambda}");
+                assert!(points_actual.iter().all(|&p| p == 0.), "{penalty:?}, non_negative = {non_negative}\nactual: {points_actual:?}");
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod penalty_matrix {
+    use super::*;
+
+    #[test]
+    fn second_derivative() {
+        assert_eq!(
+            DMat::from_row_slice(2, 4, &[
+                1., -2., 1., 0.,
+                0., 1., -2., 1.,
+            ]),
+            Penalty::SecondDerivative.matrix(4)
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn total_variation_non_negative_l_curve() {
+        assert_eq!(
+            Regularized {
+                diff_function_type: DiffFunction::DySqr,
+                penalty: Penalty::TotalVariation,
+                non_negative: true,
+                lambda_selection: LambdaSelection::LCurve,
+                initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+            },
+            Regularized::load_from_self(
+                &r#"
+                    penalty = "total_variation"
+                    non_negative = true
+                    lambda_selection = "l_curve"
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::empty(),
+            ).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_lambda_selection() {
+        assert_eq!(
+            "`Regularized` -> `lambda_selection`: unkown type: `manual`, known types: [`l_curve`, `gcv`]",
+            Regularized::load_from_self(
+                &r#"
+                    penalty = "zeroth"
+                    lambda_selection = "manual"
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::new(Regularized::TOML_NAME),
+            ).unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Richardson–Lucy
+
+use toml::Value as TomlValue;
+
+use crate::{
+    config::ExtTomlValueLoadPrimitives,
+    diff_function::DiffFunction,
+    error::Result,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::DVect,
+        named_wrappers::{DeconvolvedV, Instrument, InstrumentRevV, Params},
+    },
+};
+
+use super::super::convolution::convolve_by_points_v;
+
+use super::{Function, ValueAndDomain, per_points::InitialValues_PerPoint};
+
+
+/// Iterative (expectation-maximization) deconvolution:
+/// `deconvolved *= instrument_rev ⊛ (measured / (instrument ⊛ deconvolved)) / (instrument_rev ⊛ 1)`.
+///
+/// Deconvolved stays non-negative, if it was at start, so negative measured points are clamped to zero.
+///
+/// Params are deconvolved points, as in [`PerPoint`](super::per_points::PerPoint), after last iteration,
+/// see [`is_fitted`](super::super::DeconvolutionVariant::is_fitted).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichardsonLucy {
+    /// Ignored by deconvolution, bc iterations always maximize Poisson likelihood.
+    pub diff_function_type: DiffFunction,
+    pub iterations_max: u64,
+    /// Stop if max change of deconvolved (relative to it's max) is less than this.
+    pub convergence_tolerance: Option<float>,
+    /// Measured points, which differ from convolved by less than `damping` sigmas (Poisson),
+    /// are considered to be noise and don't change deconvolved, so noise isn't amplified.
+    pub damping: Option<float>,
+    /// Values aren't used, bc iterations start from flat spectrum, only it's len (same as of measured points).
+    pub initial_vad: InitialValues_PerPoint<ValueAndDomain>,
+}
+
+impl RichardsonLucy {
+    /// Iterate from flat spectrum until [`iterations_max`](RichardsonLucy::iterations_max) or convergence,
+    /// `points_instrument` is centered, with odd len.
+    ///
+    /// Returns deconvolved points and number of iterations done.
+    pub fn deconvolve(&self, points_instrument: &[float], points_measured: &[float]) -> (Vec<float>, u64) {
+        let instrument_rev: InstrumentRevV = Instrument(points_instrument.to_vec()).into();
+        // convolution with reversed instrument is correlation with instrument:
+        let instrument: InstrumentRevV = InstrumentRevV(DVect::from_column_slice(points_instrument));
+        let measured: DVect = DVect::from_iterator(points_measured.len(), points_measured.iter().map(|&p| p.max(0.)));
+        let measured_len = measured.len();
+
+        // sum of instrument points, that contributes to each measured point, so edges are handled correctly:
+        let norm: DVect = convolve_by_points_v(&instrument, DeconvolvedV(DVect::repeat(measured_len, 1.))).0;
+        let norm_total: float = points_instrument.iter().sum();
+        if norm_total <= 0. { return (vec![0.; measured_len], 0) }
+        // flat start with same total intensity:
+        let mut deconvolved: DVect = DVect::repeat(measured_len, measured.sum() / norm_total / measured_len as float);
+
+        let mut iterations: u64 = 0;
+        while iterations < self.iterations_max {
+            iterations += 1;
+            let convolved: DVect = convolve_by_points_v(&instrument_rev, DeconvolvedV(deconvolved.clone())).0;
+            let ratio: DVect = measured.zip_map(&convolved, |measured_point, convolved_point| {
+                if convolved_point <= 0. { return 0. }
+                match self.damping {
+                    Some(damping) if (measured_point - convolved_point).abs() < damping * convolved_point.sqrt() => 1.,
+                    _ => measured_point / convolved_point,
+                }
+            });
+            let correction: DVect = convolve_by_points_v(&instrument, DeconvolvedV(ratio)).0;
+            let deconvolved_new: DVect = deconvolved.zip_zip_map(&correction, &norm, |deconvolved_point, correction_point, norm_point| {
+                if norm_point <= 0. { 0. } else { deconvolved_point * correction_point / norm_point }
+            });
+            let change_max: float = (&deconvolved_new - &deconvolved).amax();
+            deconvolved = deconvolved_new;
+            if let Some(convergence_tolerance) = self.convergence_tolerance {
+                if change_max <= convergence_tolerance * deconvolved.amax() { break }
+            }
+        }
+        (deconvolved.data.into(), iterations)
+    }
+}
+
+impl Function for RichardsonLucy {
+    const NAME: &'static str = "richardson_lucy";
+
+    const FORMAT_FOR_DESMOS: &'static str = unreachable!();
+    const FORMAT_FOR_ORIGIN: &'static str = unreachable!();
+
+    fn to_plottable_function(&self, _params: &Params, _significant_digits: u8, _format: &'static str) -> String {
+        unreachable!()
+    }
+}
+
+impl Load for RichardsonLucy {
+    const TOML_NAME: &'static str = stringify!(RichardsonLucy);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_optional_or_dy_sqr(toml_value, stacktrace)?,
+            iterations_max: toml_value.load_u64("iterations_max", stacktrace)?,
+            convergence_tolerance: toml_value.load_optional_non_negative_float("convergence_tolerance", stacktrace)?,
+            damping: toml_value.load_optional_non_negative_float("damping", stacktrace)?,
+            // flat start is calculated from measured, so only `len` is needed, it's set by `sized_to_measured`.
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod deconvolve {
+    use super::super::super::test_fixtures::{assert_points_close, points_deconvolved, points_instrument, points_measured};
+    use super::*;
+
+    fn richardson_lucy(iterations_max: u64, convergence_tolerance: Option<float>, damping: Option<float>) -> RichardsonLucy {
+        RichardsonLucy {
+            diff_function_type: DiffFunction::DySqr,
+            iterations_max,
+            convergence_tolerance,
+            damping,
+            initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+        }
+    }
+
+    #[test]
+    fn recovers_deconvolved() {
+        let (points_actual, _) = richardson_lucy(2_000, None, None)
+            .deconvolve(&points_instrument(), &points_measured());
+        assert_points_close(&points_deconvolved(), &points_actual, 1e-2);
+    }
+
+    #[test]
+    fn non_negative() {
+        let points_measured: Vec<float> = points_measured().iter().enumerate()
+            .map(|(i, p)| p + if i % 2 == 0 { 0.05 } else { -0.05 })
+            .collect();
+        let (points_actual, _) = richardson_lucy(1_000, None, None)
+            .deconvolve(&points_instrument(), &points_measured);
+        assert!(points_actual.iter().all(|&p| p >= 0.), "actual: {points_actual:?}");
+    }
+
+    #[test]
+    fn stops_at_convergence() {
+        let (_, iterations) = richardson_lucy(1_000_000, Some(1e-3), None)
+            .deconvolve(&points_instrument(), &points_measured());
+        assert!(iterations < 1_000_000, "iterations = {iterations}");
+        let (_, iterations) = richardson_lucy(42, Some(0.), None)
+            .deconvolve(&points_instrument(), &points_measured());
+        assert_eq!(42, iterations);
+    }
+
+    #[test]
+    fn damping_smooth() {
+        let points_measured: Vec<float> = points_measured().iter().enumerate()
+            .map(|(i, p)| p + if i % 2 == 0 { 0.01 } else { -0.01 })
+            .collect();
+        let roughness = |points: &[float]| -> float {
+            points.array_windows().map(|[a, b]| (b - a).abs()).sum()
+        };
+        let (points_undamped, _) = richardson_lucy(1_000, None, None)
+            .deconvolve(&points_instrument(), &points_measured);
+        let (points_damped, _) = richardson_lucy(1_000, None, Some(1.))
+            .deconvolve(&points_instrument(), &points_measured);
+        assert!(roughness(&points_damped) < roughness(&points_undamped));
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn with_convergence_tolerance_and_damping() {
+        assert_eq!(
+            RichardsonLucy {
+                diff_function_type: DiffFunction::DySqr,
+                iterations_max: 1000,
+                convergence_tolerance: Some(1e-6),
+                damping: Some(1.),
+                initial_vad: InitialValues_PerPoint::new(0, ValueAndDomain::free(0.)),
+            },
+            RichardsonLucy::load_from_self(
+                &r#"
+                    iterations_max = 1000
+                    convergence_tolerance = 1e-6
+                    damping = 1.0
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::empty(),
+            ).unwrap()
+        );
+    }
+
+    #[test]
+    fn negative_damping() {
+        assert_eq!(
+            "`RichardsonLucy` -> `damping`: must be non-negative",
+            RichardsonLucy::load_from_self(
+                &r#"
+                    iterations_max = 1000
+                    damping = -1.0
+                "#.parse::<TomlValue>().unwrap(),
+                &Stacktrace::new(RichardsonLucy::TOML_NAME),
+            ).unwrap_err().to_string()
+        );
+    }
+}
@@ -7,6 +7,7 @@ use toml::Value as TomlValue;
 use crate::{
     aliases_method_to_function::exp,
     diff_function::DiffFunction,
+    error::Result,
     extensions::ToStringWithSignificantDigits,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
@@ -53,12 +54,12 @@ impl Function for SatExp_DecExpPlusConst {
 
 impl Load for SatExp_DecExpPlusConst {
     const TOML_NAME: &'static str = stringify!(SatExp_DecExpPlusConst);
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            initial_vads: InitialValues_SatExp_DecExpPlusConst::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            allow_tb_less_than_ta: AllowTbLessThanTa::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_SatExp_DecExpPlusConst::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            allow_tb_less_than_ta: AllowTbLessThanTa::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
     }
 }
 
@@ -68,12 +69,12 @@ pub struct AllowTbLessThanTa(bool);
 
 impl Load for AllowTbLessThanTa {
     const TOML_NAME: &'static str = "allow_tb_less_than_ta";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self (
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self (
             toml_value
                 .as_bool()
-                .unwrap_or_else(|| stacktrace.panic_cant_parse_as("boolean"))
-        )
+                .ok_or_else(|| stacktrace.err_cant_parse_as("boolean"))?
+        ))
     }
 }
 
@@ -149,29 +150,30 @@ impl From<InitialValues_SatExp_DecExpPlusConst<ValueAndDomain>> for InitialValue
 
 impl Load for InitialValues_SatExp_DecExpPlusConst<ValueAndDomain> {
     const TOML_NAME: &'static str = "initial_values";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
         let str = toml_value
             .as_str()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("string"));
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
         let ivs: HashMap<String, ValueAndDomain> = str
             .trim_matches(|c: char| c.is_whitespace() || c == ',')
             .split(',')
             // TODO: add index to stacktrace
             .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
-            .collect();
+            .collect::<Result<_>>()?;
         // TODO: assert `ivs.len` == Self::LEN
-        let try_get = |name: &'static str| -> ValueAndDomain {
-            *ivs
+        let try_get = |name: &'static str| -> Result<ValueAndDomain> {
+            ivs
                 .get(name)
-                .unwrap_or_else(|| stacktrace.pushed(name).panic_not_found())
+                .copied()
+                .ok_or_else(|| stacktrace.pushed(name).err_not_found())
         };
-        Self {
-            amplitude: try_get("a"),
-            shift: try_get("s"),
-            height: try_get("h"),
-            tau_a: try_get("ta"),
-            tau_b: try_get("tb"),
-        }
+        Ok(Self {
+            amplitude: try_get("a")?,
+            shift: try_get("s")?,
+            height: try_get("h")?,
+            tau_a: try_get("ta")?,
+            tau_b: try_get("tb")?,
+        })
     }
 }
 
@@ -0,0 +1,213 @@
+//! SatExp_StretchedExp
+
+use std::collections::HashMap;
+
+use toml::Value as TomlValue;
+
+use crate::{
+    aliases_method_to_function::exp,
+    diff_function::DiffFunction,
+    error::Result,
+    extensions::ToStringWithSignificantDigits,
+    load::{LoadAutoImplFns, Load},
+    stacktrace::Stacktrace,
+    types::{float::float, linalg::DVect, named_wrappers::{DeconvolvedV, Params, ParamsG, ParamsV}},
+    utils_io::format_by_dollar_str,
+};
+
+use super::super::initial_values::{InitialValuesGeneric, InitialValuesVAD};
+
+use super::{Function, ValueAndDomain, i_to_x::i_to_x};
+
+
+// a * (1-exp(-(x-s)/ta)) * exp(-((x-s)/tb)^beta)
+/// (1-exp(-(x-s)/ta)) * exp(-((x-s)/tb)^beta), stretched (Kohlrausch–Williams–Watts) decay with rise.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatExp_StretchedExp {
+    pub diff_function_type: DiffFunction,
+    pub initial_vads: InitialValues_SatExp_StretchedExp<ValueAndDomain>,
+}
+
+impl Function for SatExp_StretchedExp {
+    const NAME: &'static str = "saturated stretched exponential";
+
+    // power of negative is undefined, so `x-s` is "cut" at zero (value is zero there anyway).
+    const FORMAT_FOR_DESMOS: &'static str = r"max(0,$a\left(1-\exp\left(-\frac{x$pm$s}{$ta}\right)\right)\left(\exp\left(-\left(\frac{\max\left(0,x$pm$s\right)}{$tb}\right)^{$beta}\right)\right))";
+    const FORMAT_FOR_ORIGIN: &'static str = r"max(0,$a*(1-exp(-(x$pm$s)/($ta)))*(exp(-(max(0,x$pm$s)/($tb))^($beta))))";
+
+    fn to_plottable_function(&self, params: &Params, significant_digits: u8, format: &'static str) -> String {
+        let params = InitialValues_SatExp_StretchedExp::from_vec(params);
+        let sd = significant_digits;
+        format_by_dollar_str(
+            format,
+            vec![
+                ("a", &params.amplitude.to_string_with_significant_digits(sd)),
+                ("pm", if !params.shift.is_sign_positive() { "+" } else { "-" }),
+                ("s", &params.shift.abs().to_string_with_significant_digits(sd)),
+                ("ta", &params.tau_a.to_string_with_significant_digits(sd)),
+                ("tb", &params.tau_b.to_string_with_significant_digits(sd)),
+                ("beta", &params.beta.to_string_with_significant_digits(sd)),
+            ]
+        )
+    }
+}
+
+impl Load for SatExp_StretchedExp {
+    const TOML_NAME: &'static str = stringify!(SatExp_StretchedExp);
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_SatExp_StretchedExp::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
+    }
+}
+
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InitialValues_SatExp_StretchedExp<T> {
+    pub amplitude: T,
+    pub shift: T,
+    pub tau_a: T,
+    pub tau_b: T,
+    /// Stretching exponent, `0 < beta <= 1` usually, `beta = 1` is usual exponent.
+    pub beta: T,
+}
+
+impl InitialValues_SatExp_StretchedExp<float> {
+    fn from_vec_vf(params: &ParamsV) -> Self {
+        match params.0.as_slice()[..] {
+            [      amplitude, shift, tau_a, tau_b, beta ] =>
+            Self { amplitude, shift, tau_a, tau_b, beta },
+            _ => unreachable!()
+        }
+    }
+}
+
+impl<T: Copy> InitialValuesGeneric<T> for InitialValues_SatExp_StretchedExp<T> {
+    const LEN: usize = 5;
+
+    fn from_vec(params: &ParamsG<T>) -> Self {
+        match params.0[..] {
+            [amplitude, shift, tau_a, tau_b, beta] => Self { amplitude, shift, tau_a, tau_b, beta },
+            _ => unreachable!()
+        }
+    }
+
+    fn to_vec(&self) -> ParamsG<T> {
+        let Self { amplitude, shift, tau_a, tau_b, beta } = *self;
+        ParamsG::<T>(vec![amplitude, shift, tau_a, tau_b, beta])
+    }
+
+    fn params_to_points_v(&self, params: &ParamsV, points_len: usize, x_start_end: (float, float)) -> DeconvolvedV {
+        type SelfF = InitialValues_SatExp_StretchedExp<float>;
+        let SelfF { amplitude, shift, tau_a, tau_b, beta } = SelfF::from_vec_vf(params);
+        let mut points = DVect::zeros(points_len);
+        for i in 0..points_len {
+            let x: float = i_to_x(i, points_len, x_start_end);
+            let x_m_shift = x - shift;
+            if x_m_shift <= 0. { continue }
+            let y = amplitude * (1. - exp(-x_m_shift/tau_a)) * exp(-(x_m_shift/tau_b).powf(beta));
+            // `NaN` (e.g. if `tau_b < 0`) also becomes zero here:
+            let y = y.max(0.);
+            points[i] = y;
+        }
+        DeconvolvedV(points)
+    }
+}
+
+impl InitialValuesVAD for InitialValues_SatExp_StretchedExp<ValueAndDomain> {}
+
+impl From<InitialValues_SatExp_StretchedExp<ValueAndDomain>> for InitialValues_SatExp_StretchedExp<float> {
+    fn from(value: InitialValues_SatExp_StretchedExp<ValueAndDomain>) -> Self {
+        Self::from_vec(&ParamsG::<float>(value.to_vec().0.iter().map(|v| v.value).collect::<Vec<float>>()))
+    }
+}
+
+
+impl Load for InitialValues_SatExp_StretchedExp<ValueAndDomain> {
+    const TOML_NAME: &'static str = "initial_values";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        let ivs: HashMap<String, ValueAndDomain> = str
+            .trim_matches(|c: char| c.is_whitespace() || c == ',')
+            .split(',')
+            // TODO: add index to stacktrace
+            .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
+            .collect::<Result<_>>()?;
+        let try_get = |name: &'static str| -> Result<ValueAndDomain> {
+            ivs
+                .get(name)
+                .copied()
+                .ok_or_else(|| stacktrace.pushed(name).err_not_found())
+        };
+        Ok(Self {
+            amplitude: try_get("a")?,
+            shift: try_get("s")?,
+            tau_a: try_get("ta")?,
+            tau_b: try_get("tb")?,
+            beta: try_get("beta")?,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod params_to_points_v {
+    use super::*;
+
+    #[test]
+    fn beta_1_is_sat_exp_dec_exp() {
+        let initial_vads = InitialValues_SatExp_StretchedExp::from_vec(&ParamsG(vec![ValueAndDomain::free(0.); 5]));
+        let params = ParamsV(DVect::from_vec(vec![2., 1., 0.5, 3., 1.]));
+        let points_expected = DVect::from_iterator(6, (0..6).map(|i| {
+            let x_m_shift = i as float - 1.;
+            if x_m_shift <= 0. { 0. } else { 2. * (1. - exp(-x_m_shift/0.5)) * exp(-x_m_shift/3.) }
+        }));
+        let points_actual = initial_vads.params_to_points_v(&params, 6, (0., 5.)).0;
+        assert!((points_expected.clone() - points_actual.clone()).abs().max() < 1e-12, "expected: {points_expected}, actual: {points_actual}");
+    }
+
+    #[test]
+    fn stretched() {
+        let initial_vads = InitialValues_SatExp_StretchedExp::from_vec(&ParamsG(vec![ValueAndDomain::free(0.); 5]));
+        // rise is almost instant, so at `x-s = tb*4` value is `exp(-4^0.5) = exp(-2)`:
+        let params = ParamsV(DVect::from_vec(vec![1., 0., 1e-6, 0.25, 0.5]));
+        let points_actual = initial_vads.params_to_points_v(&params, 5, (-1., 1.)).0;
+        assert_eq!(0., points_actual[0]);
+        assert!((exp(-2.) - points_actual[4]).abs() < 1e-12, "actual: {points_actual}");
+    }
+}
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn all() {
+        let toml_value: TomlValue = toml::toml! { initial_values = "a=1, s==-2, ta=0.1, tb=10>0, 0 < beta = 0.6 < 1" }.into();
+        assert_eq!(
+            InitialValues_SatExp_StretchedExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::fixed(-2.),
+                tau_a: ValueAndDomain::free(0.1),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+                beta: ValueAndDomain::range_closed(0.6, (0., 1.)),
+            },
+            InitialValues_SatExp_StretchedExp::load_from_parent_handle_stacktrace(&toml_value, &Stacktrace::new("SatExp_StretchedExp")).unwrap(),
+        );
+    }
+
+    #[test]
+    fn missing_beta() {
+        let toml_value: TomlValue = toml::toml! { initial_values = "a=1, s=-2, ta=0.1, tb=10" }.into();
+        assert_eq!(
+            "`SatExp_StretchedExp` -> `initial_values` -> `beta`: not found",
+            InitialValues_SatExp_StretchedExp::load_from_parent_handle_stacktrace(&toml_value, &Stacktrace::new("SatExp_StretchedExp")).unwrap_err().to_string(),
+        );
+    }
+}
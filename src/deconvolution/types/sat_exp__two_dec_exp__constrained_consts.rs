@@ -7,6 +7,7 @@ use toml::Value as TomlValue;
 use crate::{
     aliases_method_to_function::exp,
     diff_function::DiffFunction,
+    error::Result,
     extensions::ToStringWithSignificantDigits,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
@@ -55,11 +56,11 @@ impl Function for SatExp_TwoDecExp_ConstrainedConsts {
 
 impl Load for SatExp_TwoDecExp_ConstrainedConsts {
     const TOML_NAME: &'static str = stringify!(SatExp_TwoDecExp_ConstrainedConsts);
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            initial_vads: InitialValues_SatExp_TwoDecExp_ConstrainedConsts::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_SatExp_TwoDecExp_ConstrainedConsts::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
     }
 }
 
@@ -128,30 +129,31 @@ impl From<InitialValues_SatExp_TwoDecExp_ConstrainedConsts<ValueAndDomain>> for
 
 impl Load for InitialValues_SatExp_TwoDecExp_ConstrainedConsts<ValueAndDomain> {
     const TOML_NAME: &'static str = "initial_values";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
         let str = toml_value
             .as_str()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("string"));
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
         let ivs: HashMap<String, ValueAndDomain> = str
             .trim_matches(|c: char| c.is_whitespace() || c == ',')
             .split(',')
             // TODO: add index to stacktrace
             .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
-            .collect();
+            .collect::<Result<_>>()?;
         // TODO: assert `ivs.len` == Self::LEN
-        let try_get = |name: &'static str| -> ValueAndDomain {
-            *ivs
+        let try_get = |name: &'static str| -> Result<ValueAndDomain> {
+            ivs
                 .get(name)
-                .unwrap_or_else(|| stacktrace.pushed(name).panic_not_found())
+                .copied()
+                .ok_or_else(|| stacktrace.pushed(name).err_not_found())
         };
-        Self {
-            amplitude_a: try_get("a"),
-            amplitude_b: try_get("b"),
-            shift: try_get("s"),
-            tau_a: try_get("ta"),
-            tau_b: try_get("tb"),
-            tau_c: try_get("tc"),
-        }
+        Ok(Self {
+            amplitude_a: try_get("a")?,
+            amplitude_b: try_get("b")?,
+            shift: try_get("s")?,
+            tau_a: try_get("ta")?,
+            tau_b: try_get("tb")?,
+            tau_c: try_get("tc")?,
+        })
     }
 }
 
@@ -7,6 +7,7 @@ use toml::Value as TomlValue;
 use crate::{
     aliases_method_to_function::exp,
     diff_function::DiffFunction,
+    error::Result,
     extensions::ToStringWithSignificantDigits,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
@@ -65,11 +66,11 @@ impl Function for Two_SatExp_DecExp {
 
 impl Load for Two_SatExp_DecExp {
     const TOML_NAME: &'static str = stringify!(Two_SatExp_DecExp);
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        Self {
-            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-            initial_vads: InitialValues_Two_SatExp_DecExp::load_from_parent_handle_stacktrace(toml_value, stacktrace),
-        }
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            diff_function_type: DiffFunction::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+            initial_vads: InitialValues_Two_SatExp_DecExp::load_from_parent_handle_stacktrace(toml_value, stacktrace)?,
+        })
     }
 }
 
@@ -160,32 +161,33 @@ impl From<InitialValues_Two_SatExp_DecExp<ValueAndDomain>> for InitialValues_Two
 
 impl Load for InitialValues_Two_SatExp_DecExp<ValueAndDomain> {
     const TOML_NAME: &'static str = "initial_values";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
         let str = toml_value
             .as_str()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("string"));
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
         let ivs: HashMap<String, ValueAndDomain> = str
             .trim_matches(|c: char| c.is_whitespace() || c == ',')
             .split(',')
             // TODO: add index to stacktrace
             .map(|part| ValueAndDomain::load_from_str(part, stacktrace))
-            .collect();
+            .collect::<Result<_>>()?;
         // TODO: assert `ivs.len` == Self::LEN
-        let try_get = |name: &'static str| -> ValueAndDomain {
-            *ivs
+        let try_get = |name: &'static str| -> Result<ValueAndDomain> {
+            ivs
                 .get(name)
-                .unwrap_or_else(|| stacktrace.pushed(name).panic_not_found())
+                .copied()
+                .ok_or_else(|| stacktrace.pushed(name).err_not_found())
         };
-        Self {
-            amplitude_1: try_get("a1"),
-            shift_1: try_get("s1"),
-            tau_a1: try_get("ta1"),
-            tau_b1: try_get("tb1"),
-            amplitude_2: try_get("a2"),
-            shift_2: try_get("s2"),
-            tau_a2: try_get("ta2"),
-            tau_b2: try_get("tb2"),
-        }
+        Ok(Self {
+            amplitude_1: try_get("a1")?,
+            shift_1: try_get("s1")?,
+            tau_a1: try_get("ta1")?,
+            tau_b1: try_get("tb1")?,
+            amplitude_2: try_get("a2")?,
+            shift_2: try_get("s2")?,
+            tau_a2: try_get("ta2")?,
+            tau_b2: try_get("tb2")?,
+        })
     }
 }
 
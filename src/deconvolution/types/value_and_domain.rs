@@ -3,6 +3,7 @@
 use rand::{Rng, rngs::ThreadRng};
 
 use crate::{
+    error::Result,
     extensions::SplitAndKeep,
     stacktrace::Stacktrace,
     types::float::float,
@@ -85,6 +86,43 @@ impl ValueAndDomain {
         }
     }
 
+    pub fn is_fixed(&self) -> bool {
+        self.domain == ValueDomain::Fixed
+    }
+
+    /// Domain name, same as constructor name: `free`, `fixed`, `range_with_min`, `range_with_max` or `range_closed`.
+    pub fn get_domain_name(&self) -> &'static str {
+        match self.domain {
+            ValueDomain::Free => "free",
+            ValueDomain::Fixed => "fixed",
+            ValueDomain::RangeWithMin(..) => "range_with_min",
+            ValueDomain::RangeWithMax(..) => "range_with_max",
+            ValueDomain::RangeClosed(..) => "range_closed",
+        }
+    }
+
+    /// Domain bounds: `(min, max)`, `None` if there is no bound.
+    pub fn get_domain_min_max(&self) -> (Option<float>, Option<float>) {
+        match self.domain {
+            ValueDomain::Free => (None, None),
+            ValueDomain::Fixed => (Some(self.value), Some(self.value)),
+            ValueDomain::RangeWithMin(min) => (Some(min), None),
+            ValueDomain::RangeWithMax(max) => (None, Some(max)),
+            ValueDomain::RangeClosed(min, max) => (Some(min), Some(max)),
+        }
+    }
+
+    /// Closest to `value` value, that is in domain.
+    pub fn clamp(&self, value: float) -> float {
+        match self.domain {
+            ValueDomain::Free => value,
+            ValueDomain::Fixed => self.value,
+            ValueDomain::RangeWithMin(min) => value.max(min),
+            ValueDomain::RangeWithMax(max) => value.min(max),
+            ValueDomain::RangeClosed(min, max) => value.clamp(min, max),
+        }
+    }
+
     // pub fn get_randomized(&self, initial_values_random_scale: float) -> float {
     //     self.get_randomized_with_rng(initial_values_random_scale, &mut thread_rng())
     // }
@@ -108,7 +146,7 @@ impl ValueAndDomain {
         }
     }
 
-    pub fn load_from_str(str: &str, stacktrace: &Stacktrace) -> (String, Self) {
+    pub fn load_from_str(str: &str, stacktrace: &Stacktrace) -> Result<(String, Self)> {
         let by_eq = |c: char| c == '=';
         let str = str.trim();
         let parts = str.split_and_keep(|c| c=='<' || c=='>');
@@ -123,38 +161,38 @@ impl ValueAndDomain {
             [v] => match v.split_and_keep(by_eq).as_slice() {
                 [name, "=", "=", num] => (name, num, ValueDomainStr::Fixed),
                 [name, "=", num] => (name, num, ValueDomainStr::Free),
-                _ => stacktrace.pushed("{var}").panic_cant_parse_as(r#""{var_name} = {var_value}" or "{var_name} == {var_value}""#)
+                _ => return Err(stacktrace.pushed("{var}").err_cant_parse_as(r#""{var_name} = {var_value}" or "{var_name} == {var_value}""#))
             },
             [v, "<", max] => match v.split_and_keep(by_eq).as_slice() {
                 [name, "=", num] => (name, num, ValueDomainStr::RangeWithMax(max)),
-                _ => stacktrace.pushed("{var}").panic_cant_parse_as(r#""{var_name} < {var_value_max}""#)
+                _ => return Err(stacktrace.pushed("{var}").err_cant_parse_as(r#""{var_name} < {var_value_max}""#))
             }
             [v, ">", min] => match v.split_and_keep(by_eq).as_slice() {
                 [name, "=", num] => (name, num, ValueDomainStr::RangeWithMin(min)),
-                _ => stacktrace.pushed("{var}").panic_cant_parse_as(r#""{var_name} > {var_value_min}""#)
+                _ => return Err(stacktrace.pushed("{var}").err_cant_parse_as(r#""{var_name} > {var_value_min}""#))
             }
             [min, "<", v, "<", max] => match v.split_and_keep(by_eq).as_slice() {
                 [name, "=", num] => (name, num, ValueDomainStr::RangeClosed(min, max)),
-                _ => stacktrace.pushed("{var}").panic_cant_parse_as(r#""{var_value_min} < {var_value} < {var_value_max}""#)
+                _ => return Err(stacktrace.pushed("{var}").err_cant_parse_as(r#""{var_value_min} < {var_value} < {var_value_max}""#))
             }
-            _ => stacktrace.panic_cant_parse_as(r#""{var_free}" or "{var_fixed}" or "{var} < {var_value_max}" or "{var} > {var_value_min}" or "{var_value_min} < {var} < {var_value_max}""#)
+            _ => return Err(stacktrace.err_cant_parse_as(r#""{var_free}" or "{var_fixed}" or "{var} < {var_value_max}" or "{var} > {var_value_min}" or "{var_value_min} < {var} < {var_value_max}""#))
         };
-        let parse_float = |value_str: &str, value_name: &'static str| -> float {
+        let parse_float = |value_str: &str, value_name: &'static str| -> Result<float> {
             let stacktrace = stacktrace.pushed(value_name);
             value_str
                 .trim()
                 .parse::<float>()
-                .unwrap_or_else(|_| stacktrace.panic_cant_parse_as("float"))
+                .map_err(|_| stacktrace.err_cant_parse_as("float"))
         };
-        let value: float = parse_float(value_str, "value");
+        let value: float = parse_float(value_str, "value")?;
         let domain: ValueDomain = match domain_str {
             ValueDomainStr::Free => ValueDomain::Free,
             ValueDomainStr::Fixed => ValueDomain::Fixed,
-            ValueDomainStr::RangeWithMax(max) => ValueDomain::RangeWithMax(parse_float(max, "max")),
-            ValueDomainStr::RangeWithMin(min) => ValueDomain::RangeWithMin(parse_float(min, "min")),
-            ValueDomainStr::RangeClosed(min, max) => ValueDomain::RangeClosed(parse_float(min, "min"), parse_float(max, "max")),
+            ValueDomainStr::RangeWithMax(max) => ValueDomain::RangeWithMax(parse_float(max, "max")?),
+            ValueDomainStr::RangeWithMin(min) => ValueDomain::RangeWithMin(parse_float(min, "min")?),
+            ValueDomainStr::RangeClosed(min, max) => ValueDomain::RangeClosed(parse_float(min, "min")?, parse_float(max, "max")?),
         };
-        (name.trim().to_string(), Self { value, domain })
+        Ok((name.trim().to_string(), Self { value, domain }))
     }
 }
 
@@ -6,9 +6,11 @@ use toml::Value as TomlValue;
 
 use crate::{
     antispikes::Antispikes,
-    load::Load,
+    config::ExtTomlValueLoadPrimitives,
+    error::Result,
+    load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
-    types::{float::float, linalg::DVect},
+    types::{float::{consts, float}, linalg::DVect},
 };
 
 
@@ -19,10 +21,35 @@ pub enum DiffFunction {
     DyAbs,
     DySqrPerEl,
     DyAbsPerEl,
-    LeastDist,
+    /// Orthogonal distance from measured points to convolved curve, see [`calc_least_dist`].
+    LeastDist { x_scale: float, y_scale: float },
+    /// Poisson maximum likelihood, for spectra in counts, see [`calc_poisson_deviance`].
+    PoissonMLE,
+    /// Huber loss: quadratic for small residuals, linear for big ones.
+    Huber(RobustScale),
+    /// Cauchy (Lorentzian) loss: logarithmic for big residuals.
+    Cauchy(RobustScale),
+    /// Tukey biweight loss: constant for residuals bigger than scale, so they are ignored.
+    Tukey(RobustScale),
 }
 
 impl DiffFunction {
+    /// Loads from `parent`, or [`DySqr`](DiffFunction::DySqr) if it isn't there.
+    pub fn load_optional_or_dy_sqr(parent: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(
+            parent
+                .get(Self::TOML_NAME)
+                .map(|diff_function_type| Self::load_from_self_handle_stacktrace(diff_function_type, stacktrace))
+                .transpose()?
+                .unwrap_or(Self::DySqr)
+        )
+    }
+
+    /// Is minimum of it same as minimum of sum of squared residuals.
+    pub const fn is_least_squares(&self) -> bool {
+        matches!(self, Self::DySqr | Self::DySqrPerEl)
+    }
+
     // TODO(optimize)?
     pub fn calc_diff(&self, points_1: &Vec<float>, points_2: &Vec<float>) -> float {
         assert_eq!(points_1.len(), points_2.len());
@@ -47,7 +74,15 @@ impl DiffFunction {
             }
             Self::DySqrPerEl => Self::DySqr.calc_diff(points_1, points_2) / (points_1.len() as float),
             Self::DyAbsPerEl => Self::DyAbs.calc_diff(points_1, points_2) / (points_1.len() as float),
-            Self::LeastDist => { unimplemented!() }
+            Self::LeastDist { x_scale, y_scale } => calc_least_dist(points_1, points_2, *x_scale, *y_scale),
+            Self::PoissonMLE => {
+                points_1.iter().zip(points_2)
+                    .map(|(&measured, &model)| calc_poisson_deviance(measured, model))
+                    .sum()
+            }
+            Self::Huber(..) | Self::Cauchy(..) | Self::Tukey(..) => {
+                self.calc_robust_loss(points_1.iter().zip(points_2).map(|(point_1, point_2)| point_2 - point_1).collect())
+            }
         }
     }
 
@@ -68,10 +103,86 @@ impl DiffFunction {
             }
             Self::DySqrPerEl => Self::DySqr.calc_diff_v(points_1, points_2) / (points_1.len() as float),
             Self::DyAbsPerEl => Self::DyAbs.calc_diff_v(points_1, points_2) / (points_1.len() as float),
-            Self::LeastDist => { unimplemented!() }
+            Self::LeastDist { x_scale, y_scale } => calc_least_dist(points_1.as_slice(), points_2.as_slice(), *x_scale, *y_scale),
+            Self::PoissonMLE => {
+                points_1.iter().zip(points_2)
+                    .map(|(&measured, &model)| calc_poisson_deviance(measured, model))
+                    .sum()
+            }
+            Self::Huber(..) | Self::Cauchy(..) | Self::Tukey(..) => {
+                self.calc_robust_loss((points_2 - points_1).data.into())
+            }
+        }
+    }
+
+    /// Combine residues of several spectra into one, used in global fit.
+    ///
+    /// For root of sum of squares it's same as if residue was calculated for all points at once,
+    /// for others it's sum of residues.
+    pub fn combine_residues(&self, residues: impl IntoIterator<Item=float>) -> float {
+        match self {
+            Self::DySqr | Self::LeastDist { .. } => residues.into_iter().map(|residue| residue.powi(2)).sum::<float>().sqrt(),
+            Self::DyAbs
+            | Self::DySqrPerEl
+            | Self::DyAbsPerEl
+            | Self::PoissonMLE
+            | Self::Huber(..)
+            | Self::Cauchy(..)
+            | Self::Tukey(..)
+            => residues.into_iter().sum(),
+        }
+    }
+
+    /// Same diff function, but if it's robust loss with [`RobustScale::Mad`],
+    /// scale is estimated from `residuals` once and then is [`RobustScale::Fixed`].
+    ///
+    /// Scale estimated at each call would shrink together with residuals,
+    /// so fit would prefer params, at which most of residuals are small, instead of minimum of loss.
+    pub fn with_robust_scale_estimated(&self, residuals: &[float]) -> Self {
+        match *self {
+            Self::Huber(RobustScale::Mad) => Self::Huber(RobustScale::Fixed(self.calc_robust_scale_by_mad(residuals))),
+            Self::Cauchy(RobustScale::Mad) => Self::Cauchy(RobustScale::Fixed(self.calc_robust_scale_by_mad(residuals))),
+            Self::Tukey(RobustScale::Mad) => Self::Tukey(RobustScale::Fixed(self.calc_robust_scale_by_mad(residuals))),
+            _ => *self
         }
     }
 
+    /// `k * sigma`, where `k` is tuning constant of robust loss and `sigma` is estimated by [`calc_sigma_by_mad`].
+    ///
+    /// If all residuals are same (e.g. initial params fit perfectly), `sigma` can't be estimated, so it's `1`.
+    fn calc_robust_scale_by_mad(&self, residuals: &[float]) -> float {
+        /// Tuning constants (in units of sigma) for 95% efficiency at normal distribution.
+        const HUBER_K: float = 1.345;
+        const CAUCHY_K: float = 2.385;
+        const TUKEY_K: float = 4.685;
+        let k: float = match self {
+            Self::Huber(..) => HUBER_K,
+            Self::Cauchy(..) => CAUCHY_K,
+            Self::Tukey(..) => TUKEY_K,
+            _ => unreachable!()
+        };
+        let sigma = calc_sigma_by_mad(residuals);
+        k * if sigma > 0. { sigma } else { 1. }
+    }
+
+    /// Sum of `scale^2 * rho(residual / scale)` for robust losses.
+    ///
+    /// If scale is [`RobustScale::Mad`], it's estimated from `residuals`,
+    /// so for fit it should be estimated once before, see [`with_robust_scale_estimated`](DiffFunction::with_robust_scale_estimated).
+    fn calc_robust_loss(&self, residuals: Vec<float>) -> float {
+        let (robust_scale, rho): (RobustScale, fn(float) -> float) = match *self {
+            Self::Huber(robust_scale) => (robust_scale, |u| if u.abs() <= 1. { u.powi(2) / 2. } else { u.abs() - 0.5 }),
+            Self::Cauchy(robust_scale) => (robust_scale, |u| (1. + u.powi(2)).ln() / 2.),
+            Self::Tukey(robust_scale) => (robust_scale, |u| if u.abs() <= 1. { (1. - (1. - u.powi(2)).powi(3)) / 6. } else { 1. / 6. }),
+            _ => unreachable!()
+        };
+        let scale: float = match robust_scale {
+            RobustScale::Fixed(scale) => scale,
+            RobustScale::Mad => self.calc_robust_scale_by_mad(&residuals),
+        };
+        residuals.iter().map(|r| scale.powi(2) * rho(r / scale)).sum()
+    }
+
     pub fn calc_diff_with_antispikes(&self, points_1: &Vec<float>, points_2: &Vec<float>, antispikes: &Option<Antispikes>) -> float {
         let diff_main: float = self.calc_diff(points_1, &points_2);
         let diff_antispikes: float = antispikes.as_ref().map_or(
@@ -100,7 +211,11 @@ impl FromStr for DiffFunction {
             "DyAbs" | "dy_abs" => Ok(Self::DyAbs),
             "DySqrPerEl" | "dy_sqr_per_el" => Ok(Self::DySqrPerEl),
             "DyAbsPerEl" | "dy_abs_per_el" => Ok(Self::DyAbsPerEl),
-            "LeastDist" | "least_dist" => Ok(Self::LeastDist),
+            "LeastDist" | "least_dist" => Ok(Self::LeastDist { x_scale: 1., y_scale: 1. }),
+            "PoissonMLE" | "poisson_mle" => Ok(Self::PoissonMLE),
+            "Huber" | "huber" => Ok(Self::Huber(RobustScale::Mad)),
+            "Cauchy" | "cauchy" | "Lorentzian" | "lorentzian" => Ok(Self::Cauchy(RobustScale::Mad)),
+            "Tukey" | "tukey" => Ok(Self::Tukey(RobustScale::Mad)),
             _ => Err(())
         }
     }
@@ -109,13 +224,353 @@ impl FromStr for DiffFunction {
 
 impl Load for DiffFunction {
     const TOML_NAME: &'static str = "diff_function_type";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        // diff functions with params, e.g. `{ LeastDist = { x_scale = 2.0, y_scale = 0.1 } }` or `{ Huber = { scale = 0.1 } }`
+        if let Some(toml_table) = toml_value.as_table() {
+            const KNOWN_TYPES_WITH_PARAMS: [&str; 4] = ["LeastDist", "Huber", "Cauchy", "Tukey"];
+            let name: &'static str = match toml_table.keys().collect::<Vec<_>>()[..] {
+                [name] => KNOWN_TYPES_WITH_PARAMS
+                    .into_iter()
+                    .find(|known_name| known_name == name)
+                    .ok_or_else(|| stacktrace.err_unknown_type(name, KNOWN_TYPES_WITH_PARAMS))?,
+                [] => return Err(stacktrace.err_unknown_type_without_value(KNOWN_TYPES_WITH_PARAMS)),
+                ref names => return Err(stacktrace.err_more_than_one_found(names.iter().map(|name| name.as_str()).collect())),
+            };
+            let params = &toml_table[name];
+            let stacktrace = stacktrace.pushed(name);
+            return Ok(match name {
+                "LeastDist" => {
+                    let load_scale = |name: &'static str| -> Result<float> {
+                        let scale = params.load_float(name, &stacktrace)?;
+                        if !(scale > 0. && scale.is_finite()) {
+                            return Err(stacktrace.pushed(name).err("must be positive"));
+                        }
+                        Ok(scale)
+                    };
+                    Self::LeastDist { x_scale: load_scale("x_scale")?, y_scale: load_scale("y_scale")? }
+                }
+                "Huber" => Self::Huber(RobustScale::load_from_parent_handle_stacktrace(params, &stacktrace)?),
+                "Cauchy" => Self::Cauchy(RobustScale::load_from_parent_handle_stacktrace(params, &stacktrace)?),
+                "Tukey" => Self::Tukey(RobustScale::load_from_parent_handle_stacktrace(params, &stacktrace)?),
+                _ => unreachable!()
+            });
+        }
         let diff_function_str: &str = toml_value
             .as_str()
-            .unwrap_or_else(|| stacktrace.panic_cant_parse_as("string"));
-        const KNOWN_TYPES: [&str; 10] = ["DySqr", "dy_sqr", "DyAbs", "dy_abs", "DySqrPerEl", "dy_sqr_per_el", "DyAbsPerEl", "dy_abs_per_el", "LeastDist", "least_dist"];
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        const KNOWN_TYPES: [&str; 20] = [
+            "DySqr", "dy_sqr", "DyAbs", "dy_abs", "DySqrPerEl", "dy_sqr_per_el", "DyAbsPerEl", "dy_abs_per_el",
+            "LeastDist", "least_dist", "PoissonMLE", "poisson_mle",
+            "Huber", "huber", "Cauchy", "cauchy", "Lorentzian", "lorentzian", "Tukey", "tukey",
+        ];
         DiffFunction::from_str(diff_function_str)
-            .unwrap_or_else(|_| stacktrace.panic_unknown_type(diff_function_str, KNOWN_TYPES))
+            .map_err(|_| stacktrace.err_unknown_type(diff_function_str, KNOWN_TYPES))
+    }
+}
+
+
+/// Scale of robust loss, residuals much bigger than it are treated as outliers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RobustScale {
+    /// Given scale, in units of `y`.
+    Fixed(float),
+    /// Estimated once, from residuals at initial params: `k * 1.4826 * MAD`, where `k` is standard tuning constant of the loss
+    /// (`1.345` for Huber, `2.385` for Cauchy, `4.685` for Tukey).
+    Mad,
+}
+
+impl Load for RobustScale {
+    const TOML_NAME: &'static str = "scale";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        if let Some(scale) = toml_value.as_float() {
+            if !(scale > 0. && scale.is_finite()) {
+                return Err(stacktrace.err("must be positive"));
+            }
+            return Ok(Self::Fixed(scale));
+        }
+        let scale_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("float or string"))?;
+        match scale_str {
+            "mad" | "MAD" => Ok(Self::Mad),
+            _ => Err(stacktrace.err_unknown_type(scale_str, ["mad", "MAD"]))
+        }
+    }
+}
+
+
+/// Robust estimation of sigma of normally distributed residuals: `1.4826 * MAD`,
+/// where MAD is median absolute deviation from median.
+///
+/// If more than half of residuals are same (so MAD is zero), mean absolute deviation is used instead.
+pub fn calc_sigma_by_mad(residuals: &[float]) -> float {
+    fn median(mut values: Vec<float>) -> float {
+        values.sort_by(|a, b| a.total_cmp(b));
+        let len = values.len();
+        if len % 2 == 1 { values[len/2] } else { (values[len/2-1] + values[len/2]) / 2. }
+    }
+    if residuals.is_empty() { return 0. }
+    let residuals_median = median(residuals.to_vec());
+    let deviations: Vec<float> = residuals.iter().map(|r| (r - residuals_median).abs()).collect();
+    let mad = median(deviations.clone());
+    if mad > 0. {
+        1.4826 * mad
+    } else {
+        // src: https://en.wikipedia.org/wiki/Average_absolute_deviation#Mean_absolute_deviation_around_the_mean
+        (consts::PI / 2.).sqrt() * deviations.iter().sum::<float>() / (deviations.len() as float)
     }
 }
 
+
+/// Root of sum of squared orthogonal distances from measured points to convolved curve (polyline through its points),
+/// see [`calc_least_dists_sq`].
+pub fn calc_least_dist(points_measured: &[float], points_convolved: &[float], x_scale: float, y_scale: float) -> float {
+    calc_least_dists_sq(points_measured, points_convolved, x_scale, y_scale).iter().sum::<float>().sqrt()
+}
+
+/// Squared orthogonal distance from each measured point to convolved curve (polyline through its points).
+///
+/// Distance is calculated in scaled space: `(dx / x_scale, dy / y_scale)`, where `dx` is in points (steps),
+/// so steep edges (e.g. rise at time zero) aren't punished by huge vertical residuals for small shift.
+/// With very small `x_scale` it's same as squared `dy / y_scale`.
+pub fn calc_least_dists_sq(points_measured: &[float], points_convolved: &[float], x_scale: float, y_scale: float) -> Vec<float> {
+    assert_eq!(points_measured.len(), points_convolved.len());
+    let len = points_convolved.len();
+    points_measured.iter().enumerate()
+        .map(|(i, &y)| {
+            let (px, py) = (i as float / x_scale, y / y_scale);
+            // vertical distance is upper bound, so segments further than it along x can be skipped:
+            let mut dist_sq_min: float = ((y - points_convolved[i]) / y_scale).powi(2);
+            let di_max: usize = (dist_sq_min.sqrt() * x_scale).ceil().min(len as float) as usize;
+            let j_min = i.saturating_sub(di_max + 1);
+            let j_max = (i + di_max + 1).min(len - 1);
+            for j in j_min..j_max {
+                let (ax, ay) = (j as float / x_scale, points_convolved[j] / y_scale);
+                let (bx, by) = ((j + 1) as float / x_scale, points_convolved[j+1] / y_scale);
+                let (abx, aby) = (bx - ax, by - ay);
+                let t = (((px - ax) * abx + (py - ay) * aby) / (abx.powi(2) + aby.powi(2))).clamp(0., 1.);
+                let dist_sq = (px - ax - t * abx).powi(2) + (py - ay - t * aby).powi(2);
+                dist_sq_min = dist_sq_min.min(dist_sq);
+            }
+            dist_sq_min
+        })
+        .collect()
+}
+
+
+/// Term of Cash C-statistic (Poisson deviance) for one point:
+/// `2 * (model - measured + measured * ln(measured / model))`.
+///
+/// It's `-2 ln(likelihood ratio)` of `model` to perfect model (`model = measured`),
+/// so it's zero for perfect fit and for many counts it's close to `(measured - model)^2 / model`.
+///
+/// For zero counts (`measured <= 0`) log term is zero.
+/// `model` is clamped to small positive value, bc Poisson mean can't be zero or negative.
+pub fn calc_poisson_deviance(measured: float, model: float) -> float {
+    const MODEL_MIN: float = 1e-10;
+    let model = model.max(MODEL_MIN);
+    let log_term = if measured > 0. { measured * (measured / model).ln() } else { 0. };
+    2. * (model - measured.max(0.) + log_term)
+}
+
+
+
+#[cfg(test)]
+mod combine_residues {
+    use super::*;
+
+    #[test]
+    fn dy_sqr() {
+        let points_1 = DVect::from_vec(vec![0., 0., 0., 0.]);
+        let points_2 = DVect::from_vec(vec![1., 2., 3., 4.]);
+        assert_eq!(
+            DiffFunction::DySqr.calc_diff_v(&points_1, &points_2),
+            DiffFunction::DySqr.combine_residues([
+                DiffFunction::DySqr.calc_diff_v(&points_1.rows(0, 2).into(), &points_2.rows(0, 2).into()),
+                DiffFunction::DySqr.calc_diff_v(&points_1.rows(2, 2).into(), &points_2.rows(2, 2).into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn dy_abs() {
+        assert_eq!(3., DiffFunction::DyAbs.combine_residues([1., 2.]));
+    }
+}
+
+
+#[cfg(test)]
+mod calc_poisson_deviance {
+    use super::*;
+
+    #[test]
+    fn perfect_fit() {
+        assert_eq!(0., calc_poisson_deviance(5., 5.));
+    }
+
+    #[test]
+    fn zero_counts() {
+        assert_eq!(2. * 0.5, calc_poisson_deviance(0., 0.5));
+    }
+
+    #[test]
+    fn zero_model() {
+        assert!(calc_poisson_deviance(3., 0.).is_finite());
+        assert!(calc_poisson_deviance(3., 0.) > calc_poisson_deviance(3., 1.));
+    }
+
+    #[test]
+    fn many_counts_is_close_to_chi_square() {
+        let (measured, model) = (10_000., 10_100.);
+        let chi_square = (measured - model) * (measured - model) / model;
+        assert!((calc_poisson_deviance(measured, model) - chi_square).abs() / chi_square < 1e-2);
+    }
+}
+
+
+#[cfg(test)]
+mod calc_least_dist {
+    use super::*;
+
+    #[test]
+    fn same() {
+        assert_eq!(0., calc_least_dist(&[0., 1., 2.], &[0., 1., 2.], 1., 1.));
+    }
+
+    #[test]
+    fn horizontal_line() {
+        assert_eq!((3. * 0.25 as float).sqrt(), calc_least_dist(&[0.5, 0.5, 0.5], &[0., 0., 0.], 1., 1.));
+    }
+
+    #[test]
+    fn steep_edge_shifted() {
+        // edge shifted by one point: vertical residue is `10`, but orthogonal one is small.
+        let measured  = vec![0., 0., 10., 10., 10.];
+        let convolved = vec![0., 0., 0., 10., 10.];
+        assert!(calc_least_dist(&measured, &convolved, 1., 1.) < 1.);
+        assert_eq!(10., DiffFunction::DySqr.calc_diff(&measured, &convolved));
+    }
+
+    #[test]
+    fn small_x_scale_is_dy_sqr() {
+        let measured  = vec![0., 1., 4., 2., 1.];
+        let convolved = vec![0., 2., 3., 2., 0.];
+        let dy_sqr = DiffFunction::DySqr.calc_diff(&measured, &convolved);
+        assert!((dy_sqr / 2. - calc_least_dist(&measured, &convolved, 1e-9, 2.)).abs() < 1e-9);
+    }
+}
+
+
+#[cfg(test)]
+mod calc_robust_loss {
+    use super::*;
+
+    #[test]
+    fn huber_small_residuals_are_quadratic() {
+        let diff_function = DiffFunction::Huber(RobustScale::Fixed(1.));
+        assert_eq!((0.25 + 0.0625) / 2., diff_function.calc_diff(&vec![0., 0.], &vec![0.5, -0.25]));
+    }
+
+    #[test]
+    fn huber_big_residuals_are_linear() {
+        let diff_function = DiffFunction::Huber(RobustScale::Fixed(1.));
+        assert_eq!(0.5 + (10. - 0.5), diff_function.calc_diff(&vec![0., 0.], &vec![1., -10.]));
+    }
+
+    #[test]
+    fn tukey_ignores_outliers() {
+        let diff_function = DiffFunction::Tukey(RobustScale::Fixed(1.));
+        assert_eq!(
+            diff_function.calc_diff(&vec![0., 0., 0.], &vec![0.1, 0.2, 10.]),
+            diff_function.calc_diff(&vec![0., 0., 0.], &vec![0.1, 0.2, 1000.]),
+        );
+    }
+
+    #[test]
+    fn cauchy_v_same_as_not_v() {
+        let diff_function = DiffFunction::Cauchy(RobustScale::Mad);
+        let points_1 = vec![0., 1., 2., 3., 4., 5.];
+        let points_2 = vec![0.1, 0.9, 2.3, 2.8, 40., 5.];
+        assert_eq!(
+            diff_function.calc_diff(&points_1, &points_2),
+            diff_function.calc_diff_v(&DVect::from_vec(points_1), &DVect::from_vec(points_2)),
+        );
+    }
+
+    #[test]
+    fn sigma_by_mad() {
+        // MAD of [1, 2, 3, 4, 100] is 1 (median is 3, deviations are [2, 1, 0, 1, 97]).
+        assert_eq!(1.4826, calc_sigma_by_mad(&[1., 2., 3., 4., 100.]));
+    }
+
+    #[test]
+    fn mad_zero_perfect_fit() {
+        assert_eq!(0., DiffFunction::Huber(RobustScale::Mad).calc_diff(&vec![1., 2., 3.], &vec![1., 2., 3.]));
+    }
+}
+
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    #[test]
+    fn least_dist_str() {
+        assert_eq!(
+            DiffFunction::LeastDist { x_scale: 1., y_scale: 1. },
+            DiffFunction::load_from_self(&TomlValue::String("LeastDist".to_string()), &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn least_dist_table() {
+        let toml_value: TomlValue = toml::toml! { LeastDist = { x_scale = 2.0, y_scale = 0.1 } }.into();
+        assert_eq!(
+            DiffFunction::LeastDist { x_scale: 2., y_scale: 0.1 },
+            DiffFunction::load_from_self(&toml_value, &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn least_dist_table_negative_scale() {
+        let toml_value: TomlValue = toml::toml! { LeastDist = { x_scale = -2.0, y_scale = 0.1 } }.into();
+        assert_eq!(
+            "`LeastDist` -> `x_scale`: must be positive",
+            DiffFunction::load_from_self(&toml_value, &Stacktrace::empty()).unwrap_err().to_string()
+        );
+    }
+    #[test]
+    fn huber_str() {
+        assert_eq!(
+            DiffFunction::Huber(RobustScale::Mad),
+            DiffFunction::load_from_self(&TomlValue::String("Huber".to_string()), &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn tukey_table_fixed_scale() {
+        let toml_value: TomlValue = toml::toml! { Tukey = { scale = 0.5 } }.into();
+        assert_eq!(
+            DiffFunction::Tukey(RobustScale::Fixed(0.5)),
+            DiffFunction::load_from_self(&toml_value, &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn cauchy_table_mad() {
+        let toml_value: TomlValue = toml::toml! { Cauchy = { scale = "mad" } }.into();
+        assert_eq!(
+            DiffFunction::Cauchy(RobustScale::Mad),
+            DiffFunction::load_from_self(&toml_value, &Stacktrace::empty()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_table() {
+        let toml_value: TomlValue = toml::toml! { DySqr = { scale = 1.0 } }.into();
+        assert_eq!(
+            "`diff_function_type`: unkown type: `DySqr`, known types: [`LeastDist`, `Huber`, `Cauchy`, `Tukey`]",
+            DiffFunction::load_from_self(&toml_value, &Stacktrace::new("diff_function_type")).unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Errors.
+
+use std::{fmt, io};
+
+use crate::fit_algorithms::FitError;
+
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+
+pub enum Error {
+    /// Can't read config file.
+    ConfigRead { filepath: String, io_error: io::Error },
+    /// Config file isn't valid TOML.
+    ConfigParse { filepath: String, toml_error: toml::de::Error },
+    /// Value in config is missing or invalid, `path` is like "`fit_algorithm` -> `pattern_search` -> `alpha`".
+    ConfigValue { path: String, msg: String },
+    /// Can't read spectrum file.
+    SpectrumRead { filepath: String, io_error: io::Error },
+    /// Can't parse spectrum file at given line (starting from 1).
+    SpectrumParse { filepath: String, line_number: usize, msg: String },
+    /// Spectrum parsed, but it's unusable (e.g. too few points).
+    SpectrumInvalid { filepath: String, msg: String },
+    /// Fit algorithm failed.
+    Fit(FitError),
+    /// Can't write output file.
+    OutputWrite { filepath: String, io_error: io::Error },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigRead { filepath, io_error } => write!(f, "can't read config file `{filepath}`: {io_error}"),
+            Self::ConfigParse { filepath, toml_error } => write!(f, "can't parse config file `{filepath}` as toml: {toml_error}"),
+            Self::ConfigValue { path, msg } => write!(f, "{path}: {msg}"),
+            Self::SpectrumRead { filepath, io_error } => write!(f, "can't read spectrum file `{filepath}`: {io_error}"),
+            Self::SpectrumParse { filepath, line_number, msg } => write!(f, "can't parse spectrum file `{filepath}` at line {line_number}: {msg}"),
+            Self::SpectrumInvalid { filepath, msg } => write!(f, "invalid spectrum in `{filepath}`: {msg}"),
+            Self::Fit(fit_error) => write!(f, "fit failed: {fit_error}"),
+            Self::OutputWrite { filepath, io_error } => write!(f, "can't write output file `{filepath}`: {io_error}"),
+        }
+    }
+}
+
+/// Same as [`Display`](fmt::Display), so `main` returning `Err` prints human readable message.
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<FitError> for Error {
+    fn from(fit_error: FitError) -> Self {
+        Self::Fit(fit_error)
+    }
+}
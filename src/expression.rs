@@ -0,0 +1,501 @@
+//! Math expression, parsed from string, with `x` and named params.
+
+use std::fmt;
+
+use crate::{
+    extensions::ToStringWithSignificantDigits,
+    types::float::float,
+};
+
+
+/// Math expression of `x` and params, e.g. `a*(1-exp(-(x-s)/ta))*exp(-(x-s)/tb)`.
+///
+/// Supported: numbers, `+ - * / ^`, parentheses and functions from [`FunctionName`].
+/// Every other name is param, they are indexed in order of first appearance in expression.
+///
+/// Same syntax is valid for Desmos and Origin, so expression (with params' values) can be plotted there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    tokens: Vec<Token>,
+    tree: Node,
+    params_names: Vec<String>,
+}
+
+impl Expression {
+    const VARIABLE_NAME: &'static str = "x";
+
+    pub fn parse(str: &str) -> Result<Self, String> {
+        let tokens: Vec<Token> = tokenize(str)?;
+        let mut parser = Parser { tokens: &tokens, index: 0, params_names: vec![] };
+        let tree: Node = parser.parse_sum()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected `{token}` at token {}", parser.index + 1));
+        }
+        let params_names = parser.params_names;
+        Ok(Self { tokens, tree, params_names })
+    }
+
+    /// Names of params, in the same order as in params vector.
+    pub fn get_params_names(&self) -> &[String] {
+        &self.params_names
+    }
+
+    pub fn eval(&self, x: float, params: &[float]) -> float {
+        assert_eq!(self.params_names.len(), params.len());
+        self.tree.eval(x, params)
+    }
+
+    /// Expression with params replaced by their values.
+    pub fn to_string_with_params(&self, params: &[float], significant_digits: u8) -> String {
+        assert_eq!(self.params_names.len(), params.len());
+        self.tokens.iter()
+            .map(|token| match token {
+                Token::Name(name) => match self.params_names.iter().position(|param_name| param_name == name) {
+                    Some(param_index) => {
+                        let value = params[param_index];
+                        let value_str = value.to_string_with_significant_digits(significant_digits);
+                        if value < 0. { format!("({value_str})") } else { value_str }
+                    }
+                    None => name.clone(),
+                }
+                _ => token.to_string()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "{token}")?;
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Number and it's string, so it's printed as it was written.
+    Number(float, String),
+    Name(String),
+    Operator(Operator),
+    ParenOpen,
+    ParenClose,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(_, str) => write!(f, "{str}"),
+            Token::Name(name) => write!(f, "{name}"),
+            Token::Operator(operator) => write!(f, "{}", operator.to_char()),
+            Token::ParenOpen => write!(f, "("),
+            Token::ParenClose => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+fn tokenize(str: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = str.chars().collect();
+    let mut tokens: Vec<Token> = vec![];
+    let mut i: usize = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue
+        }
+        let token = if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1 }
+            // exponent, e.g. `1e-3`:
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let exponent_start = i + if chars.get(i+1).is_some_and(|&c| c == '+' || c == '-') { 2 } else { 1 };
+                if chars.get(exponent_start).is_some_and(|c| c.is_ascii_digit()) {
+                    i = exponent_start;
+                    while i < chars.len() && chars[i].is_ascii_digit() { i += 1 }
+                }
+            }
+            let number_str: String = chars[start..i].iter().collect();
+            let number: float = number_str.parse()
+                .map_err(|_| format!("can't parse `{number_str}` as number"))?;
+            tokens.push(Token::Number(number, number_str));
+            continue
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1 }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+            continue
+        } else if let Some(operator) = Operator::from_char(c) {
+            Token::Operator(operator)
+        } else {
+            match c {
+                '(' => Token::ParenOpen,
+                ')' => Token::ParenClose,
+                ',' => Token::Comma,
+                _ => return Err(format!("unexpected symbol `{c}`"))
+            }
+        };
+        tokens.push(token);
+        i += 1;
+    }
+    if tokens.is_empty() {
+        return Err("expression is empty".to_string());
+    }
+    Ok(tokens)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '+' => Some(Self::Add),
+            '-' => Some(Self::Sub),
+            '*' => Some(Self::Mul),
+            '/' => Some(Self::Div),
+            '^' => Some(Self::Pow),
+            _ => None
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Self::Add => '+',
+            Self::Sub => '-',
+            Self::Mul => '*',
+            Self::Div => '/',
+            Self::Pow => '^',
+        }
+    }
+
+    fn apply(self, a: float, b: float) -> float {
+        match self {
+            Self::Add => a + b,
+            Self::Sub => a - b,
+            Self::Mul => a * b,
+            Self::Div => a / b,
+            Self::Pow => a.powf(b),
+        }
+    }
+}
+
+
+/// Functions, that are same in Desmos and Origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionName {
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Max,
+    Min,
+}
+
+impl FunctionName {
+    const NAMES: [&'static str; 12] = ["exp", "ln", "sqrt", "abs", "sin", "cos", "tan", "sinh", "cosh", "tanh", "max", "min"];
+
+    fn from_str(name: &str) -> Option<Self> {
+        Some(match name {
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "sinh" => Self::Sinh,
+            "cosh" => Self::Cosh,
+            "tanh" => Self::Tanh,
+            "max" => Self::Max,
+            "min" => Self::Min,
+            _ => return None
+        })
+    }
+
+    const fn get_args_len(self) -> usize {
+        match self {
+            Self::Max | Self::Min => 2,
+            _ => 1
+        }
+    }
+
+    fn apply(self, args: &[float]) -> float {
+        match (self, args) {
+            (Self::Exp, [a]) => a.exp(),
+            (Self::Ln, [a]) => a.ln(),
+            (Self::Sqrt, [a]) => a.sqrt(),
+            (Self::Abs, [a]) => a.abs(),
+            (Self::Sin, [a]) => a.sin(),
+            (Self::Cos, [a]) => a.cos(),
+            (Self::Tan, [a]) => a.tan(),
+            (Self::Sinh, [a]) => a.sinh(),
+            (Self::Cosh, [a]) => a.cosh(),
+            (Self::Tanh, [a]) => a.tanh(),
+            (Self::Max, [a, b]) => a.max(*b),
+            (Self::Min, [a, b]) => a.min(*b),
+            _ => unreachable!()
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(float),
+    Variable,
+    Param(usize),
+    Neg(Box<Node>),
+    BinaryOperation(Operator, Box<Node>, Box<Node>),
+    Function(FunctionName, Vec<Node>),
+}
+
+impl Node {
+    fn eval(&self, x: float, params: &[float]) -> float {
+        match self {
+            Node::Number(number) => *number,
+            Node::Variable => x,
+            Node::Param(param_index) => params[*param_index],
+            Node::Neg(node) => -node.eval(x, params),
+            Node::BinaryOperation(operator, a, b) => operator.apply(a.eval(x, params), b.eval(x, params)),
+            Node::Function(function_name, args) => {
+                let args: Vec<float> = args.iter().map(|arg| arg.eval(x, params)).collect();
+                function_name.apply(&args)
+            }
+        }
+    }
+}
+
+
+/// Recursive descent parser:
+/// - sum = product (("+" | "-") product)*
+/// - product = unary (("*" | "/") unary)*
+/// - unary = ("-" | "+") unary | power
+/// - power = primary ("^" unary)?
+/// - primary = number | name | function "(" sum ("," sum)* ")" | "(" sum ")"
+struct Parser<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    params_names: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Result<&'a Token, String> {
+        let token = self.peek().ok_or_else(|| "unexpected end of expression".to_string())?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, token_expected: Token) -> Result<(), String> {
+        let token = self.next()?;
+        if *token != token_expected {
+            return Err(format!("expected `{token_expected}`, but found `{token}` at token {}", self.index));
+        }
+        Ok(())
+    }
+
+    fn next_if_operator(&mut self, operators: &[Operator]) -> Option<Operator> {
+        match self.peek() {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                self.index += 1;
+                Some(*operator)
+            }
+            _ => None
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_product()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::Add, Operator::Sub]) {
+            node = Node::BinaryOperation(operator, Box::new(node), Box::new(self.parse_product()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_product(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_unary()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::Mul, Operator::Div]) {
+            node = Node::BinaryOperation(operator, Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        match self.next_if_operator(&[Operator::Add, Operator::Sub]) {
+            Some(Operator::Sub) => Ok(Node::Neg(Box::new(self.parse_unary()?))),
+            Some(_) => self.parse_unary(),
+            None => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Node, String> {
+        let node = self.parse_primary()?;
+        match self.next_if_operator(&[Operator::Pow]) {
+            // right associative, so `a^b^c = a^(b^c)`:
+            Some(operator) => Ok(Node::BinaryOperation(operator, Box::new(node), Box::new(self.parse_unary()?))),
+            None => Ok(node),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Number(number, _) => Ok(Node::Number(*number)),
+            Token::ParenOpen => {
+                let node = self.parse_sum()?;
+                self.expect(Token::ParenClose)?;
+                Ok(node)
+            }
+            Token::Name(name) if self.peek() == Some(&Token::ParenOpen) => {
+                let function_name = FunctionName::from_str(name)
+                    .ok_or_else(|| format!("unknown function `{name}`, known functions: [{}]", FunctionName::NAMES.join(", ")))?;
+                self.expect(Token::ParenOpen)?;
+                let mut args: Vec<Node> = vec![self.parse_sum()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.index += 1;
+                    args.push(self.parse_sum()?);
+                }
+                self.expect(Token::ParenClose)?;
+                let args_len_expected = function_name.get_args_len();
+                if args.len() != args_len_expected {
+                    return Err(format!("function `{name}` takes {args_len_expected} args, but {} given", args.len()));
+                }
+                Ok(Node::Function(function_name, args))
+            }
+            Token::Name(name) if name == Expression::VARIABLE_NAME => Ok(Node::Variable),
+            Token::Name(name) => {
+                if FunctionName::from_str(name).is_some() {
+                    return Err(format!("`{name}` is function, so it can't be param"));
+                }
+                let param_index = match self.params_names.iter().position(|param_name| param_name == name) {
+                    Some(param_index) => param_index,
+                    None => {
+                        self.params_names.push(name.clone());
+                        self.params_names.len() - 1
+                    }
+                };
+                Ok(Node::Param(param_index))
+            }
+            token => Err(format!("unexpected `{token}` at token {}", self.index)),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod parse_and_eval {
+    use super::*;
+
+    fn eval(str: &str, x: float, params: &[float]) -> float {
+        Expression::parse(str).unwrap().eval(x, params)
+    }
+
+    #[test]
+    fn number() {
+        assert_eq!(1.5e-3, eval("1.5e-3", 0., &[]));
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(7., eval("1 + 2*3", 0., &[]));
+        assert_eq!(2., eval("8/2/2", 0., &[]));
+        assert_eq!(-4., eval("-2^2", 0., &[]));
+        assert_eq!(512., eval("2^3^2", 0., &[]));
+        assert_eq!(0.5, eval("2^-1", 0., &[]));
+        assert_eq!(-6., eval("2*-3", 0., &[]));
+        assert_eq!(9., eval("(1+2)*3", 0., &[]));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(1., eval("exp(0) + ln(1)", 0., &[]));
+        assert_eq!(3., eval("max(2, 3)", 0., &[]));
+        assert_eq!(4., eval("sqrt(abs(-16))", 0., &[]));
+    }
+
+    #[test]
+    fn params_in_order_of_appearance() {
+        let expression = Expression::parse("a*(1-exp(-(x-s)/ta))*exp(-(x-s)/tb)").unwrap();
+        assert_eq!(["a", "s", "ta", "tb"], expression.get_params_names());
+        let (a, s, ta, tb, x): (float, float, float, float, float) = (2., 1., 0.5, 3., 2.5);
+        let expected = a * (1. - (-(x-s)/ta).exp()) * (-(x-s)/tb).exp();
+        assert_eq!(expected, expression.eval(x, &[a, s, ta, tb]));
+    }
+}
+
+#[cfg(test)]
+mod parse_errors {
+    use super::*;
+
+    fn parse_err(str: &str) -> String {
+        Expression::parse(str).unwrap_err()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!("expression is empty", parse_err("  "));
+    }
+
+    #[test]
+    fn unknown_function() {
+        assert!(parse_err("foo(x)").starts_with("unknown function `foo`"));
+    }
+
+    #[test]
+    fn wrong_args_len() {
+        assert_eq!("function `max` takes 2 args, but 1 given", parse_err("max(x)"));
+    }
+
+    #[test]
+    fn function_as_param() {
+        assert_eq!("`exp` is function, so it can't be param", parse_err("a*exp"));
+    }
+
+    #[test]
+    fn unclosed_paren() {
+        assert_eq!("unexpected end of expression", parse_err("(x+1"));
+    }
+
+    #[test]
+    fn extra_token() {
+        assert_eq!("unexpected `)` at token 4", parse_err("x+1)"));
+    }
+
+    #[test]
+    fn unexpected_symbol() {
+        assert_eq!("unexpected symbol `&`", parse_err("x & 1"));
+    }
+}
+
+#[cfg(test)]
+mod to_string_with_params {
+    use super::*;
+
+    #[test]
+    fn params_are_replaced() {
+        let expression = Expression::parse("a * exp(-(x - s)/t)").unwrap();
+        assert_eq!("a*exp(-(x-s)/t)", expression.to_string());
+        assert_eq!("2.000*exp(-(x-(-1.000))/0.5000)", expression.to_string_with_params(&[2., -1., 0.5], 4));
+    }
+}
@@ -4,22 +4,22 @@ use std::cmp::Ordering;
 
 use rand::{thread_rng, Rng};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use toml::Value as TomlValue;
+use toml::{Table as TomlTable, Value as TomlValue};
 
 use crate::{
-    deconvolution::deconvolution_data::DeconvolutionData,
+    error::Result,
     extensions::IndexOfMin,
     load::Load,
     stacktrace::Stacktrace,
     types::{
         float::float,
         linalg::DVect,
-        named_wrappers::{Instrument, InstrumentRevV, Measured, MeasuredV, ParamsG, ParamsV},
+        named_wrappers::{ParamsG, ParamsV},
     },
     unmut,
 };
 
-use super::{Fit, FitResult};
+use super::{Fit, FitData, FitError, FitResult};
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -34,27 +34,25 @@ pub struct DifferentialEvolution {
 }
 
 impl DifferentialEvolution {
-    pub fn fit(&self, deconvolution_data: &DeconvolutionData) -> FitResult {
+    pub fn fit(&self, fit_data: &impl FitData) -> FitResult {
         const DEBUG: bool = false;
 
         let Self { initial_values_random_scale, generations, population, mutation_speed, crossover_probability } = *self;
 
-        let f_params_amount: usize = deconvolution_data.deconvolution.get_initial_values_len();
+        let f_params_amount: usize = fit_data.get_params_amount();
         if f_params_amount == 0 {
-            return Err("too few params");
+            return Err(FitError::TooFewParams);
             // return None;
         }
-
-        let instrument_v_rev: InstrumentRevV = Instrument(deconvolution_data.instrument.points.clone()).into();
-        let measured_v: MeasuredV = Measured(deconvolution_data.measured.points.clone()).into();
+        let fit_data_prepared = fit_data.prepare();
 
         let mut rng = thread_rng();
 
         let mut generation = Vec::<ParamsV>::from_iter(
             (0..population)
                 .map(|_|
-                    deconvolution_data.deconvolution
-                        .get_initial_values_randomized_with_rng_v(initial_values_random_scale, &mut rng)
+                    fit_data
+                        .get_initial_params_randomized_with_rng_v(initial_values_random_scale, &mut rng)
                 )
         );
         let mut fit_residue_evals: u64 = 0;
@@ -62,11 +60,11 @@ impl DifferentialEvolution {
         let mut ress_of_current_gen: Vec<float> = generation
             .iter()
             // TODO(optim): parallel iter?
-            .map(|p| deconvolution_data.calc_residue_function_v(p, &instrument_v_rev, &measured_v))
+            .map(|p| fit_data.calc_residue_function_v(p, &fit_data_prepared))
             .collect();
         fit_residue_evals += population as u64;
         if DEBUG { println!("res_at_current_gen = {:?}", ress_of_current_gen) }
-        if ress_of_current_gen.iter().all(|r| !r.is_finite()) { return Err("`res_at_current_params` isn't finite") }
+        if ress_of_current_gen.iter().all(|r| !r.is_finite()) { return Err(FitError::ResidueIsNotFinite) }
         // if !res_at_current_params.is_finite() { return None }
         // if ress_of_current_gen.iter().all(|&r| r >= fit_residue_max_value) { return Err("`res_at_current_params` is too big") }
 
@@ -112,10 +110,10 @@ impl DifferentialEvolution {
                 // .into_iter()
                 .into_par_iter()
                 .map(|params_new: &ParamsV| -> (u64, float) {
-                    if !deconvolution_data.is_params_ok_v(params_new) {
+                    if !fit_data.is_params_ok_v(params_new) {
                         (0, float::NAN)
                     } else {
-                        let residue = deconvolution_data.calc_residue_function_v(params_new, &instrument_v_rev, &measured_v);
+                        let residue = fit_data.calc_residue_function_v(params_new, &fit_data_prepared);
                         (1, if residue.is_finite() { residue } else { float::NAN })
                     }
                     // returns tuple of `residue_function_evals` and `residue_result`.
@@ -153,7 +151,7 @@ impl DifferentialEvolution {
         //         println!("HIT MAX_ITERS!!!");
         //         press_enter_to_continue();
         //     }
-        //     return Err("hit max evals");
+        //     return Err(FitError::HitMaxEvals);
         //     // return None;
         // }
         if DEBUG { println!("finished in {} iters", fit_residue_evals) }
@@ -165,41 +163,57 @@ impl DifferentialEvolution {
             params,
             fit_residue,
             fit_residue_evals,
+            covariance: None,
+            regularization_lambda: None,
         })
     }
 }
 
 
+impl DifferentialEvolution {
+    /// Same keys, as in config.
+    pub fn to_toml_table(self) -> TomlTable {
+        let Self { initial_values_random_scale, generations, population, mutation_speed, crossover_probability } = self;
+        let mut toml_table = TomlTable::new();
+        toml_table.insert("initial_values_random_scale".to_string(), initial_values_random_scale.into());
+        toml_table.insert("generations".to_string(), (generations as i64).into());
+        toml_table.insert("population".to_string(), (population as i64).into());
+        toml_table.insert("mutation_speed".to_string(), mutation_speed.into());
+        toml_table.insert("crossover_probability".to_string(), crossover_probability.into());
+        toml_table
+    }
+}
+
 impl Load for DifferentialEvolution {
     const TOML_NAME: &'static str = "differential_evolution";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        let load_float = |name: &'static str| -> float {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let load_float = |name: &'static str| -> Result<float> {
             let stacktrace = stacktrace.pushed(name);
             toml_value
                 .get(name)
-                .unwrap_or_else(|| stacktrace.panic_not_found())
+                .ok_or_else(|| stacktrace.err_not_found())?
                 .as_float()
-                .unwrap_or_else(|| stacktrace.panic_cant_parse_as("float"))
+                .ok_or_else(|| stacktrace.err_cant_parse_as("float"))
         };
-        let load_usize = |name: &'static str| -> usize {
+        let load_usize = |name: &'static str| -> Result<usize> {
             let stacktrace = stacktrace.pushed(name);
             let value = toml_value
                 .get(name)
-                .unwrap_or_else(|| stacktrace.panic_not_found())
+                .ok_or_else(|| stacktrace.err_not_found())?
                 .as_integer()
-                .unwrap_or_else(|| stacktrace.panic_cant_parse_as("int"));
+                .ok_or_else(|| stacktrace.err_cant_parse_as("int"))?;
             if !(usize::MIN as i128..=usize::MAX as i128).contains(&(value as i128)) {
-                stacktrace.panic_cant_parse_as("usize")
+                return Err(stacktrace.err_cant_parse_as("usize"));
             }
-            value as usize
+            Ok(value as usize)
         };
-        Self {
-            initial_values_random_scale: load_float("initial_values_random_scale"),
-            generations: load_usize("generations"),
-            population: load_usize("population"),
-            mutation_speed: load_float("mutation_speed"),
-            crossover_probability: load_float("crossover_probability"),
-        }
+        Ok(Self {
+            initial_values_random_scale: load_float("initial_values_random_scale")?,
+            generations: load_usize("generations")?,
+            population: load_usize("population")?,
+            mutation_speed: load_float("mutation_speed")?,
+            crossover_probability: load_float("crossover_probability")?,
+        })
     }
 }
 
@@ -0,0 +1,231 @@
+//! Levenberg–Marquardt algorithm.
+
+use toml::{Table as TomlTable, Value as TomlValue};
+
+use crate::{
+    config::ExtTomlValueLoadPrimitives,
+    error::Result,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::{
+        float::float,
+        linalg::{DMat, DVect},
+        named_wrappers::{ParamsG, ParamsV},
+    },
+};
+
+use super::{Fit, FitData, FitDataAutoImplFns, FitError, FitResult, calc_covariance_from_jacobian};
+
+
+/// Minimizes sum of squares of residuals (`measured - convolved`),
+/// so only least squares `diff_function_type` (`DySqr` or `DySqrPerEl`) should be used with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevenbergMarquardt {
+    // TODO(refactor): remove pub (used only for tests)
+    pub fit_residue_evals_max: u64,
+    pub initial_lambda: float,
+    pub lambda_increase: float,
+    pub lambda_decrease: Option<float>,
+    pub min_relative_improvement: float,
+    pub jacobian_relative_step: float,
+}
+
+impl LevenbergMarquardt {
+    /// If `lambda` is this big, step is negligible, so algorithm is stuck in minimum.
+    const LAMBDA_MAX: float = 1e16;
+
+    pub fn fit(&self, fit_data: &impl FitData, initial_params: ParamsV) -> FitResult {
+        const DEBUG: bool = false;
+
+        let Self { fit_residue_evals_max, initial_lambda, lambda_increase, lambda_decrease, min_relative_improvement, jacobian_relative_step } = *self;
+        let lambda_decrease = lambda_decrease.unwrap_or(1. / lambda_increase);
+
+        let f_params_amount: usize = initial_params.0.len();
+        if f_params_amount == 0 {
+            return Err(FitError::TooFewParams);
+        }
+        let fit_data_prepared = fit_data.prepare();
+
+        let mut params: ParamsV = initial_params;
+        let mut lambda: float = initial_lambda;
+        let mut fit_residue_evals: u64 = 0;
+
+        let mut residuals: DVect = fit_data.calc_residuals_v(&params, &fit_data_prepared);
+        fit_residue_evals += 1;
+        let mut sum_sq: float = residuals.norm_squared();
+        if DEBUG { println!("sum_sq = {}", sum_sq) }
+        if !sum_sq.is_finite() { return Err(FitError::ResidueIsNotFinite) }
+
+        let (fit_residue_evals_extra, mut jacobian) = fit_data.calc_convolved_jacobian_v(&params, &residuals, &fit_data_prepared, jacobian_relative_step);
+        fit_residue_evals += fit_residue_evals_extra;
+
+        while lambda < Self::LAMBDA_MAX && sum_sq > 0. {
+            if fit_residue_evals >= fit_residue_evals_max {
+                return Err(FitError::HitMaxEvals);
+            }
+            if DEBUG {
+                println!("params = {:#?}", params);
+                println!("lambda = {}", lambda);
+            }
+
+            let jacobian_t: DMat = jacobian.transpose();
+            let jtj: DMat = &jacobian_t * &jacobian;
+            let jtr: DVect = &jacobian_t * &residuals;
+            // Marquardt's damping, scaled by diagonal, so it doesn't depend on params scale:
+            let mut jtj_damped: DMat = jtj.clone();
+            for i in 0..f_params_amount {
+                let d = jtj[(i, i)];
+                // zero diagonal element means param doesn't change residuals (e.g. it's fixed),
+                // so `1` here just makes matrix invertible, and it's delta will be zero anyway.
+                jtj_damped[(i, i)] = if d > 0. { d * (1. + lambda) } else { 1. };
+            }
+            let Some(jtj_damped_cholesky) = jtj_damped.cholesky() else {
+                lambda *= lambda_increase;
+                continue;
+            };
+            let delta: DVect = jtj_damped_cholesky.solve(&jtr);
+
+            let params_new: ParamsV = fit_data.clamp_params_v(&ParamsV(&params.0 + delta));
+            if !fit_data.is_params_ok_v(&params_new) || params_new.0.iter().any(|p| !p.is_finite()) {
+                lambda *= lambda_increase;
+                continue;
+            }
+            let residuals_new: DVect = fit_data.calc_residuals_v(&params_new, &fit_data_prepared);
+            fit_residue_evals += 1;
+            let sum_sq_new: float = residuals_new.norm_squared();
+            if DEBUG { println!("sum_sq_new = {}", sum_sq_new) }
+
+            if sum_sq_new.is_finite() && sum_sq_new < sum_sq {
+                let relative_improvement: float = (sum_sq - sum_sq_new) / sum_sq;
+                params = params_new;
+                residuals = residuals_new;
+                sum_sq = sum_sq_new;
+                lambda *= lambda_decrease;
+                let (fit_residue_evals_extra, jacobian_new) = fit_data.calc_convolved_jacobian_v(&params, &residuals, &fit_data_prepared, jacobian_relative_step);
+                fit_residue_evals += fit_residue_evals_extra;
+                jacobian = jacobian_new;
+                if relative_improvement < min_relative_improvement { break }
+            } else {
+                lambda *= lambda_increase;
+            }
+        }
+        if DEBUG { println!("finished in {} iters", fit_residue_evals) }
+
+        let covariance: Option<DMat> = calc_covariance_from_jacobian(&jacobian, sum_sq, fit_data.get_free_params_amount(), fit_data.is_residuals_in_sigmas());
+
+        let fit_residue = fit_data.calc_residue_function_v(&params, &fit_data_prepared);
+        fit_residue_evals += 1;
+        let params = ParamsG::<float>(params.0.data.into());
+        Ok(Fit {
+            params,
+            fit_residue,
+            fit_residue_evals,
+            covariance,
+            regularization_lambda: None,
+        })
+    }
+
+}
+
+
+impl LevenbergMarquardt {
+    /// Same keys, as in config.
+    pub fn to_toml_table(self) -> TomlTable {
+        let Self { fit_residue_evals_max, initial_lambda, lambda_increase, lambda_decrease, min_relative_improvement, jacobian_relative_step } = self;
+        let mut toml_table = TomlTable::new();
+        toml_table.insert("fit_residue_evals_max".to_string(), (fit_residue_evals_max as i64).into());
+        toml_table.insert("initial_lambda".to_string(), initial_lambda.into());
+        toml_table.insert("lambda_increase".to_string(), lambda_increase.into());
+        if let Some(lambda_decrease) = lambda_decrease { toml_table.insert("lambda_decrease".to_string(), lambda_decrease.into()); }
+        toml_table.insert("min_relative_improvement".to_string(), min_relative_improvement.into());
+        toml_table.insert("jacobian_relative_step".to_string(), jacobian_relative_step.into());
+        toml_table
+    }
+}
+
+impl Load for LevenbergMarquardt {
+    const TOML_NAME: &'static str = "levenberg_marquardt";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let lambda_decrease = {
+            let name = "lambda_decrease";
+            let stacktrace = stacktrace.pushed(name);
+            toml_value
+                .get(name)
+                .map(|lambda_decrease_toml_value| {
+                    lambda_decrease_toml_value
+                        .as_float()
+                        .ok_or_else(|| stacktrace.err_cant_parse_as("float"))
+                })
+                .transpose()?
+        };
+        Ok(Self {
+            fit_residue_evals_max: toml_value.load_u64("fit_residue_evals_max", stacktrace)?,
+            initial_lambda: toml_value.load_float("initial_lambda", stacktrace)?,
+            lambda_increase: toml_value.load_float("lambda_increase", stacktrace)?,
+            lambda_decrease,
+            min_relative_improvement: toml_value.load_float("min_relative_improvement", stacktrace)?,
+            jacobian_relative_step: toml_value.load_float("jacobian_relative_step", stacktrace)?,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod fit {
+    use crate::deconvolution::{
+        test_fixtures::deconvolution_data_sat_exp_dec_exp,
+        types::{sat_exp__dec_exp::InitialValues_SatExp_DecExp, value_and_domain::ValueAndDomain},
+    };
+    use super::LevenbergMarquardt;
+
+    const LEVENBERG_MARQUARDT: LevenbergMarquardt = LevenbergMarquardt {
+        fit_residue_evals_max: 100_000,
+        initial_lambda: 1e-3,
+        lambda_increase: 10.,
+        lambda_decrease: None,
+        min_relative_improvement: 1e-12,
+        jacobian_relative_step: 1e-7,
+    };
+
+    #[test]
+    fn sat_exp_dec_exp() {
+        let params_true = [2., 20., 3., 15.];
+        let deconvolution_data = deconvolution_data_sat_exp_dec_exp(
+            InitialValues_SatExp_DecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::free(18.),
+                tau_a: ValueAndDomain::range_with_min(5., 0.),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+            },
+            params_true,
+        );
+        let fit = LEVENBERG_MARQUARDT.fit(&deconvolution_data, deconvolution_data.get_initial_params().into()).unwrap();
+        for (expected, actual) in params_true.iter().zip(&fit.params.0) {
+            assert!((expected - actual).abs() < 1e-4, "expected: {params_true:?}, actual: {:?}", fit.params.0);
+        }
+        let covariance = fit.covariance.unwrap();
+        assert_eq!((4, 4), covariance.shape());
+    }
+
+    #[test]
+    fn fixed_param_is_not_changed() {
+        let params_true = [2., 20., 3., 15.];
+        let deconvolution_data = deconvolution_data_sat_exp_dec_exp(
+            InitialValues_SatExp_DecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::fixed(20.),
+                tau_a: ValueAndDomain::range_closed(5., (1., 10.)),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+            },
+            params_true,
+        );
+        let fit = LEVENBERG_MARQUARDT.fit(&deconvolution_data, deconvolution_data.get_initial_params().into()).unwrap();
+        assert_eq!(20., fit.params.0[1]);
+        for (expected, actual) in params_true.iter().zip(&fit.params.0) {
+            assert!((expected - actual).abs() < 1e-4, "expected: {params_true:?}, actual: {:?}", fit.params.0);
+        }
+        let covariance = fit.covariance.unwrap();
+        assert_eq!(0., covariance[(1, 1)]);
+    }
+}
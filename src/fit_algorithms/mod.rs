@@ -1,39 +1,215 @@
 //! Fit Algorithms.
 
 pub mod differential_evolution;
+pub mod levenberg_marquardt;
+pub mod nelder_mead;
 pub mod pattern_search;
 pub mod pattern_search_adaptive_step;
 pub mod pattern_search_scaled_step;
 
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt};
 
-use toml::Value as TomlValue;
+use rand::rngs::ThreadRng;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use toml::{Table as TomlTable, Value as TomlValue};
 
 use crate::{
-    deconvolution::deconvolution_data::DeconvolutionData,
+    error::Result,
     load::{LoadAutoImplFns, Load},
     stacktrace::Stacktrace,
-    types::{float::float, named_wrappers::Params},
+    types::{float::float, linalg::{DMat, DVect}, named_wrappers::{Params, ParamsV}},
 };
 
 use self::{
     differential_evolution::DifferentialEvolution,
+    levenberg_marquardt::LevenbergMarquardt,
+    nelder_mead::NelderMead,
     pattern_search::PatternSearch,
     pattern_search_adaptive_step::PatternSearchAdaptiveStep,
     pattern_search_scaled_step::PatternSearchScaledStep,
 };
 
 
+// "have to be implemented" part
+/// Data, which params can be fitted by any [`FitAlgorithmVariant`].
+pub trait FitData: Sync {
+    /// Everything, that doesn't depend on params, so it's calculated once per fit, not in every residue function eval.
+    type Prepared: Sync;
+
+    fn prepare(&self) -> Self::Prepared;
+    fn get_params_amount(&self) -> usize;
+    /// Number of not fixed params, used for degrees of freedom, see [`calc_degrees_of_freedom`].
+    fn get_free_params_amount(&self) -> usize;
+    /// Are residuals divided by known absolute sigmas (from file or poisson),
+    /// so covariance isn't rescaled by residual variance, see [`calc_covariance_from_jacobian`].
+    fn is_residuals_in_sigmas(&self) -> bool;
+    fn get_initial_params_randomized_with_rng_v(&self, initial_values_random_scale: float, rng: &mut ThreadRng) -> ParamsV;
+    fn is_params_ok_v(&self, params: &ParamsV) -> bool;
+    fn clamp_params_v(&self, params: &ParamsV) -> ParamsV;
+    fn calc_residue_function_v(&self, params: &ParamsV, prepared: &Self::Prepared) -> float;
+    /// Weighted residuals (`(measured - convolved) / sigma`) for each point used in fit,
+    /// used by algorithms working with vector, not scalar.
+    fn calc_residuals_v(&self, params: &ParamsV, prepared: &Self::Prepared) -> DVect;
+}
+
+
+// "will be auto implemented" part
+pub trait FitDataAutoImplFns: FitData {
+    fn calc_convolved_jacobian_v(&self, params: &ParamsV, residuals: &DVect, prepared: &Self::Prepared, relative_step: float) -> (u64, DMat);
+    fn calc_covariance(&self, params: &Params) -> Option<DMat>;
+}
+
+impl<T: FitData> FitDataAutoImplFns for T {
+    /// Jacobian of convolved points by params, calculated by finite differences,
+    /// using already calculated `residuals` at `params`.
+    ///
+    /// If param can't be changed (e.g. it's fixed or at the domain boundary from both sides),
+    /// it's column is zero.
+    ///
+    /// Returns tuple of `residue_function_evals` and `jacobian`.
+    fn calc_convolved_jacobian_v(
+        &self,
+        params: &ParamsV,
+        residuals: &DVect,
+        prepared: &Self::Prepared,
+        relative_step: float,
+    ) -> (u64, DMat) {
+        let (fit_residue_evals, columns): (Vec<u64>, Vec<DVect>) = (0..params.0.len())
+            .into_par_iter()
+            .map(|i| -> (u64, DVect) {
+                let param = params.0[i];
+                let h = relative_step * if param != 0. { param.abs() } else { 1. };
+                // try forward difference, and if it's out of domain, then backward one:
+                for h in [h, -h] {
+                    let mut params_shifted = params.clone();
+                    params_shifted.0[i] += h;
+                    if !self.is_params_ok_v(&params_shifted) { continue }
+                    let residuals_shifted = self.calc_residuals_v(&params_shifted, prepared);
+                    // residuals are `measured - convolved`, so derivative of convolved have opposite sign.
+                    let column: DVect = (residuals - residuals_shifted) / h;
+                    return if column.iter().all(|v| v.is_finite()) {
+                        (1, column)
+                    } else {
+                        (1, DVect::zeros(residuals.len()))
+                    };
+                }
+                (0, DVect::zeros(residuals.len()))
+            })
+            .unzip();
+        (fit_residue_evals.iter().sum(), DMat::from_columns(&columns))
+    }
+
+    /// Estimate params covariance matrix at `params` (which should be the optimum)
+    /// from Jacobian of residuals, independent of fit algorithm used.
+    ///
+    /// Returns `None` if there are not enough points or matrix is singular.
+    fn calc_covariance(&self, params: &Params) -> Option<DMat> {
+        /// Relative step for finite differences, `~ sqrt(float::EPSILON)`.
+        const JACOBIAN_RELATIVE_STEP: float = 1e-7;
+        let params: ParamsV = params.clone().into();
+        let prepared = self.prepare();
+        let residuals: DVect = self.calc_residuals_v(&params, &prepared);
+        // no need to calculate jacobian, if covariance can't be estimated anyway:
+        if residuals.len() <= self.get_free_params_amount() { return None }
+        let (_, jacobian) = self.calc_convolved_jacobian_v(&params, &residuals, &prepared, JACOBIAN_RELATIVE_STEP);
+        calc_covariance_from_jacobian(&jacobian, residuals.norm_squared(), self.get_free_params_amount(), self.is_residuals_in_sigmas())
+    }
+}
+
+
+
 #[derive(Debug)]
 pub struct Fit {
     pub params: Params,
     pub fit_residue: float,
     pub fit_residue_evals: u64,
+    /// Params covariance matrix, if fit algorithm can estimate it.
+    pub covariance: Option<DMat>,
+    /// Regularization strength, if deconvolution is regularized (and it's chosen automatically).
+    pub regularization_lambda: Option<float>,
+}
+
+
+impl Fit {
+    /// Standard errors of params: square roots of covariance matrix diagonal.
+    pub fn get_standard_errors(&self) -> Option<Vec<float>> {
+        self.covariance.as_ref().map(|covariance| {
+            covariance.diagonal().iter().map(|variance| variance.max(0.).sqrt()).collect()
+        })
+    }
+
+    /// Correlation matrix of params: `cov_ij / (σ_i σ_j)`.
+    ///
+    /// Rows and columns of params with zero standard error (e.g. fixed ones) are zero.
+    pub fn get_correlation_matrix(&self) -> Option<DMat> {
+        let covariance = self.covariance.as_ref()?;
+        let standard_errors = self.get_standard_errors()?;
+        Some(DMat::from_fn(covariance.nrows(), covariance.ncols(), |i, j| {
+            let denominator = standard_errors[i] * standard_errors[j];
+            if denominator > 0. { covariance[(i, j)] / denominator } else { 0. }
+        }))
+    }
+}
+
+
+/// Degrees of freedom: `N - p`, where `N` is number of points used in fit and `p` is number of not fixed params.
+///
+/// `None` if there are no degrees of freedom.
+pub fn calc_degrees_of_freedom(points_len: usize, params_free_amount: usize) -> Option<usize> {
+    points_len.checked_sub(params_free_amount).filter(|&degrees_of_freedom| degrees_of_freedom > 0)
+}
+
+/// Covariance matrix: `s² · (JᵀJ)⁻¹`, where `s² = Σr² / (N - p)`, see [`calc_degrees_of_freedom`].
+///
+/// If `is_residuals_in_sigmas`, then sigmas are known absolute uncertainties, so `s² = 1`
+/// (otherwise errors would be rescaled by reduced chi square).
+///
+/// Rows and columns of params, that don't change residuals (e.g. fixed ones), are zero.
+pub fn calc_covariance_from_jacobian(
+    jacobian: &DMat,
+    sum_sq: float,
+    params_free_amount: usize,
+    is_residuals_in_sigmas: bool,
+) -> Option<DMat> {
+    let params_changing_indices: Vec<usize> = jacobian.column_iter()
+        .enumerate()
+        .filter(|(_i, column)| column.iter().any(|&v| v != 0.))
+        .map(|(i, _column)| i)
+        .collect();
+    let degrees_of_freedom: usize = calc_degrees_of_freedom(jacobian.nrows(), params_free_amount)?;
+    let residual_variance: float = if is_residuals_in_sigmas { 1. } else { sum_sq / (degrees_of_freedom as float) };
+    let jacobian_changing: DMat = jacobian.select_columns(&params_changing_indices);
+    let jtj_changing_inv: DMat = (jacobian_changing.transpose() * jacobian_changing).try_inverse()?;
+    let mut covariance: DMat = DMat::zeros(jacobian.ncols(), jacobian.ncols());
+    for (i_changing, &i) in params_changing_indices.iter().enumerate() {
+        for (j_changing, &j) in params_changing_indices.iter().enumerate() {
+            covariance[(i, j)] = residual_variance * jtj_changing_inv[(i_changing, j_changing)];
+        }
+    }
+    Some(covariance)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FitError {
+    TooFewParams,
+    ResidueIsNotFinite,
+    HitMaxEvals,
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooFewParams => write!(f, "too few params"),
+            Self::ResidueIsNotFinite => write!(f, "`res_at_current_params` isn't finite"),
+            Self::HitMaxEvals => write!(f, "hit max evals"),
+        }
+    }
 }
 
 
 // type FitResult = Option<Fit>;
-pub type FitResult = Result<Fit, &'static str>;
+pub type FitResult = Result<Fit, FitError>;
 
 
 
@@ -43,27 +219,46 @@ pub enum FitAlgorithmVariant {
     PatternSearch(PatternSearch),
     PatternSearchAdaptiveStep(PatternSearchAdaptiveStep),
     PatternSearchScaledStep(PatternSearchScaledStep),
+    LevenbergMarquardt(LevenbergMarquardt),
+    NelderMead(NelderMead),
 }
 
 impl FitAlgorithmVariant {
-    pub fn fit(&self, deconvolution_data: &DeconvolutionData, initial_params: Params) -> FitResult {
+    pub fn fit(&self, fit_data: &impl FitData, initial_params: Params) -> FitResult {
         match self {
-            Self::DifferentialEvolution(dev)      => dev.fit(deconvolution_data),
-            Self::PatternSearch(psv)              => psv.fit(deconvolution_data, initial_params.into()),
-            Self::PatternSearchAdaptiveStep(psas) => psas.fit(deconvolution_data, initial_params.into()),
-            Self::PatternSearchScaledStep(psss)   => psss.fit(deconvolution_data, initial_params.into()),
+            Self::DifferentialEvolution(dev)      => dev.fit(fit_data),
+            Self::PatternSearch(psv)              => psv.fit(fit_data, initial_params.into()),
+            Self::PatternSearchAdaptiveStep(psas) => psas.fit(fit_data, initial_params.into()),
+            Self::PatternSearchScaledStep(psss)   => psss.fit(fit_data, initial_params.into()),
+            Self::LevenbergMarquardt(lm)          => lm.fit(fit_data, initial_params.into()),
+            Self::NelderMead(nm)                  => nm.fit(fit_data, initial_params.into()),
         }
     }
+
+    /// Same structure, as in config: `{ <fit_algorithm_name> = { <params> } }`.
+    pub fn to_toml_table(&self) -> TomlTable {
+        let (name, params) = match self {
+            Self::DifferentialEvolution(dev)      => (DifferentialEvolution::TOML_NAME, dev.to_toml_table()),
+            Self::PatternSearch(psv)              => (PatternSearch::TOML_NAME, psv.to_toml_table()),
+            Self::PatternSearchAdaptiveStep(psas) => (PatternSearchAdaptiveStep::TOML_NAME, psas.to_toml_table()),
+            Self::PatternSearchScaledStep(psss)   => (PatternSearchScaledStep::TOML_NAME, psss.to_toml_table()),
+            Self::LevenbergMarquardt(lm)          => (LevenbergMarquardt::TOML_NAME, lm.to_toml_table()),
+            Self::NelderMead(nm)                  => (NelderMead::TOML_NAME, nm.to_toml_table()),
+        };
+        TomlTable::from_iter([(name.to_string(), TomlValue::Table(params))])
+    }
 }
 
 impl Load for FitAlgorithmVariant {
     const TOML_NAME: &'static str = "fit_algorithm";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        const FIT_ALGORITHMS_NAMES: [&'static str; 4] = [
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        const FIT_ALGORITHMS_NAMES: [&'static str; 6] = [
             DifferentialEvolution::TOML_NAME,
             PatternSearch::TOML_NAME,
             PatternSearchAdaptiveStep::TOML_NAME,
             PatternSearchScaledStep::TOML_NAME,
+            LevenbergMarquardt::TOML_NAME,
+            NelderMead::TOML_NAME,
         ];
         let fit_algorithms = FIT_ALGORITHMS_NAMES
             .map(|fa_name| toml_value.get(fa_name));
@@ -72,16 +267,16 @@ impl Load for FitAlgorithmVariant {
             .flatten() // flatten on Iter<Option/Result> gives only Some/Ok variants and unwraps them
             .count();
         match fit_algorithms_number.cmp(&1) {
-            // TODO: maybe somehow get first entry from table and use `panic_unknown_type` with value
-            Ordering::Less    => stacktrace.panic_unknown_type_without_value(FIT_ALGORITHMS_NAMES),
-            Ordering::Greater => stacktrace.panic_more_than_one_found(
+            // TODO: maybe somehow get first entry from table and use `err_unknown_type` with value
+            Ordering::Less    => return Err(stacktrace.err_unknown_type_without_value(FIT_ALGORITHMS_NAMES)),
+            Ordering::Greater => return Err(stacktrace.err_more_than_one_found(
                 fit_algorithms
                     .iter()
                     .zip(FIT_ALGORITHMS_NAMES)
                     .map(|(ofa, fan)| ofa.map(|_| fan))
                     .flatten()
                     .collect::<Vec<_>>()
-            ),
+            )),
             Ordering::Equal => {}
         }
         let fit_algorithm_index = fit_algorithms
@@ -91,13 +286,87 @@ impl Load for FitAlgorithmVariant {
         let toml_value = fit_algorithms[fit_algorithm_index].unwrap();
         // TODO(refactor): dont use numbers, bc they must be kept in sync with `DECONVOLUTION_FUNCTIONS_NAMES`
         // - maybe create vec of [PerPoint, Exponents, ...] and try load by them?
-        match fit_algorithm_index {
-            0 => Self::DifferentialEvolution(DifferentialEvolution::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            1 => Self::PatternSearch(PatternSearch::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            2 => Self::PatternSearchAdaptiveStep(PatternSearchAdaptiveStep::load_from_self_handle_stacktrace(toml_value, stacktrace)),
-            3 => Self::PatternSearchScaledStep(PatternSearchScaledStep::load_from_self_handle_stacktrace(toml_value, stacktrace)),
+        Ok(match fit_algorithm_index {
+            0 => Self::DifferentialEvolution(DifferentialEvolution::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            1 => Self::PatternSearch(PatternSearch::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            2 => Self::PatternSearchAdaptiveStep(PatternSearchAdaptiveStep::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            3 => Self::PatternSearchScaledStep(PatternSearchScaledStep::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            4 => Self::LevenbergMarquardt(LevenbergMarquardt::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
+            5 => Self::NelderMead(NelderMead::load_from_self_handle_stacktrace(toml_value, stacktrace)?),
             _ => unreachable!()
-        }
+        })
     }
 }
 
+
+
+
+#[cfg(test)]
+mod calc_covariance_from_jacobian {
+    use crate::types::{float::float, linalg::DMat, named_wrappers::ParamsG};
+    use super::{Fit, calc_covariance_from_jacobian};
+
+    /// Jacobian of linear model `a + b x` at `x = 0..5`.
+    fn jacobian_linear() -> DMat {
+        DMat::from_fn(5, 2, |i, j| if j == 0 { 1. } else { i as float })
+    }
+
+    fn assert_matrix_eq(expected: DMat, actual: DMat) {
+        assert_eq!(expected.shape(), actual.shape());
+        assert!((&expected - &actual).abs().max() < 1e-12, "expected: {expected}\nactual: {actual}");
+    }
+
+    #[test]
+    fn linear() {
+        assert_matrix_eq(
+            DMat::from_row_slice(2, 2, &[0.6, -0.2, -0.2, 0.1]),
+            calc_covariance_from_jacobian(&jacobian_linear(), 3., 2, false).unwrap(),
+        );
+    }
+
+    #[test]
+    fn residuals_in_sigmas_are_not_rescaled() {
+        assert_matrix_eq(
+            DMat::from_row_slice(2, 2, &[6., -2., -2., 1.]),
+            calc_covariance_from_jacobian(&jacobian_linear(), 30., 2, false).unwrap(),
+        );
+        assert_matrix_eq(
+            DMat::from_row_slice(2, 2, &[0.6, -0.2, -0.2, 0.1]),
+            calc_covariance_from_jacobian(&jacobian_linear(), 30., 2, true).unwrap(),
+        );
+    }
+
+    #[test]
+    fn with_fixed_param() {
+        let jacobian = jacobian_linear().insert_column(1, 0.);
+        assert_matrix_eq(
+            DMat::from_row_slice(3, 3, &[0.6, 0., -0.2, 0., 0., 0., -0.2, 0., 0.1]),
+            calc_covariance_from_jacobian(&jacobian, 3., 2, false).unwrap(),
+        );
+    }
+
+    #[test]
+    fn too_few_points() {
+        assert_eq!(None, calc_covariance_from_jacobian(&jacobian_linear().rows(0, 2).into_owned(), 3., 2, false));
+    }
+
+    #[test]
+    fn standard_errors_and_correlation_matrix() {
+        let fit = Fit {
+            params: ParamsG(vec![0., 0., 0.]),
+            fit_residue: 3.,
+            fit_residue_evals: 0,
+            covariance: calc_covariance_from_jacobian(&jacobian_linear().insert_column(1, 0.), 3., 2, false),
+            regularization_lambda: None,
+        };
+        let standard_errors = fit.get_standard_errors().unwrap();
+        for (expected, actual) in [0.6_f64.sqrt(), 0., 0.1_f64.sqrt()].iter().zip(&standard_errors) {
+            assert!((expected - actual).abs() < 1e-12, "expected: {expected}, actual: {actual}");
+        }
+        let c = -0.2 / (0.6_f64 * 0.1).sqrt();
+        assert_matrix_eq(
+            DMat::from_row_slice(3, 3, &[1., 0., c, 0., 0., 0., c, 0., 1.]),
+            fit.get_correlation_matrix().unwrap(),
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Nelder–Mead (downhill simplex) algorithm.
+
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use toml::{Table as TomlTable, Value as TomlValue};
+
+use crate::{
+    config::ExtTomlValueLoadPrimitives,
+    error::Result,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::{float::float, named_wrappers::{ParamsG, ParamsV}},
+};
+
+use super::{Fit, FitData, FitError, FitResult};
+
+
+/// Params outside of domain are projected onto it (moved to the closest boundary),
+/// and if they still aren't ok, residue at them is considered infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NelderMead {
+    /// Minimal simplex size (max distance by any param from the best vertex to others).
+    fit_algorithm_min_step: float,
+    fit_residue_evals_max: u64,
+    /// Initial simplex edge length.
+    initial_step: float,
+}
+
+impl NelderMead {
+    const REFLECTION: float = 1.;
+    const EXPANSION: float = 2.;
+    const CONTRACTION: float = 0.5;
+    const SHRINK: float = 0.5;
+
+    pub fn fit(&self, fit_data: &impl FitData, initial_params: ParamsV) -> FitResult {
+        const DEBUG: bool = false;
+
+        let Self { fit_algorithm_min_step, fit_residue_evals_max, initial_step } = *self;
+
+        let f_params_amount: usize = initial_params.0.len();
+        if f_params_amount == 0 {
+            return Err(FitError::TooFewParams);
+        }
+        let fit_data_prepared = fit_data.prepare();
+
+        // returns tuple of `residue_function_evals` and `residue_result`.
+        let calc_residue = |params: &ParamsV| -> (u64, float) {
+            if !fit_data.is_params_ok_v(params) || params.0.iter().any(|p| !p.is_finite()) {
+                (0, float::INFINITY)
+            } else {
+                let residue = fit_data.calc_residue_function_v(params, &fit_data_prepared);
+                (1, if residue.is_finite() { residue } else { float::INFINITY })
+            }
+        };
+        let project = |params: ParamsV| -> ParamsV {
+            fit_data.clamp_params_v(&params)
+        };
+
+        let mut fit_residue_evals: u64 = 0;
+
+        let initial_params: ParamsV = project(initial_params);
+        // initial simplex: shift each param by `initial_step`, backwards if it can't be moved forward,
+        // and don't shift it at all if it can't be moved (e.g. it's fixed).
+        let mut simplex: Vec<ParamsV> = vec![initial_params.clone()];
+        for i in 0..f_params_amount {
+            for delta in [initial_step, -initial_step] {
+                let mut params_shifted = initial_params.clone();
+                params_shifted.0[i] += delta;
+                let params_shifted = project(params_shifted);
+                if params_shifted.0[i] != initial_params.0[i] {
+                    simplex.push(params_shifted);
+                    break;
+                }
+            }
+        }
+        if simplex.len() == 1 {
+            return Err(FitError::TooFewParams);
+        }
+
+        let (fit_residue_evals_extra, mut residues): (Vec<u64>, Vec<float>) = simplex
+            .clone()
+            .into_par_iter()
+            .map(|params| calc_residue(&params))
+            .unzip();
+        fit_residue_evals += fit_residue_evals_extra.iter().sum::<u64>();
+        if !residues[0].is_finite() { return Err(FitError::ResidueIsNotFinite) }
+
+        loop {
+            // sort vertices from the best to the worst:
+            let mut indices: Vec<usize> = (0..simplex.len()).collect();
+            indices.sort_by(|&i, &j| residues[i].total_cmp(&residues[j]));
+            simplex = indices.iter().map(|&i| simplex[i].clone()).collect();
+            residues = indices.iter().map(|&i| residues[i]).collect();
+
+            let simplex_size: float = simplex[1..].iter()
+                .map(|params| (&params.0 - &simplex[0].0).amax())
+                .fold(0., float::max);
+            if DEBUG {
+                println!("params = {:#?}", simplex[0]);
+                println!("residue = {}", residues[0]);
+                println!("simplex_size = {}", simplex_size);
+            }
+            if simplex_size <= fit_algorithm_min_step { break }
+            if fit_residue_evals >= fit_residue_evals_max {
+                return Err(FitError::HitMaxEvals);
+            }
+
+            let index_worst: usize = simplex.len() - 1;
+            let res_worst: float = residues[index_worst];
+            let res_second_worst: float = residues[index_worst - 1];
+            let res_best: float = residues[0];
+            let centroid: ParamsV = ParamsV(
+                simplex[..index_worst].iter()
+                    .fold(simplex[0].0.scale(0.), |acc, params| acc + &params.0)
+                    / (index_worst as float)
+            );
+            let point_on_line = |k: float| -> ParamsV {
+                project(ParamsV(&centroid.0 + (&centroid.0 - &simplex[index_worst].0) * k))
+            };
+
+            let params_reflected = point_on_line(Self::REFLECTION);
+            let (fit_residue_evals_extra, res_reflected) = calc_residue(&params_reflected);
+            fit_residue_evals += fit_residue_evals_extra;
+
+            let mut params_and_res_new: Option<(ParamsV, float)> = None;
+            if res_reflected < res_best {
+                let params_expanded = point_on_line(Self::REFLECTION * Self::EXPANSION);
+                let (fit_residue_evals_extra, res_expanded) = calc_residue(&params_expanded);
+                fit_residue_evals += fit_residue_evals_extra;
+                params_and_res_new = Some(if res_expanded < res_reflected {
+                    (params_expanded, res_expanded)
+                } else {
+                    (params_reflected, res_reflected)
+                });
+            } else if res_reflected < res_second_worst {
+                params_and_res_new = Some((params_reflected, res_reflected));
+            } else if res_reflected < res_worst {
+                let params_contracted = point_on_line(Self::REFLECTION * Self::CONTRACTION);
+                let (fit_residue_evals_extra, res_contracted) = calc_residue(&params_contracted);
+                fit_residue_evals += fit_residue_evals_extra;
+                if res_contracted <= res_reflected {
+                    params_and_res_new = Some((params_contracted, res_contracted));
+                }
+            } else {
+                let params_contracted = point_on_line(-Self::CONTRACTION);
+                let (fit_residue_evals_extra, res_contracted) = calc_residue(&params_contracted);
+                fit_residue_evals += fit_residue_evals_extra;
+                if res_contracted < res_worst {
+                    params_and_res_new = Some((params_contracted, res_contracted));
+                }
+            }
+
+            match params_and_res_new {
+                Some((params_new, res_new)) => {
+                    simplex[index_worst] = params_new;
+                    residues[index_worst] = res_new;
+                }
+                None => {
+                    if DEBUG { println!("SHRINK") }
+                    let params_best: ParamsV = simplex[0].clone();
+                    let (fit_residue_evals_extra, params_and_ress_shrinked): (Vec<u64>, Vec<(ParamsV, float)>) = simplex[1..]
+                        .to_vec()
+                        .into_par_iter()
+                        .map(|params| {
+                            let params_shrinked = project(ParamsV(&params_best.0 + (&params.0 - &params_best.0) * Self::SHRINK));
+                            let (fit_residue_evals, res_shrinked) = calc_residue(&params_shrinked);
+                            (fit_residue_evals, (params_shrinked, res_shrinked))
+                        })
+                        .unzip();
+                    fit_residue_evals += fit_residue_evals_extra.iter().sum::<u64>();
+                    for (i, (params_shrinked, res_shrinked)) in params_and_ress_shrinked.into_iter().enumerate() {
+                        simplex[i+1] = params_shrinked;
+                        residues[i+1] = res_shrinked;
+                    }
+                }
+            }
+        }
+        if DEBUG { println!("finished in {} iters", fit_residue_evals) }
+        let fit_residue = residues[0];
+        let params = ParamsG::<float>(simplex.swap_remove(0).0.data.into());
+        Ok(Fit {
+            params,
+            fit_residue,
+            fit_residue_evals,
+            covariance: None,
+            regularization_lambda: None,
+        })
+    }
+}
+
+
+impl NelderMead {
+    /// Same keys, as in config.
+    pub fn to_toml_table(self) -> TomlTable {
+        let Self { fit_algorithm_min_step, fit_residue_evals_max, initial_step } = self;
+        let mut toml_table = TomlTable::new();
+        toml_table.insert("fit_algorithm_min_step".to_string(), fit_algorithm_min_step.into());
+        toml_table.insert("fit_residue_evals_max".to_string(), (fit_residue_evals_max as i64).into());
+        toml_table.insert("initial_step".to_string(), initial_step.into());
+        toml_table
+    }
+}
+
+impl Load for NelderMead {
+    const TOML_NAME: &'static str = "nelder_mead";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        Ok(Self {
+            fit_algorithm_min_step: toml_value.load_float("fit_algorithm_min_step", stacktrace)?,
+            fit_residue_evals_max: toml_value.load_u64("fit_residue_evals_max", stacktrace)?,
+            initial_step: toml_value.load_float("initial_step", stacktrace)?,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod fit {
+    use crate::deconvolution::{
+        test_fixtures::deconvolution_data_sat_exp_dec_exp,
+        types::{sat_exp__dec_exp::InitialValues_SatExp_DecExp, value_and_domain::ValueAndDomain},
+    };
+    use super::NelderMead;
+
+    const NELDER_MEAD: NelderMead = NelderMead {
+        fit_algorithm_min_step: 1e-8,
+        fit_residue_evals_max: 100_000,
+        initial_step: 1.,
+    };
+
+    #[test]
+    fn sat_exp_dec_exp() {
+        let params_true = [2., 20., 3., 15.];
+        let deconvolution_data = deconvolution_data_sat_exp_dec_exp(
+            InitialValues_SatExp_DecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::free(18.),
+                tau_a: ValueAndDomain::range_with_min(5., 0.),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+            },
+            params_true,
+        );
+        let fit = NELDER_MEAD.fit(&deconvolution_data, deconvolution_data.get_initial_params().into()).unwrap();
+        for (expected, actual) in params_true.iter().zip(&fit.params.0) {
+            assert!((expected - actual).abs() < 1e-4, "expected: {params_true:?}, actual: {:?}", fit.params.0);
+        }
+    }
+
+    #[test]
+    fn with_bounds_and_fixed_param() {
+        let params_true = [2., 20., 3., 15.];
+        let deconvolution_data = deconvolution_data_sat_exp_dec_exp(
+            InitialValues_SatExp_DecExp {
+                amplitude: ValueAndDomain::free(1.),
+                shift: ValueAndDomain::fixed(20.),
+                // true value is outside of domain, so it should end up at the boundary:
+                tau_a: ValueAndDomain::range_closed(5., (4., 10.)),
+                tau_b: ValueAndDomain::range_with_min(10., 0.),
+            },
+            params_true,
+        );
+        let fit = NELDER_MEAD.fit(&deconvolution_data, deconvolution_data.get_initial_params().into()).unwrap();
+        assert_eq!(20., fit.params.0[1]);
+        assert!((4. - fit.params.0[2]).abs() < 1e-6, "actual: {:?}", fit.params.0);
+    }
+}
@@ -1,18 +1,18 @@
 //! Pattern Search using step scaled by initial_params.
 
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use toml::Value as TomlValue;
+use toml::{Table as TomlTable, Value as TomlValue};
 
 use crate::{
-    deconvolution::deconvolution_data::DeconvolutionData,
+    error::Result,
     extensions::IndexOfMinWithCeil,
     load::Load,
     stacktrace::Stacktrace,
-    types::{float::float, named_wrappers::{Instrument, InstrumentRevV, Measured, MeasuredV, ParamsG, ParamsV}},
+    types::{float::float, named_wrappers::{ParamsG, ParamsV}},
     utils_io::press_enter_to_continue,
 };
 
-use super::{Fit, FitResult};
+use super::{Fit, FitData, FitError, FitResult};
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -27,7 +27,7 @@ pub struct PatternSearchScaledStep {
 }
 
 impl PatternSearchScaledStep {
-    pub fn fit(&self, deconvolution_data: &DeconvolutionData, initial_params: ParamsV) -> FitResult {
+    pub fn fit(&self, fit_data: &impl FitData, initial_params: ParamsV) -> FitResult {
         const DEBUG: bool = false;
 
         let Self { fit_algorithm_min_step, fit_residue_evals_max, initial_step, alpha, beta } = *self;
@@ -35,22 +35,20 @@ impl PatternSearchScaledStep {
 
         let f_params_amount: usize = initial_params.0.len();
         if f_params_amount == 0 {
-            return Err("too few params");
+            return Err(FitError::TooFewParams);
             // return None;
         }
-
-        let instrument_v_rev: InstrumentRevV = Instrument(deconvolution_data.instrument.points.clone()).into();
-        let measured_v: MeasuredV = Measured(deconvolution_data.measured.points.clone()).into();
+        let fit_data_prepared = fit_data.prepare();
 
         let initial_params: ParamsV = initial_params;
         let mut params: ParamsV = initial_params.clone();
         let mut step: float = initial_step;
         let mut fit_residue_evals: u64 = 0;
 
-        let mut res_at_current_params: float = deconvolution_data.calc_residue_function_v(&params, &instrument_v_rev, &measured_v);
+        let mut res_at_current_params: float = fit_data.calc_residue_function_v(&params, &fit_data_prepared);
         fit_residue_evals += 1;
         if DEBUG { println!("res_at_current_params = {}", res_at_current_params) }
-        if !res_at_current_params.is_finite() { return Err("`res_at_current_params` isn't finite") }
+        if !res_at_current_params.is_finite() { return Err(FitError::ResidueIsNotFinite) }
         // if !res_at_current_params.is_finite() { return None }
         // if res_at_current_params >= fit_residue_max_value { return Err("`res_at_current_params` is too big") }
 
@@ -72,10 +70,10 @@ impl PatternSearchScaledStep {
                     // TODO(optimization)?: remove `.is_finite()` check, bc it already will be "done" when calculating residue function.
                     let mut params_new = params.clone();
                     params_new.0[i/2] = param_new;
-                    if !deconvolution_data.is_params_ok_v(&params_new) || !param_new.is_finite() {
+                    if !fit_data.is_params_ok_v(&params_new) || !param_new.is_finite() {
                         (0, float::NAN)
                     } else {
-                        let residue = deconvolution_data.calc_residue_function_v(&params_new, &instrument_v_rev, &measured_v);
+                        let residue = fit_data.calc_residue_function_v(&params_new, &fit_data_prepared);
                         (1, if residue.is_finite() { residue } else { float::NAN })
                     }
                     // returns tuple of `residue_function_evals` and `residue_result`.
@@ -97,7 +95,7 @@ impl PatternSearchScaledStep {
 
                     res_at_current_params = ress_at_shifted_params[index_of_min];
                     if DEBUG { println!("res_at_current_params = {}", res_at_current_params) }
-                    if !res_at_current_params.is_finite() { return Err("`res_at_current_params` isn't finite") }
+                    if !res_at_current_params.is_finite() { return Err(FitError::ResidueIsNotFinite) }
                     // if !res_at_current_params.is_finite() { return None }
                     // if res_at_current_params >= fit_residue_max_value { return Err("`res_at_current_params` is too big") }
 
@@ -117,7 +115,7 @@ impl PatternSearchScaledStep {
                 println!("HIT MAX_ITERS!!!");
                 press_enter_to_continue();
             }
-            return Err("hit max evals");
+            return Err(FitError::HitMaxEvals);
             // return None;
         }
         if DEBUG { println!("finished in {} iters", fit_residue_evals) }
@@ -127,33 +125,49 @@ impl PatternSearchScaledStep {
             params,
             fit_residue,
             fit_residue_evals,
+            covariance: None,
+            regularization_lambda: None,
         })
     }
 }
 
 
+impl PatternSearchScaledStep {
+    /// Same keys, as in config.
+    pub fn to_toml_table(self) -> TomlTable {
+        let Self { fit_algorithm_min_step, fit_residue_evals_max, initial_step, alpha, beta } = self;
+        let mut toml_table = TomlTable::new();
+        toml_table.insert("fit_algorithm_min_step".to_string(), fit_algorithm_min_step.into());
+        toml_table.insert("fit_residue_evals_max".to_string(), (fit_residue_evals_max as i64).into());
+        toml_table.insert("initial_step".to_string(), initial_step.into());
+        toml_table.insert("alpha".to_string(), alpha.into());
+        if let Some(beta) = beta { toml_table.insert("beta".to_string(), beta.into()); }
+        toml_table
+    }
+}
+
 impl Load for PatternSearchScaledStep {
     const TOML_NAME: &'static str = "pattern_search_scaled_step";
-    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Self {
-        let load_float = |name: &'static str| -> float {
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let load_float = |name: &'static str| -> Result<float> {
             let stacktrace = stacktrace.pushed(name);
             toml_value
                 .get(name)
-                .unwrap_or_else(|| stacktrace.panic_not_found())
+                .ok_or_else(|| stacktrace.err_not_found())?
                 .as_float()
-                .unwrap_or_else(|| stacktrace.panic_cant_parse_as("float"))
+                .ok_or_else(|| stacktrace.err_cant_parse_as("float"))
         };
-        let load_u64 = |name: &'static str| -> u64 {
+        let load_u64 = |name: &'static str| -> Result<u64> {
             let stacktrace = stacktrace.pushed(name);
             let value = toml_value
                 .get(name)
-                .unwrap_or_else(|| stacktrace.panic_not_found())
+                .ok_or_else(|| stacktrace.err_not_found())?
                 .as_integer()
-                .unwrap_or_else(|| stacktrace.panic_cant_parse_as("int"));
+                .ok_or_else(|| stacktrace.err_cant_parse_as("int"))?;
             if !(u64::MIN as i128..=u64::MAX as i128).contains(&(value as i128)) {
-                stacktrace.panic_cant_parse_as("u64")
+                return Err(stacktrace.err_cant_parse_as("u64"));
             }
-            value as u64
+            Ok(value as u64)
         };
         let beta = {
             let name = "beta";
@@ -163,16 +177,17 @@ impl Load for PatternSearchScaledStep {
                 .map(|beta_toml_value| {
                     beta_toml_value
                         .as_float()
-                        .unwrap_or_else(|| stacktrace.panic_cant_parse_as("float"))
+                        .ok_or_else(|| stacktrace.err_cant_parse_as("float"))
                 })
+                .transpose()?
         };
-        Self {
-            fit_algorithm_min_step: load_float("fit_algorithm_min_step"),
-            fit_residue_evals_max: load_u64("fit_residue_evals_max"),
-            initial_step: load_float("initial_step"),
-            alpha: load_float("alpha"),
+        Ok(Self {
+            fit_algorithm_min_step: load_float("fit_algorithm_min_step")?,
+            fit_residue_evals_max: load_u64("fit_residue_evals_max")?,
+            initial_step: load_float("initial_step")?,
+            alpha: load_float("alpha")?,
             beta,
-        }
+        })
     }
 }
 
@@ -13,83 +13,156 @@
 
 use std::{
     env,
-    fs::File,
+    fs::{File, create_dir_all},
     io::{BufRead, BufReader},
     path::Path,
+    process::exit,
 };
 
 mod aliases_method_to_function;
 mod antispikes;
+mod cli;
 mod config;
 mod deconvolution;
 mod diff_function;
+mod error;
+mod expression;
 mod extensions;
 mod fit_algorithms;
 mod load;
 mod macros;
+mod result_file;
 mod spectrum;
 mod stacktrace;
+mod summary;
 mod types;
 mod utils_io;
 
-use config::Config;
-use deconvolution::deconvolution_data::DeconvolutionData;
+use cli::{CliArgs, Subcommand, USAGE};
+use config::{Config, ConfigGlobalFit};
+use deconvolution::{deconvolution_data::{DeconvolutionData, Weights}, global_deconvolution_data::GlobalDeconvolutionData};
+use error::{Error, Result};
 use extensions::{ToStringUnderscoreSeparated, ToStringWithSignificantDigits}; // TODO: use
 use fit_algorithms::Fit;
+use result_file::{ResultFormat, ResultInputs, build_result_toml_table, write_result_toml_table_to_file};
 use spectrum::Spectrum;
-use types::{float::float, named_wrappers::{Instrument, MeasuredV}};
+use summary::{SummaryFit, SummaryRow, write_summary_to_file};
+use types::{float::float, named_wrappers::{Instrument, MeasuredV, Params}};
 use utils_io::flush;
 
 
-// TODO: make `-> Result<(), TODO>`.
-fn main() {
-    let config = Config::load_from_default_file();
+fn main() -> Result<()> {
+    let cli_args: Vec<String> = env::args().skip(1).collect();
+    let cli_args: CliArgs = CliArgs::parse(&cli_args).unwrap_or_else(|err| {
+        eprintln!("error: {err}\n\n{USAGE}");
+        exit(2);
+    });
+    let verbosity = cli_args.verbosity;
 
-    let cli_args: Vec<_> = env::args().collect();
-    match cli_args.as_slice() {
-        [_, _] => panic!("Expected at least two filenames (instrumental & measured), provided only one."),
-        [_] => panic!("Expected at least two filenames (instrumental & measured), provided zero."),
-        [] => unreachable!("Unexpected CLI args number."),
-        _ => {}
+    if cli_args.subcommand == Subcommand::Help {
+        println!("{USAGE}");
+        return Ok(());
     }
-    let filepathstr_instrument: &str = &cli_args[1];
 
-    print!("Loading instrumental spectrum  from `{}`...", filepathstr_instrument); flush();
-    let instrument = Spectrum::load_from_file_as_instrumental(filepathstr_instrument, config.input_params.max_step_relative_diff);
-    let filepathstr_instrument_stem = Path::new(filepathstr_instrument)
-        .file_stem().unwrap().to_str().unwrap();
-    println!(" done");
+    let config = Config::load_from_file_with_overrides(&cli_args.config_filepath, &cli_args.config_overrides)?;
+    if verbosity.is_verbose() || (verbosity.is_normal_or_more() && cli_args.subcommand == Subcommand::CheckConfig) {
+        println!("config = {config:#?}");
+    }
 
-    for filepathstr_measured in cli_args[2..].iter() {
-        println!();
-        process_measured_file(
+    let (filepathstr_instrument, filepathstrs_measured, is_convolve_only): (&str, &[String], bool) = match &cli_args.subcommand {
+        Subcommand::Help => unreachable!(),
+        Subcommand::CheckConfig => {
+            if verbosity.is_normal_or_more() { println!("Config `{}` is ok.", cli_args.config_filepath) }
+            return Ok(());
+        }
+        Subcommand::Fit { filepathstr_instrument, filepathstrs_measured } => (filepathstr_instrument, filepathstrs_measured, false),
+        Subcommand::Convolve { filepathstr_instrument, filepathstrs_measured } => (filepathstr_instrument, filepathstrs_measured, true),
+    };
+
+    if let Some(output_dir) = &cli_args.output_dir {
+        create_dir_all(output_dir)
+            .map_err(|io_error| Error::OutputWrite { filepath: output_dir.display().to_string(), io_error })?;
+    }
+
+    if verbosity.is_normal_or_more() { print!("Loading instrumental spectrum  from `{}`...", filepathstr_instrument); flush() }
+    let instrument = Spectrum::load_from_file_as_instrumental(filepathstr_instrument, config.input_params.max_step_relative_diff)?;
+    if verbosity.is_normal_or_more() { println!(" done") }
+
+    let summary_rows: Vec<SummaryRow> = match &config.global_fit {
+        Some(global_fit) if !is_convolve_only => process_measured_files_globally(
             &config,
-            instrument.clone(),
-            filepathstr_instrument_stem,
-            filepathstr_measured,
+            &cli_args,
+            global_fit,
+            &instrument,
+            filepathstr_instrument,
+            filepathstrs_measured,
+        )?,
+        _ => {
+            let mut summary_rows: Vec<SummaryRow> = vec![];
+            for filepathstr_measured in filepathstrs_measured.iter() {
+                if verbosity.is_normal_or_more() { println!() }
+                // one bad measured file shouldn't stop processing of others, so error is reported and file is skipped.
+                let process_result = process_measured_file(
+                    &config,
+                    &cli_args,
+                    instrument.clone(),
+                    filepathstr_instrument,
+                    filepathstr_measured,
+                    is_convolve_only,
+                );
+                let summary_fit = process_result.unwrap_or_else(|err| {
+                    if verbosity.is_normal_or_more() { println!() }
+                    eprintln!("ERROR: {err}");
+                    eprintln!("Skipping `{filepathstr_measured}`.");
+                    None
+                });
+                summary_rows.push(SummaryRow { filepathstr_measured: filepathstr_measured.clone(), summary_fit });
+            }
+            summary_rows
+        }
+    };
+
+    if !is_convolve_only {
+        // summary is written into `output_dir`, if it's given, or near instrument file otherwise.
+        let summary_format = config.output_params.summary_format;
+        let filepath_instrument = Path::new(filepathstr_instrument);
+        let filename_summary = format!(
+            "summary_{stem}.{ext}",
+            stem=filepath_instrument.file_stem().unwrap().to_str().unwrap(),
+            ext=summary_format.get_extension(),
         );
+        let filepath_summary = match &cli_args.output_dir {
+            Some(output_dir) => output_dir.join(filename_summary),
+            None => filepath_instrument.with_file_name(filename_summary),
+        };
+        let filepathstr_summary = filepath_summary.to_str().unwrap();
+        write_summary_to_file(&summary_rows, summary_format, filepathstr_summary)?;
+        if verbosity.is_normal_or_more() {
+            println!();
+            println!("Summary written to `{filepathstr_summary}`.");
+        }
     }
+    Ok(())
 }
 
 
 fn process_measured_file(
     config: &Config,
+    cli_args: &CliArgs,
     instrument: Spectrum,
-    filepathstr_instrument_stem: &str,
+    filepathstr_instrument: &str,
     filepathstr_measured: &str,
-) {
-    print!("Loading spectrum to deconvolve from `{}`...", filepathstr_measured); flush();
-    let measured = Spectrum::load_from_file(filepathstr_measured, config.input_params.max_step_relative_diff);
-    println!(" done");
+    is_convolve_only: bool,
+) -> Result<Option<SummaryFit>> {
+    let verbosity = cli_args.verbosity;
 
-    // TODO: warning if points in instr more than in spectrum.
-    // assert!(measured.points.len() > instrument.points.len());
+    let deconvolution_data = load_deconvolution_data(config, cli_args, instrument, filepathstr_measured)?;
 
-    println!("Fit Algorithm = {:#?}", config.fit_algorithm);
+    if verbosity.is_normal_or_more() && !is_convolve_only { println!("Fit Algorithm = {:#?}", config.fit_algorithm) }
     // TODO: fit_algorithm.max_evals.to_string_underscore_separated
 
-    let file_spectrum = Path::new(filepathstr_measured);
-    let filepathstr_spectrum_stem = file_spectrum.file_stem().unwrap().to_str().unwrap();
+    let filepathstr_instrument_stem = Path::new(filepathstr_instrument).file_stem().unwrap().to_str().unwrap();
+    let filepathstr_spectrum_stem = Path::new(filepathstr_measured).file_stem().unwrap().to_str().unwrap();
 
     // TODO: fix: dont work on win7?
     // assert_eq!(
@@ -97,61 +170,67 @@ fn process_measured_file(
     //     file_spectrum  .parent().unwrap().canonicalize().unwrap().to_str().unwrap()
     // );
 
-    const FILENAME_PREFIX: &str = "result";
-
     let build_filepathstr_output = |randomized_initial_values_i: u64| -> String {
-        let riv = if randomized_initial_values_i == 0 { "".to_string() } else { format!("_riv{}", randomized_initial_values_i) };
-        let filepath_output = file_spectrum.with_file_name(format!(
-            "{FILENAME_PREFIX}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}{riv}.dat"
-        ));
-        let filepathstr_output: String = filepath_output.to_str().unwrap().to_string();
-        filepathstr_output
+        build_filepathstr_output_result(config, cli_args, filepathstr_instrument, filepathstr_measured, randomized_initial_values_i)
     };
 
-    let build_filepathstr_output_convolved = |randomized_initial_values_i: u64| -> String {
-        let riv = if randomized_initial_values_i == 0 { "".to_string() } else { format!("_riv{}", randomized_initial_values_i) };
-        let filepath_output_convolved = file_spectrum.with_file_name(format!(
-            "{FILENAME_PREFIX}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}{riv}_convolved.dat",
-        ));
-        let filepathstr_output_convolved: String = filepath_output_convolved.to_str().unwrap().to_string();
-        filepathstr_output_convolved
+    let result_inputs = ResultInputs {
+        filepathstr_instrument,
+        filepathstr_instrument_stem,
+        filepathstr_measured,
+        randomized_initial_values_i: 0,
     };
 
-    let deconvolution = config.deconvolution_function.clone();
-
-    let deconvolution_data: DeconvolutionData = DeconvolutionData {
-        instrument,
-        measured,
-        deconvolution,
-    }.aligned_steps_to(config.input_params.align_step_to);
+    let build_filepathstr_output_convolved = |randomized_initial_values_i: u64| -> String {
+        build_filepathstr_output_convolved(cli_args, filepathstr_instrument, filepathstr_measured, randomized_initial_values_i)
+    };
 
-    println!();
+    if verbosity.is_normal_or_more() { println!() }
     let fit_residue_with_initial_values = deconvolution_data.calc_residue_function_v(
         &deconvolution_data.get_initial_params().into(),
         &Instrument(deconvolution_data.instrument.points.clone()).into(),
         &MeasuredV(deconvolution_data.measured.points.clone().into()).into(),
     );
-    println!("fit_residue @ initial_values: {:.4}", fit_residue_with_initial_values);
-    println!();
+    if verbosity.is_normal_or_more() {
+        println!("fit_residue @ initial_values: {:.4}", fit_residue_with_initial_values);
+        println!();
+    }
+
+    if is_convolve_only {
+        let filepathstr_output_convolved = build_filepathstr_output_with_filename(cli_args, filepathstr_measured, format!(
+            "{FILENAME_PREFIX_CONVOLVE_ONLY}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}.dat",
+        ));
+        write_convolved_to_file(&deconvolution_data, &deconvolution_data.get_initial_params(), &filepathstr_output_convolved)?;
+        if verbosity.is_normal_or_more() { println!("Convolved spectrum written to `{filepathstr_output_convolved}`.") }
+        return Ok(None);
+    }
 
     let deconvolve_results = deconvolution_data.deconvolve(&config.fit_algorithm, None);
+    let mut best_summary_fit: Option<SummaryFit> = None;
     match deconvolve_results {
-        Err(err) => println!("ERROR: {}", err),
+        Err(err) => eprintln!("ERROR: {}", err),
         Ok(ref deconvolution_results_unwrapped) => {
+            best_summary_fit = Some(SummaryFit::new(&deconvolution_data, deconvolution_results_unwrapped, 0));
             output_results(
                 &config,
+                cli_args,
                 &deconvolution_data,
                 deconvolution_results_unwrapped,
+                &result_inputs,
                 &build_filepathstr_output(0),
                 &build_filepathstr_output_convolved(0),
-            );
+            )?;
         }
     }
-    if config.deconvolution_params.try_randomized_initial_values == 0 { return }
+    if config.deconvolution_params.try_randomized_initial_values == 0 { return Ok(best_summary_fit) }
+    // params are calculated directly, so they don't depend on initial values:
+    if !deconvolution_data.deconvolution.is_fitted() { return Ok(best_summary_fit) }
 
-    println!();
-    println!("------- NOW TRYING RANDOM INITIAL VALUES -------");
-    println!();
+    if verbosity.is_normal_or_more() {
+        println!();
+        println!("------- NOW TRYING RANDOM INITIAL VALUES -------");
+        println!();
+    }
 
     let mut best_fit_residue: float = if deconvolve_results.is_ok() { deconvolve_results.unwrap().fit_residue } else { float::MAX };
     for randomized_initial_values_i in 1..=config.deconvolution_params.try_randomized_initial_values {
@@ -162,19 +241,24 @@ fn process_measured_file(
         match deconvolution_results {
             Ok(deconvolution_results_unwrapped) if deconvolution_results_unwrapped.fit_residue < best_fit_residue => {
                 best_fit_residue = deconvolution_results_unwrapped.fit_residue;
-                println!("{}", "-".repeat(42));
-                println!("initial values tried: {}", randomized_initial_values_i);
+                best_summary_fit = Some(SummaryFit::new(&deconvolution_data, &deconvolution_results_unwrapped, randomized_initial_values_i));
+                if verbosity.is_normal_or_more() {
+                    println!("{}", "-".repeat(42));
+                    println!("initial values tried: {}", randomized_initial_values_i);
+                }
                 // dbg!(initial_values);
                 output_results(
                     &config,
+                    cli_args,
                     &deconvolution_data,
                     &deconvolution_results_unwrapped,
+                    &ResultInputs { randomized_initial_values_i, ..result_inputs },
                     &build_filepathstr_output(randomized_initial_values_i),
                     &build_filepathstr_output_convolved(randomized_initial_values_i),
-                );
-                println!("{}", "-".repeat(42));
+                )?;
+                if verbosity.is_normal_or_more() { println!("{}", "-".repeat(42)) }
             }
-            _ if !config.deconvolution_params.print_only_better_deconvolution => {
+            _ if verbosity.is_verbose() || (verbosity.is_normal_or_more() && !config.deconvolution_params.print_only_better_deconvolution) => {
                 println!(
                     "fit_residue: {}",
                     deconvolution_results.as_ref()
@@ -185,41 +269,251 @@ fn process_measured_file(
             _ => {}
         }
     }
+    Ok(best_summary_fit)
+}
+
+
+fn load_deconvolution_data(
+    config: &Config,
+    cli_args: &CliArgs,
+    instrument: Spectrum,
+    filepathstr_measured: &str,
+) -> Result<DeconvolutionData> {
+    let verbosity = cli_args.verbosity;
+
+    if verbosity.is_normal_or_more() { print!("Loading spectrum to deconvolve from `{}`...", filepathstr_measured); flush() }
+    let (measured, sigmas) = Spectrum::load_from_file_with_sigmas(filepathstr_measured, config.input_params.max_step_relative_diff)?;
+    let sigmas = match config.input_params.weights {
+        Weights::None | Weights::Poisson => None,
+        Weights::Sigmas => Some(sigmas.ok_or_else(|| Error::SpectrumInvalid {
+            filepath: filepathstr_measured.to_string(),
+            msg: "`weights` is `sigmas`, but file don't have third column with sigmas".to_string(),
+        })?),
+    };
+    if verbosity.is_normal_or_more() { println!(" done") }
+
+    // TODO: warning if points in instr more than in spectrum.
+    // assert!(measured.points.len() > instrument.points.len());
+
+    let deconvolution = config.deconvolution_function.clone();
+
+    let deconvolution_data: DeconvolutionData = DeconvolutionData {
+        instrument,
+        measured,
+        sigmas,
+        fit_mask: config.input_params.fit_mask.clone(),
+        irf_params: config.deconvolution_params.irf_params,
+        add_ons: config.deconvolution_params.add_ons,
+        deconvolution,
+        convolution_method: config.deconvolution_params.convolution_method,
+    }
+        .aligned_steps_to(config.input_params.align_step_to)
+        .sized_to_measured();
+    let deconvolution_data = match config.input_params.weights {
+        Weights::None | Weights::Sigmas => deconvolution_data,
+        Weights::Poisson => deconvolution_data.with_poisson_sigmas(),
+    };
+    if deconvolution_data.get_fit_points_len() == 0 {
+        return Err(Error::SpectrumInvalid {
+            filepath: filepathstr_measured.to_string(),
+            msg: "no points left in fit after `fit_window` and `fit_exclude`".to_string(),
+        });
+    }
+    Ok(deconvolution_data)
+}
+
+
+const FILENAME_PREFIX: &str = "result";
+const FILENAME_PREFIX_CONVOLVE_ONLY: &str = "convolved";
+
+/// Output files are written into `output_dir`, if it's given, or near `filepathstr_near` otherwise.
+fn build_filepathstr_output_with_filename(cli_args: &CliArgs, filepathstr_near: &str, filename: String) -> String {
+    let filepath_output = match &cli_args.output_dir {
+        Some(output_dir) => output_dir.join(filename),
+        None => Path::new(filepathstr_near).with_file_name(filename),
+    };
+    filepath_output.to_str().unwrap().to_string()
+}
+
+fn build_filepathstr_output_result(
+    config: &Config,
+    cli_args: &CliArgs,
+    filepathstr_instrument: &str,
+    filepathstr_measured: &str,
+    randomized_initial_values_i: u64,
+) -> String {
+    let filepathstr_instrument_stem = Path::new(filepathstr_instrument).file_stem().unwrap().to_str().unwrap();
+    let filepathstr_spectrum_stem = Path::new(filepathstr_measured).file_stem().unwrap().to_str().unwrap();
+    let riv = if randomized_initial_values_i == 0 { "".to_string() } else { format!("_riv{}", randomized_initial_values_i) };
+    let result_format = config.output_params.result_format;
+    let ext = result_format.get_extension();
+    build_filepathstr_output_with_filename(cli_args, filepathstr_measured, match result_format {
+        ResultFormat::Txt => format!("{FILENAME_PREFIX}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}{riv}.{ext}"),
+        // one file per measured spectrum, so it's overwritten by better fits.
+        ResultFormat::Toml | ResultFormat::Json => format!("{FILENAME_PREFIX}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}.{ext}"),
+    })
+}
+
+fn build_filepathstr_output_convolved(
+    cli_args: &CliArgs,
+    filepathstr_instrument: &str,
+    filepathstr_measured: &str,
+    randomized_initial_values_i: u64,
+) -> String {
+    let filepathstr_instrument_stem = Path::new(filepathstr_instrument).file_stem().unwrap().to_str().unwrap();
+    let filepathstr_spectrum_stem = Path::new(filepathstr_measured).file_stem().unwrap().to_str().unwrap();
+    let riv = if randomized_initial_values_i == 0 { "".to_string() } else { format!("_riv{}", randomized_initial_values_i) };
+    build_filepathstr_output_with_filename(cli_args, filepathstr_measured, format!(
+        "{FILENAME_PREFIX}_{filepathstr_instrument_stem}_{filepathstr_spectrum_stem}{riv}_convolved.dat",
+    ))
+}
+
+
+/// Fit all measured files together, with params from `global_fit` shared between them.
+///
+/// Results of each measured file are written same as when it's fitted alone,
+/// shared params and local params of all files (decay-associated spectra) are written near instrument file.
+fn process_measured_files_globally(
+    config: &Config,
+    cli_args: &CliArgs,
+    global_fit: &ConfigGlobalFit,
+    instrument: &Spectrum,
+    filepathstr_instrument: &str,
+    filepathstrs_measured: &[String],
+) -> Result<Vec<SummaryRow>> {
+    let verbosity = cli_args.verbosity;
+
+    let mut summary_rows: Vec<SummaryRow> = vec![];
+    let mut datasets: Vec<DeconvolutionData> = vec![];
+    let mut filepathstrs_measured_loaded: Vec<String> = vec![];
+    for filepathstr_measured in filepathstrs_measured.iter() {
+        if verbosity.is_normal_or_more() { println!() }
+        // one bad measured file shouldn't stop processing of others, so error is reported and file is skipped.
+        match load_deconvolution_data(config, cli_args, instrument.clone(), filepathstr_measured) {
+            Ok(deconvolution_data) => {
+                datasets.push(deconvolution_data);
+                filepathstrs_measured_loaded.push(filepathstr_measured.clone());
+            }
+            Err(err) => {
+                if verbosity.is_normal_or_more() { println!() }
+                eprintln!("ERROR: {err}");
+                eprintln!("Skipping `{filepathstr_measured}`.");
+            }
+        }
+        summary_rows.push(SummaryRow { filepathstr_measured: filepathstr_measured.clone(), summary_fit: None });
+    }
+    if datasets.is_empty() { return Ok(summary_rows) }
+
+    let global_deconvolution_data = GlobalDeconvolutionData::new(datasets, &global_fit.shared_params)?;
+    if verbosity.is_normal_or_more() {
+        println!();
+        println!("Global fit of {} measured spectra, shared params: {:?}", filepathstrs_measured_loaded.len(), global_deconvolution_data.get_shared_params_names());
+        println!("Fit Algorithm = {:#?}", config.fit_algorithm);
+    }
+
+    let mut best_fit: Option<(Fit, u64)> = None;
+    for randomized_initial_values_i in 0..=config.deconvolution_params.try_randomized_initial_values {
+        let initial_values_random_scale = (randomized_initial_values_i != 0).then_some(config.deconvolution_params.initial_values_random_scale);
+        match global_deconvolution_data.deconvolve(&config.fit_algorithm, initial_values_random_scale) {
+            Ok(fit) if best_fit.as_ref().map_or(true, |(best_fit, _)| fit.fit_residue < best_fit.fit_residue) => {
+                if verbosity.is_normal_or_more() {
+                    println!("initial values tried: {randomized_initial_values_i}, fit_residue: {:.4}", fit.fit_residue);
+                }
+                best_fit = Some((fit, randomized_initial_values_i));
+            }
+            fit_result if verbosity.is_verbose() || (verbosity.is_normal_or_more() && !config.deconvolution_params.print_only_better_deconvolution) => {
+                println!(
+                    "fit_residue: {}",
+                    fit_result.as_ref()
+                        .map(|fit| format!("{:.4}", fit.fit_residue))
+                        .unwrap_or_else(|err| format!("Error: {err}"))
+                );
+            }
+            _ => {}
+        }
+    }
+    let Some((fit, randomized_initial_values_i)) = best_fit else {
+        eprintln!("ERROR: global fit failed for all initial values");
+        return Ok(summary_rows);
+    };
+
+    for (dataset_i, (deconvolution_data, filepathstr_measured)) in global_deconvolution_data.datasets.iter().zip(&filepathstrs_measured_loaded).enumerate() {
+        if verbosity.is_normal_or_more() {
+            println!();
+            println!("Results for `{filepathstr_measured}`:");
+        }
+        let dataset_fit = global_deconvolution_data.get_dataset_fit(&fit, dataset_i);
+        output_results(
+            config,
+            cli_args,
+            deconvolution_data,
+            &dataset_fit,
+            &ResultInputs {
+                filepathstr_instrument,
+                filepathstr_instrument_stem: Path::new(filepathstr_instrument).file_stem().unwrap().to_str().unwrap(),
+                filepathstr_measured,
+                randomized_initial_values_i,
+            },
+            &build_filepathstr_output_result(config, cli_args, filepathstr_instrument, filepathstr_measured, 0),
+            &build_filepathstr_output_convolved(cli_args, filepathstr_instrument, filepathstr_measured, 0),
+        )?;
+        let summary_row = summary_rows.iter_mut().find(|summary_row| &summary_row.filepathstr_measured == filepathstr_measured).unwrap();
+        summary_row.summary_fit = Some(SummaryFit::new(deconvolution_data, &dataset_fit, randomized_initial_values_i));
+    }
+
+    let filepathstr_output_global = build_filepathstr_output_with_filename(cli_args, filepathstr_instrument, format!(
+        "{FILENAME_PREFIX}_global_{stem}.txt",
+        stem=Path::new(filepathstr_instrument).file_stem().unwrap().to_str().unwrap(),
+    ));
+    global_deconvolution_data.write_result_to_file(&filepathstr_output_global, &fit, &filepathstrs_measured_loaded)?;
+    if verbosity.is_normal_or_more() {
+        println!();
+        println!("Global fit results written to `{filepathstr_output_global}`.");
+    }
+    Ok(summary_rows)
 }
 
 
 fn output_results(
     config: &Config,
+    cli_args: &CliArgs,
     deconvolution_data: &DeconvolutionData,
     deconvolution_results: &Fit,
+    result_inputs: &ResultInputs,
     filepathstr_output: &str,
     filepathstr_output_convolved: &str,
-) {
-    println!("deconvolution_results = {deconvolution_results:#?}");
+) -> Result<()> {
+    let verbosity = cli_args.verbosity;
+    if verbosity.is_normal_or_more() { println!("deconvolution_results = {deconvolution_results:#?}") }
     // println!("fit_residue_evals = {}", deconvolution_results.fit_residue_evals.to_string_underscore_separated());
 
-    let params = &deconvolution_results.params;
+    let params = &deconvolution_data.get_model_params(&deconvolution_results.params);
     let significant_digits = config.output_params.significant_digits;
 
     let fit_residue_str = deconvolution_results.fit_residue.to_string_with_significant_digits(significant_digits);
-    // let chi_squared = deconvolution_data.calc_chi_squared(deconvolution_results).to_string_with_significant_digits(significant_digits);
+    let chi_square_str = deconvolution_data.calc_chi_square(deconvolution_results).to_string_with_significant_digits(significant_digits);
+    let cash_statistic_str = deconvolution_data.calc_cash_statistic(deconvolution_results).map(|c| c.to_string_with_significant_digits(significant_digits));
     let reduced_chi_square_str = deconvolution_data.calc_reduced_chi_square(deconvolution_results).to_string_with_significant_digits(significant_digits);
     let r_square = deconvolution_data.calc_r_square(deconvolution_results).to_string_with_significant_digits(significant_digits);
     let adjusted_r_square = deconvolution_data.calc_adjusted_r_square(deconvolution_results).to_string_with_significant_digits(significant_digits);
 
     let desmos_function_str = deconvolution_data.deconvolution.to_desmos_function(params, significant_digits);
-    if let Ok(ref desmos_function_str) = desmos_function_str {
+    if let (Ok(desmos_function_str), true) = (&desmos_function_str, verbosity.is_normal_or_more()) {
         println!("desmos function:");
         println!("{desmos_function_str}");
         println!("\"fit residue: {fit_residue_str}");
+        println!("\"chi squared: {chi_square_str}");
         println!("\"reduced chi squared: {reduced_chi_square_str}");
+        if let Some(cash_statistic_str) = &cash_statistic_str {
+            println!("\"cash statistic: {cash_statistic_str}");
+        }
         println!("\"r square: {r_square}");
         println!("\"adjusted r square: {adjusted_r_square}");
         println!();
     }
 
     let origin_function_str = deconvolution_data.deconvolution.to_origin_function(params, significant_digits);
-    if let Ok(ref origin_function_str) = origin_function_str {
+    if let (Ok(origin_function_str), true) = (&origin_function_str, verbosity.is_normal_or_more()) {
         println!("origin function:");
         println!("{origin_function_str}");
     }
@@ -231,24 +525,52 @@ fn output_results(
     //     writeln!(file_output, "{x}\t{p}", p=point).unwrap();
     // }
     let fit_goodness_msg: String = [
-        format!("fit goodness (achieved after {fre} fit residue function evals):", fre=deconvolution_results.fit_residue_evals),
-        format!("- fit residue: {fit_residue_str}"),
-        format!("- reduced chi square: {reduced_chi_square_str}"),
-        format!("- r square: {r_square}"),
-        format!("- adjusted r square: {adjusted_r_square}"),
+        Some(format!("fit goodness (achieved after {fre} fit residue function evals):", fre=deconvolution_results.fit_residue_evals)),
+        Some(format!("- fit residue: {fit_residue_str}")),
+        Some(format!("- chi square: {chi_square_str}")),
+        Some(format!("- reduced chi square: {reduced_chi_square_str}")),
+        cash_statistic_str.map(|cash_statistic_str| format!("- cash statistic (poisson deviance): {cash_statistic_str}")),
+        Some(format!("- r square: {r_square}")),
+        Some(format!("- adjusted r square: {adjusted_r_square}")),
     ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
         .join("\n");
-    deconvolution_data.write_result_to_file(
-        filepathstr_output,
-        &fit_goodness_msg,
-        params,
-        desmos_function_str,
-        origin_function_str,
-        &config.fit_algorithm,
-    );
+    if verbosity.is_normal_or_more() {
+        match deconvolution_data.build_params_uncertainties_msg(deconvolution_results, Some(significant_digits)) {
+            Some(params_uncertainties_msg) => println!("{params_uncertainties_msg}"),
+            None => println!("params uncertainties can't be estimated"),
+        }
+    }
+    match config.output_params.result_format {
+        ResultFormat::Txt => {
+            deconvolution_data.write_result_to_file(
+                filepathstr_output,
+                &fit_goodness_msg,
+                deconvolution_results,
+                desmos_function_str,
+                origin_function_str,
+                &config.fit_algorithm,
+            )?;
+        }
+        result_format @ (ResultFormat::Toml | ResultFormat::Json) => {
+            let result_toml_table = build_result_toml_table(deconvolution_data, deconvolution_results, &config.fit_algorithm, result_inputs);
+            write_result_toml_table_to_file(result_toml_table, result_format, filepathstr_output)?;
+        }
+    }
+
+    write_convolved_to_file(deconvolution_data, &deconvolution_results.params, filepathstr_output_convolved)?;
+    if verbosity.is_verbose() {
+        println!("Results written to `{filepathstr_output}` and `{filepathstr_output_convolved}`.");
+    }
+    Ok(())
+}
+
 
+fn write_convolved_to_file(deconvolution_data: &DeconvolutionData, params: &Params, filepathstr_output_convolved: &str) -> Result<()> {
     let convolved_points: Vec<float> = deconvolution_data.convolve_from_params_v(
-        &deconvolution_results.params.clone().into(),
+        &params.clone().into(),
         &Instrument(deconvolution_data.instrument.points.clone()).into(),
     ).0.data.as_vec().to_vec();
     let convolved = Spectrum {
@@ -256,7 +578,7 @@ fn output_results(
         x_start: deconvolution_data.measured.x_start,
         step: deconvolution_data.measured.step,
     };
-    convolved.write_to_file(filepathstr_output_convolved);
+    convolved.write_to_file(filepathstr_output_convolved)
 }
 
 
@@ -0,0 +1,228 @@
+//! Machine-readable result file (TOML or JSON).
+
+use std::{fs::File, io::Write};
+
+use toml::{Table as TomlTable, Value as TomlValue};
+
+use crate::{
+    deconvolution::deconvolution_data::DeconvolutionData,
+    error::{Error, Result},
+    fit_algorithms::{Fit, FitAlgorithmVariant},
+    load::Load,
+    stacktrace::Stacktrace,
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultFormat {
+    /// Human readable text, one file per each better fit.
+    Txt,
+    /// One TOML file per measured spectrum.
+    Toml,
+    /// One JSON file per measured spectrum.
+    Json,
+}
+
+impl ResultFormat {
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Self::Txt => "dat",
+            Self::Toml => "toml",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl Load for ResultFormat {
+    const TOML_NAME: &'static str = "result_format";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let result_format_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match result_format_str {
+            "txt" => Self::Txt,
+            "toml" => Self::Toml,
+            "json" => Self::Json,
+            _ => return Err(stacktrace.err_unknown_type(result_format_str, ["txt", "toml", "json"]))
+        })
+    }
+}
+
+
+/// Where result came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResultInputs<'a> {
+    pub filepathstr_instrument: &'a str,
+    pub filepathstr_instrument_stem: &'a str,
+    pub filepathstr_measured: &'a str,
+    /// `0` for initial values from config, `i` for `i`-th randomized initial values.
+    pub randomized_initial_values_i: u64,
+}
+
+
+pub fn build_result_toml_table(
+    deconvolution_data: &DeconvolutionData,
+    fit: &Fit,
+    fit_algorithm: &FitAlgorithmVariant,
+    result_inputs: &ResultInputs,
+) -> TomlTable {
+    let ResultInputs { filepathstr_instrument, filepathstr_instrument_stem, filepathstr_measured, randomized_initial_values_i } = *result_inputs;
+    let to_toml_string = |str: &str| TomlValue::String(str.to_string());
+
+    let mut inputs = TomlTable::new();
+    inputs.insert("instrument".to_string(), to_toml_string(filepathstr_instrument));
+    inputs.insert("instrument_stem".to_string(), to_toml_string(filepathstr_instrument_stem));
+    inputs.insert("measured".to_string(), to_toml_string(filepathstr_measured));
+    inputs.insert("randomized_initial_values_i".to_string(), (randomized_initial_values_i as i64).into());
+
+    let mut goodness = TomlTable::new();
+    goodness.insert("fit_residue".to_string(), fit.fit_residue.into());
+    goodness.insert("chi_square".to_string(), deconvolution_data.calc_chi_square(fit).into());
+    goodness.insert("reduced_chi_square".to_string(), deconvolution_data.calc_reduced_chi_square(fit).into());
+    if let Some(cash_statistic) = deconvolution_data.calc_cash_statistic(fit) {
+        goodness.insert("cash_statistic".to_string(), cash_statistic.into());
+    }
+    goodness.insert("r_square".to_string(), deconvolution_data.calc_r_square(fit).into());
+    goodness.insert("adjusted_r_square".to_string(), deconvolution_data.calc_adjusted_r_square(fit).into());
+    goodness.insert("fit_residue_evals".to_string(), (fit.fit_residue_evals as i64).into());
+
+    let params_names = deconvolution_data.get_params_names();
+    let initial_vads = deconvolution_data.get_initial_vads();
+    let standard_errors = fit.get_standard_errors();
+    let mut params = TomlTable::new();
+    for (i, (name, initial_vad)) in params_names.iter().zip(initial_vads).enumerate() {
+        let mut param = TomlTable::new();
+        param.insert("value".to_string(), fit.params.0[i].into());
+        if let Some(standard_errors) = &standard_errors {
+            param.insert("standard_error".to_string(), standard_errors[i].into());
+        }
+        param.insert("initial_value".to_string(), initial_vad.value.into());
+        param.insert("domain".to_string(), to_toml_string(initial_vad.get_domain_name()));
+        let (min, max) = initial_vad.get_domain_min_max();
+        if let Some(min) = min { param.insert("min".to_string(), min.into()); }
+        if let Some(max) = max { param.insert("max".to_string(), max.into()); }
+        params.insert(name.clone(), TomlValue::Table(param));
+    }
+
+    let mut toml_table = TomlTable::new();
+    toml_table.insert("model".to_string(), to_toml_string(deconvolution_data.deconvolution.get_name()));
+    toml_table.insert("params_names".to_string(), TomlValue::Array(params_names.iter().map(|name| to_toml_string(name)).collect()));
+    toml_table.insert("params".to_string(), TomlValue::Table(params));
+    if let Some(regularization_lambda) = fit.regularization_lambda {
+        toml_table.insert("regularization_lambda".to_string(), regularization_lambda.into());
+    }
+    if let Some(correlation_matrix) = fit.get_correlation_matrix() {
+        toml_table.insert("correlation_matrix".to_string(), TomlValue::Array(
+            correlation_matrix.row_iter()
+                .map(|row| TomlValue::Array(row.iter().map(|&c| c.into()).collect()))
+                .collect()
+        ));
+    }
+    toml_table.insert("goodness".to_string(), TomlValue::Table(goodness));
+    toml_table.insert("fit_algorithm".to_string(), TomlValue::Table(fit_algorithm.to_toml_table()));
+    toml_table.insert("inputs".to_string(), TomlValue::Table(inputs));
+    toml_table
+}
+
+
+/// Writes result in TOML or JSON, depending on `result_format`.
+///
+/// Must not be used with [`ResultFormat::Txt`], use [`DeconvolutionData::write_result_to_file`] for it.
+pub fn write_result_toml_table_to_file(toml_table: TomlTable, result_format: ResultFormat, filepathstr_output: &str) -> Result<()> {
+    let text: String = match result_format {
+        ResultFormat::Txt => unreachable!(),
+        ResultFormat::Toml => toml::to_string(&toml_table).expect("result table must be serializable to toml"),
+        ResultFormat::Json => toml_value_to_json(&TomlValue::Table(toml_table), 0),
+    };
+    let to_output_write_err = |io_error| Error::OutputWrite { filepath: filepathstr_output.to_string(), io_error };
+    let mut file_output = File::create(filepathstr_output).map_err(to_output_write_err)?;
+    writeln!(file_output, "{text}").map_err(to_output_write_err)
+}
+
+
+/// Pretty-printed JSON. Not finite floats are written as `null`, datetimes as strings.
+fn toml_value_to_json(toml_value: &TomlValue, indent_level: usize) -> String {
+    const INDENT: &str = "  ";
+    let indent_inner = INDENT.repeat(indent_level + 1);
+    let indent_outer = INDENT.repeat(indent_level);
+    match toml_value {
+        TomlValue::String(str) => string_to_json(str),
+        TomlValue::Integer(i) => i.to_string(),
+        TomlValue::Float(f) if f.is_finite() => f.to_string(),
+        TomlValue::Float(_) => "null".to_string(),
+        TomlValue::Boolean(b) => b.to_string(),
+        TomlValue::Datetime(datetime) => string_to_json(&datetime.to_string()),
+        TomlValue::Array(array) if array.is_empty() => "[]".to_string(),
+        TomlValue::Array(array) if array.iter().all(|v| !(v.is_array() || v.is_table())) => {
+            let items: Vec<String> = array.iter().map(|v| toml_value_to_json(v, indent_level + 1)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        TomlValue::Array(array) => {
+            let items: Vec<String> = array.iter()
+                .map(|v| format!("{indent_inner}{}", toml_value_to_json(v, indent_level + 1)))
+                .collect();
+            format!("[\n{}\n{indent_outer}]", items.join(",\n"))
+        }
+        TomlValue::Table(table) if table.is_empty() => "{}".to_string(),
+        TomlValue::Table(table) => {
+            let items: Vec<String> = table.iter()
+                .map(|(k, v)| format!("{indent_inner}{}: {}", string_to_json(k), toml_value_to_json(v, indent_level + 1)))
+                .collect();
+            format!("{{\n{}\n{indent_outer}}}", items.join(",\n"))
+        }
+    }
+}
+
+fn string_to_json(str: &str) -> String {
+    let mut json = String::with_capacity(str.len() + 2);
+    json.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+
+
+#[cfg(test)]
+mod toml_value_to_json {
+    use super::*;
+
+    #[test]
+    fn nested() {
+        let toml_value: TomlValue = toml::toml! {
+            name = "a \"b\"\\c"
+            values = [1.5, 2, true]
+            [inner]
+            x = 1e-3
+        }.into();
+        assert_eq!(
+            "{\n  \"inner\": {\n    \"x\": 0.001\n  },\n  \"name\": \"a \\\"b\\\"\\\\c\",\n  \"values\": [1.5, 2, true]\n}",
+            toml_value_to_json(&toml_value, 0),
+        );
+    }
+
+    #[test]
+    fn not_finite_float_is_null() {
+        let toml_value = TomlValue::Array(vec![TomlValue::Float(f64::NAN), TomlValue::Float(f64::INFINITY)]);
+        assert_eq!("[null, null]", toml_value_to_json(&toml_value, 0));
+    }
+
+    #[test]
+    fn array_of_arrays() {
+        let toml_value: TomlValue = toml::toml! { m = [[1, 0], [0, 1]] }.into();
+        assert_eq!(
+            "{\n  \"m\": [\n    [1, 0],\n    [0, 1]\n  ]\n}",
+            toml_value_to_json(&toml_value, 0),
+        );
+    }
+}
@@ -3,15 +3,20 @@
 use std::{
     cmp::Ordering,
     fs::File,
-    io::{BufRead, BufReader, Write},
+    io::{self, BufRead, BufReader, Write},
 };
 
 use crate::{
+    error::{Error, Result},
     types::float::float,
     unmut,
 };
 
 
+/// Spectrum and sigmas of its points, if there are any.
+pub type SpectrumWithSigmas = (Spectrum, Option<Vec<float>>);
+
+
 // TODO(refactor): make it generic for named wrappers.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Spectrum {
@@ -85,11 +90,12 @@ impl Spectrum {
         self
     }
 
-    pub fn write_to_file(&self, filename: &str) {
-        self.write_to_file_with_separators(filename, ".", "\t");
+    pub fn write_to_file(&self, filepath: &str) -> Result<()> {
+        self.write_to_file_with_separators(filepath, ".", "\t")
     }
-    pub fn write_to_file_with_separators(&self, filepath: &str, decimal_point_symbol: &str, numbers_separator: &str) {
-        let mut file_output = File::create(filepath).unwrap();
+    pub fn write_to_file_with_separators(&self, filepath: &str, decimal_point_symbol: &str, numbers_separator: &str) -> Result<()> {
+        let to_output_write_err = |io_error: io::Error| Error::OutputWrite { filepath: filepath.to_string(), io_error };
+        let mut file_output = File::create(filepath).map_err(to_output_write_err)?;
         for i in 0..self.points.len() {
             let (x, y) = self.get_xy_from_index(i);
             writeln!(
@@ -97,19 +103,26 @@ impl Spectrum {
                 "{x}{numbers_separator}{y}",
                 x=format!("{x}").replace('.', decimal_point_symbol),
                 y=format!("{y}").replace('.', decimal_point_symbol),
-            ).unwrap();
+            ).map_err(to_output_write_err)?;
         }
+        Ok(())
     }
 
-    pub fn load_from_file_as_instrumental(filename: &str, max_step_relative_diff: float) -> Self {
-        let mut self_ = Self::load_from_file(filename, max_step_relative_diff);
+    pub fn load_from_file_as_instrumental(filepath: &str, max_step_relative_diff: float) -> Result<Self> {
+        let mut self_ = Self::load_from_file(filepath, max_step_relative_diff)?;
+        if self_.points.iter().all(|&v| v == 0.) {
+            return Err(Error::SpectrumInvalid {
+                filepath: filepath.to_string(),
+                msg: "instrumental function must have at least one non zero".to_string(),
+            });
+        }
         self_.trim_zeros();
         self_.pad_zeros();
         if self_.points.len() % 2 != 1 { unreachable!() }
         // dbg!(self_.points.len() / 2);
         // dbg!(Self::avg_index_of_max(&self_.points));
         if self_.points.len() / 2 != Self::avg_index_of_max(&self_.points) as usize { unreachable!() }
-        self_
+        Ok(self_)
     }
     fn trim_zeros(&mut self) {
         let index_of_first_non_zero: usize = self.points
@@ -172,28 +185,44 @@ impl Spectrum {
         };
     }
 
-    pub fn load_from_file(filename: &str, max_step_relative_diff: float) -> Self {
-        Self::try_load_from_file(filename, max_step_relative_diff).unwrap()
+    pub fn load_from_file(filepath: &str, max_step_relative_diff: float) -> Result<Self> {
+        Self::load_from_file_with_sigmas(filepath, max_step_relative_diff)
+            .map(|(spectrum, _sigmas)| spectrum)
     }
-    pub fn try_load_from_file(filename: &str, max_step_relative_diff: float) -> Result<Self, String> {
-        let file = File::open(filename).map_err(|_| format!("Unable to open file `{filename}`."))?;
+    /// Same as [`load_from_file`](Spectrum::load_from_file),
+    /// but also returns sigmas (uncertainties) of points, if file have third column with them.
+    pub fn load_from_file_with_sigmas(filepath: &str, max_step_relative_diff: float) -> Result<SpectrumWithSigmas> {
+        let file = File::open(filepath)
+            .map_err(|io_error| Error::SpectrumRead { filepath: filepath.to_string(), io_error })?;
+        Self::load_from_reader(BufReader::new(file), max_step_relative_diff)
+            .map_err(|(line_number, msg)| match line_number {
+                Some(line_number) => Error::SpectrumParse { filepath: filepath.to_string(), line_number, msg },
+                None => Error::SpectrumInvalid { filepath: filepath.to_string(), msg },
+            })
+    }
+    /// Error is line number (starting from 1) and message,
+    /// line number is `None` if error isn't in some specific line.
+    ///
+    /// Sigmas (third column) must be in all lines or in none of them.
+    fn load_from_reader(reader: impl BufRead, max_step_relative_diff: float) -> Result<SpectrumWithSigmas, (Option<usize>, String)> {
         let mut x_start: Option<float> = None;
         let mut x_prev: Option<float> = None;
         let mut step: Option<float> = None;
         let mut ys: Vec<float> = vec![];
-        let lines = BufReader::new(file).lines();
-        for (line_number, line) in lines.into_iter().enumerate() {
-            let line = line.map_err(|_| format!("line#{line_number}: Unable to unwrap line."))?;
+        let mut sigmas: Vec<float> = vec![];
+        for (line_index, line) in reader.lines().enumerate() {
+            let line_number = Some(line_index + 1);
+            let line = line.map_err(|io_error| (line_number, format!("unable to read line: {io_error}")))?;
             let line = line.trim();
-            if line == "" { continue }
+            if line.is_empty() { continue }
             let (x, y) = line
                 .split_once([' ', '\t'])
-                .ok_or(format!("line#{line_number}: Unable to split line at space or tab."))?;
+                .ok_or_else(|| (line_number, "unable to split line at space or tab".to_string()))?;
             let x = x
                 .trim()
                 .replace(',', ".")
                 .parse::<float>()
-                .map_err(|_| format!("line#{line_number}: Unable to parse `x`=`{x}`."))?;
+                .map_err(|_| (line_number, format!("unable to parse `x`=`{x}`")))?;
             match x_start {
                 None => {
                     x_start = Some(x);
@@ -207,33 +236,55 @@ impl Spectrum {
                             let this_step = x - x_prev.unwrap();
                             let step_relative_diff = (this_step - step).abs() / step.abs();
                             if step_relative_diff > max_step_relative_diff {
-                                return Err(format!(
+                                return Err((line_number, format!(
                                     "\
-                                    line#{line_number}: expected `this_step` to be close enough to `step`:\n\
+                                    expected `this_step` to be close enough to `step`:\n\
                                     `step_relative_diff` = `|this_step - step| / step` < `max_step_relative_diff`={max_step_relative_diff},\n\
-                                    but step={step}, this_step={this_step} => step_relative_diff={step_relative_diff}.\
+                                    but step={step}, this_step={this_step} => step_relative_diff={step_relative_diff}\
                                     "
-                                ));
+                                )));
                             }
                         }
                     }
                     x_prev = Some(x);
                 }
             }
+            let (y, sigma) = match y.trim().split_once([' ', '\t']) {
+                Some((y, sigma)) => (y, Some(sigma)),
+                None => (y, None),
+            };
             let y = y
                 .trim()
                 .replace(',', ".")
                 .parse()
-                .map_err(|_| format!("line#{line_number}: Unable to parse `y`=`{y}`"))?;
+                .map_err(|_| (line_number, format!("unable to parse `y`=`{y}`")))?;
+            if let Some(sigma) = sigma {
+                if sigmas.len() != ys.len() {
+                    return Err((line_number, "sigma found, but previous lines don't have it".to_string()));
+                }
+                let sigma = sigma
+                    .trim()
+                    .replace(',', ".")
+                    .parse::<float>()
+                    .map_err(|_| (line_number, format!("unable to parse `sigma`=`{sigma}`")))?;
+                if !(sigma > 0. && sigma.is_finite()) {
+                    return Err((line_number, format!("expected `sigma` to be positive, but it is `{sigma}`")));
+                }
+                sigmas.push(sigma);
+            } else if !sigmas.is_empty() {
+                return Err((line_number, "sigma not found, but previous lines have it".to_string()));
+            }
             ys.push(y);
         }
-        let x_start = x_start.ok_or("`x_start` is None")?;
-        let step = step.ok_or("`step` is None")?;
-        Ok(Spectrum {
+        let x_start = x_start.ok_or((None, "no points found".to_string()))?;
+        let step = step.ok_or((None, "at least two points required".to_string()))?;
+        let spectrum = Spectrum {
             points: ys,
             x_start,
             step,
-        })
+        };
+        let sigmas = if sigmas.is_empty() { None } else { Some(sigmas) };
+        Ok((spectrum, sigmas))
     }
 }
 
@@ -1756,3 +1807,56 @@ mod spectrum {
     }
 }
 
+
+#[cfg(test)]
+mod load_from_reader {
+    use super::Spectrum;
+
+    #[test]
+    fn ok() {
+        assert_eq!(
+            Ok((Spectrum { points: vec![0.5, 1.5, 2.5], step: 0.5, x_start: 1. }, None)),
+            Spectrum::load_from_reader("1 0.5\n\n1,5\t1,5\n2 2.5\n".as_bytes(), 0.01)
+        );
+    }
+
+    #[test]
+    fn with_sigmas() {
+        assert_eq!(
+            Ok((Spectrum { points: vec![0.5, 1.5, 2.5], step: 0.5, x_start: 1. }, Some(vec![0.1, 0.2, 0.3]))),
+            Spectrum::load_from_reader("1 0.5 0.1\n1,5\t1,5\t0,2\n2 2.5  0.3\n".as_bytes(), 0.01)
+        );
+    }
+
+    #[test]
+    fn sigmas_not_in_all_lines() {
+        assert_eq!(
+            Err((Some(3), "sigma not found, but previous lines have it".to_string())),
+            Spectrum::load_from_reader("1 0.5 0.1\n1.5 1.5 0.2\n2 2.5\n".as_bytes(), 0.01)
+        );
+    }
+
+    #[test]
+    fn zero_sigma() {
+        assert_eq!(
+            Err((Some(2), "expected `sigma` to be positive, but it is `0`".to_string())),
+            Spectrum::load_from_reader("1 0.5 0.1\n1.5 1.5 0\n".as_bytes(), 0.01)
+        );
+    }
+
+    #[test]
+    fn cant_parse_y() {
+        assert_eq!(
+            Err((Some(3), "unable to parse `y`=`abc`".to_string())),
+            Spectrum::load_from_reader("1 0.5\n1.5 1.5\n2 abc\n".as_bytes(), 0.01)
+        );
+    }
+
+    #[test]
+    fn one_point() {
+        assert_eq!(
+            Err((None, "at least two points required".to_string())),
+            Spectrum::load_from_reader("1 0.5\n".as_bytes(), 0.01)
+        );
+    }
+}
@@ -1,5 +1,7 @@
 //! Error stacktrace
 
+use crate::error::Error;
+
 
 
 #[derive(Debug, Clone)]
@@ -35,49 +37,48 @@ impl Stacktrace {
     //     self.stack.pop()
     // }
 
-    pub fn panic(&self, final_msg: &str) -> ! {
+    pub fn err(&self, final_msg: &str) -> Error {
         let stack_str = self.stack
             .iter()
             .map(|s| format!("`{s}`"))
             .collect::<Vec<String>>()
             .join(" -> ");
-        panic!("{stack_str}: {final_msg}")
+        Error::ConfigValue { path: stack_str, msg: final_msg.to_string() }
     }
 
-    pub fn panic_not_found(&self) -> ! {
-        self.panic("not found")
+    pub fn err_not_found(&self) -> Error {
+        self.err("not found")
     }
 
-    pub fn panic_cant_parse_as(&self, type_: &str) -> ! {
-        self.panic(&format!("can't parse as {type_}"))
+    pub fn err_cant_parse_as(&self, type_: &str) -> Error {
+        self.err(&format!("can't parse as {type_}"))
     }
 
-    pub fn panic_unknown_type_without_value<const N: usize>(&self, known_types_arr: [&str; N]) -> ! {
+    pub fn err_unknown_type_without_value<const N: usize>(&self, known_types_arr: [&str; N]) -> Error {
         let known_types_str = known_types_arr
             .iter()
             .map(|t| format!("`{t}`"))
             .collect::<Vec<_>>()
             .join(", ");
-        self.panic(&format!("unkown type, known types: [{known_types_str}]"))
+        self.err(&format!("unkown type, known types: [{known_types_str}]"))
     }
 
-    pub fn panic_unknown_type<const N: usize>(&self, value: &str, known_types_arr: [&str; N]) -> ! {
+    pub fn err_unknown_type<const N: usize>(&self, value: &str, known_types_arr: [&str; N]) -> Error {
         let known_types_str = known_types_arr
             .iter()
             .map(|t| format!("`{t}`"))
             .collect::<Vec<_>>()
             .join(", ");
-        self.panic(&format!("unkown type: `{value}`, known types: [{known_types_str}]"))
+        self.err(&format!("unkown type: `{value}`, known types: [{known_types_str}]"))
     }
 
-    pub fn panic_more_than_one_found(&self, found_values_vec: Vec<&str>) -> ! {
+    pub fn err_more_than_one_found(&self, found_values_vec: Vec<&str>) -> Error {
         let found_values_str = found_values_vec
             .iter()
             .map(|v| format!("`{v}`"))
             .collect::<Vec<_>>()
             .join(", ");
         let stacktrace_last_name = self.stack.last().unwrap();
-        self.panic(&format!("more than one `{stacktrace_last_name}` found: [{found_values_str}]"))
+        self.err(&format!("more than one `{stacktrace_last_name}` found: [{found_values_str}]"))
     }
 }
-
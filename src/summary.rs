@@ -0,0 +1,196 @@
+//! Batch summary: one row per measured file.
+
+use std::{fs::File, io::Write};
+
+use toml::Value as TomlValue;
+
+use crate::{
+    deconvolution::deconvolution_data::DeconvolutionData,
+    error::{Error, Result},
+    fit_algorithms::Fit,
+    load::Load,
+    stacktrace::Stacktrace,
+    types::float::float,
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryFormat {
+    Csv,
+    Tsv,
+}
+
+impl SummaryFormat {
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+        }
+    }
+
+    fn get_separator(&self) -> char {
+        match self {
+            Self::Csv => ',',
+            Self::Tsv => '\t',
+        }
+    }
+}
+
+impl Load for SummaryFormat {
+    const TOML_NAME: &'static str = "summary_format";
+    fn load_from_self(toml_value: &TomlValue, stacktrace: &Stacktrace) -> Result<Self> {
+        let summary_format_str = toml_value
+            .as_str()
+            .ok_or_else(|| stacktrace.err_cant_parse_as("string"))?;
+        Ok(match summary_format_str {
+            "csv" => Self::Csv,
+            "tsv" => Self::Tsv,
+            _ => return Err(stacktrace.err_unknown_type(summary_format_str, ["csv", "tsv"]))
+        })
+    }
+}
+
+
+/// Best fit of one measured file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryFit {
+    params_names: Vec<String>,
+    params: Vec<float>,
+    fit_residue: float,
+    reduced_chi_square: float,
+    r_square: float,
+    adjusted_r_square: float,
+    /// `0` for initial values from config, `i` for `i`-th randomized initial values (`_rivN` in filenames).
+    randomized_initial_values_i: u64,
+}
+
+impl SummaryFit {
+    pub fn new(deconvolution_data: &DeconvolutionData, fit: &Fit, randomized_initial_values_i: u64) -> Self {
+        Self {
+            params_names: deconvolution_data.get_params_names(),
+            params: fit.params.0.clone(),
+            fit_residue: fit.fit_residue,
+            reduced_chi_square: deconvolution_data.calc_reduced_chi_square(fit),
+            r_square: deconvolution_data.calc_r_square(fit),
+            adjusted_r_square: deconvolution_data.calc_adjusted_r_square(fit),
+            randomized_initial_values_i,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryRow {
+    pub filepathstr_measured: String,
+    /// `None` if file can't be processed or all fits failed.
+    pub summary_fit: Option<SummaryFit>,
+}
+
+
+/// Params columns are taken from the first row with fit.
+/// Params of rows with other params names (e.g. [`PerPoint`](crate::deconvolution::types::per_points::PerPoint) with other points number) are left empty.
+fn build_summary(summary_rows: &[SummaryRow], summary_format: SummaryFormat) -> String {
+    let separator = summary_format.get_separator();
+    let escape = |field: &str| -> String {
+        if field.contains([separator, '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+    let params_names: &[String] = summary_rows.iter()
+        .find_map(|summary_row| summary_row.summary_fit.as_ref())
+        .map(|summary_fit| summary_fit.params_names.as_slice())
+        .unwrap_or(&[]);
+    let header: Vec<String> = ["measured"].into_iter()
+        .map(|s| s.to_string())
+        .chain(params_names.iter().cloned())
+        .chain(["fit_residue", "reduced_chi_square", "r_square", "adjusted_r_square", "best_riv"].map(|s| s.to_string()))
+        .collect();
+    let mut lines: Vec<String> = vec![
+        header.iter().map(|s| escape(s)).collect::<Vec<_>>().join(&separator.to_string())
+    ];
+    for SummaryRow { filepathstr_measured, summary_fit } in summary_rows {
+        let mut fields: Vec<String> = vec![filepathstr_measured.clone()];
+        match summary_fit {
+            Some(summary_fit) => {
+                if summary_fit.params_names == params_names {
+                    fields.extend(summary_fit.params.iter().map(|p| p.to_string()));
+                } else {
+                    fields.extend(params_names.iter().map(|_| String::new()));
+                }
+                fields.extend([
+                    summary_fit.fit_residue,
+                    summary_fit.reduced_chi_square,
+                    summary_fit.r_square,
+                    summary_fit.adjusted_r_square,
+                ].map(|v| v.to_string()));
+                fields.push(summary_fit.randomized_initial_values_i.to_string());
+            }
+            None => {
+                fields.extend((1..header.len()).map(|_| String::new()));
+            }
+        }
+        lines.push(fields.iter().map(|s| escape(s)).collect::<Vec<_>>().join(&separator.to_string()));
+    }
+    lines.join("\n")
+}
+
+pub fn write_summary_to_file(summary_rows: &[SummaryRow], summary_format: SummaryFormat, filepathstr_output: &str) -> Result<()> {
+    let to_output_write_err = |io_error| Error::OutputWrite { filepath: filepathstr_output.to_string(), io_error };
+    let mut file_output = File::create(filepathstr_output).map_err(to_output_write_err)?;
+    writeln!(file_output, "{}", build_summary(summary_rows, summary_format)).map_err(to_output_write_err)
+}
+
+
+
+#[cfg(test)]
+mod build_summary {
+    use super::*;
+
+    fn summary_fit(params: Vec<float>, randomized_initial_values_i: u64) -> SummaryFit {
+        SummaryFit {
+            params_names: (0..params.len()).map(|i| format!("p{i}")).collect(),
+            params,
+            fit_residue: 0.5,
+            reduced_chi_square: 0.25,
+            r_square: 0.9,
+            adjusted_r_square: 0.8,
+            randomized_initial_values_i,
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let summary_rows = vec![
+            SummaryRow { filepathstr_measured: "a.dat".to_string(), summary_fit: Some(summary_fit(vec![1., 2.], 0)) },
+            SummaryRow { filepathstr_measured: "b,c.dat".to_string(), summary_fit: None },
+            SummaryRow { filepathstr_measured: "d.dat".to_string(), summary_fit: Some(summary_fit(vec![3., 4.], 7)) },
+        ];
+        assert_eq!(
+            [
+                "measured,p0,p1,fit_residue,reduced_chi_square,r_square,adjusted_r_square,best_riv",
+                "a.dat,1,2,0.5,0.25,0.9,0.8,0",
+                "\"b,c.dat\",,,,,,,",
+                "d.dat,3,4,0.5,0.25,0.9,0.8,7",
+            ].join("\n"),
+            build_summary(&summary_rows, SummaryFormat::Csv)
+        );
+    }
+
+    #[test]
+    fn tsv_with_other_params_names() {
+        let summary_rows = vec![
+            SummaryRow { filepathstr_measured: "a.dat".to_string(), summary_fit: Some(summary_fit(vec![1.], 2)) },
+            SummaryRow { filepathstr_measured: "b.dat".to_string(), summary_fit: Some(summary_fit(vec![1., 2.], 0)) },
+        ];
+        assert_eq!(
+            [
+                "measured\tp0\tfit_residue\treduced_chi_square\tr_square\tadjusted_r_square\tbest_riv",
+                "a.dat\t1\t0.5\t0.25\t0.9\t0.8\t2",
+                "b.dat\t\t0.5\t0.25\t0.9\t0.8\t0",
+            ].join("\n"),
+            build_summary(&summary_rows, SummaryFormat::Tsv)
+        );
+    }
+}
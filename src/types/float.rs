@@ -3,3 +3,5 @@
 #[allow(non_camel_case_types)]
 pub type float = f64;
 
+/// Constants of [`float`] type.
+pub use std::f64::consts;
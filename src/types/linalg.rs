@@ -1,11 +1,12 @@
 //! Linear Algebra Types.
 
-use nalgebra::{DVector, DVectorView};
+use nalgebra::{DMatrix, DVector, DVectorView};
 
 use crate::types::float::float;
 
 
 pub type DVect = DVector<float>;
+pub type DMat = DMatrix<float>;
 
 
 pub trait Reversed {
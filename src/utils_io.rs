@@ -76,7 +76,9 @@ pub fn format_by_dollar_str(str_to_fmt: &str, params: Vec<(&str, &str)>) -> Stri
             Some(index_to_insert) => {
                 let max_len: usize = (0..MAX_PARAM_NAME_LEN).rev()
                     .find(|len| {
-                        params_hm.get(str_fmted.chars().skip(index_to_insert+1).take(len+1).collect::<String>().as_str()).is_some()
+                        let param_name: String = str_fmted.chars().skip(index_to_insert+1).take(len+1).collect();
+                        // near the end of string there are less than `len+1` chars:
+                        param_name.chars().count() == len+1 && params_hm.get(param_name.as_str()).is_some()
                     })
                     .unwrap();
                 let param_name: String = str_fmted.chars().skip(index_to_insert+1).take(max_len+1).collect::<String>();
@@ -147,3 +149,27 @@ fn format_by_dollar_str_() {
     );
 }
 
+
+#[test]
+fn format_by_dollar_str__() {
+    assert_eq!(
+        "abc { 42 + 3.14 }",
+        format_by_dollar_str(
+            "abc { $f + $ft }",
+            vec![
+                ("f", "42"),
+                ("ft", "3.14"),
+            ]
+        )
+    );
+    // param at the end:
+    assert_eq!(
+        "y=42",
+        format_by_dollar_str(
+            "y=$f",
+            vec![
+                ("f", "42"),
+            ]
+        )
+    );
+}